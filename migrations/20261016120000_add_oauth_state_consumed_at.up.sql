@@ -0,0 +1,2 @@
+ALTER TABLE oauth_states
+    ADD COLUMN consumed_at timestamptz;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_user_links_guild_id;
+
+ALTER TABLE user_links
+    DROP COLUMN IF EXISTS guild_id;
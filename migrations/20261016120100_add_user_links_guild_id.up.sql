@@ -0,0 +1,16 @@
+ALTER TABLE user_links
+    ADD COLUMN guild_id uuid REFERENCES allowed_guilds (id) ON DELETE SET NULL;
+
+CREATE INDEX idx_user_links_guild_id ON user_links (guild_id);
+
+UPDATE
+    user_links
+SET
+    guild_id = (
+        SELECT
+            id
+        FROM
+            allowed_guilds
+        WHERE
+            name = 'Server do Felpinho'
+        LIMIT 1);
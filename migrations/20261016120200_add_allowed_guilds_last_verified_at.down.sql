@@ -0,0 +1,2 @@
+ALTER TABLE allowed_guilds
+    DROP COLUMN IF EXISTS last_verified_at;
@@ -0,0 +1,2 @@
+ALTER TABLE allowed_guilds
+    ADD COLUMN last_verified_at timestamptz;
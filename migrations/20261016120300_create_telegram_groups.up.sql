@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS telegram_groups (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    allowed_guild_id uuid NOT NULL REFERENCES allowed_guilds (id) ON DELETE CASCADE,
+    telegram_group_id bigint NOT NULL UNIQUE,
+    name varchar(255) NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    updated_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_telegram_groups_allowed_guild_id ON telegram_groups (allowed_guild_id);
@@ -0,0 +1,3 @@
+ALTER TABLE telegram_groups
+    DROP COLUMN IF EXISTS title,
+    DROP COLUMN IF EXISTS description;
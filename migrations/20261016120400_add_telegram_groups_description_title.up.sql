@@ -0,0 +1,3 @@
+ALTER TABLE telegram_groups
+    ADD COLUMN description text,
+    ADD COLUMN title varchar(128);
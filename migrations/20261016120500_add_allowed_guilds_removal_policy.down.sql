@@ -0,0 +1,4 @@
+ALTER TABLE allowed_guilds
+    DROP COLUMN IF EXISTS removal_policy;
+
+DROP TYPE IF EXISTS removal_policy;
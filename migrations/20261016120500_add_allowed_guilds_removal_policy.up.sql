@@ -0,0 +1,4 @@
+CREATE TYPE removal_policy AS ENUM ('kick', 'notify_only');
+
+ALTER TABLE allowed_guilds
+    ADD COLUMN removal_policy removal_policy NOT NULL DEFAULT 'kick';
@@ -0,0 +1,5 @@
+CREATE TABLE feature_flags (
+    name varchar(64) PRIMARY KEY,
+    enabled boolean NOT NULL DEFAULT false,
+    updated_at timestamptz NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+ALTER TABLE telegram_groups
+    DROP COLUMN IF EXISTS invite_message;
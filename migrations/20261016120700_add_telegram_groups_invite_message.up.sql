@@ -0,0 +1,2 @@
+ALTER TABLE telegram_groups
+    ADD COLUMN invite_message text;
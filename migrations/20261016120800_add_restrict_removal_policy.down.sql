@@ -0,0 +1,22 @@
+ALTER TABLE user_links
+    DROP COLUMN IF EXISTS restricted_at;
+
+-- Postgres can't drop a value from an enum, so the type is recreated without it
+UPDATE
+    allowed_guilds
+SET
+    removal_policy = 'kick'
+WHERE
+    removal_policy = 'restrict';
+
+ALTER TYPE removal_policy RENAME TO removal_policy_old;
+
+CREATE TYPE removal_policy AS ENUM ('kick', 'notify_only');
+
+ALTER TABLE allowed_guilds
+    ALTER COLUMN removal_policy DROP DEFAULT,
+    ALTER COLUMN removal_policy TYPE removal_policy
+    USING removal_policy::text::removal_policy,
+    ALTER COLUMN removal_policy SET DEFAULT 'kick';
+
+DROP TYPE removal_policy_old;
@@ -0,0 +1,4 @@
+ALTER TYPE removal_policy ADD VALUE IF NOT EXISTS 'restrict';
+
+ALTER TABLE user_links
+    ADD COLUMN restricted_at timestamptz;
@@ -0,0 +1,9 @@
+CREATE TABLE audit_log (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    actor_discord_id bigint,
+    action varchar(64) NOT NULL,
+    details jsonb NOT NULL DEFAULT '{}',
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_created_at ON audit_log (created_at);
@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS user_links_active_telegram_id_key;
+
+DROP INDEX IF EXISTS user_links_active_discord_id_key;
+
+DELETE FROM user_links
+WHERE deleted_at IS NOT NULL;
+
+ALTER TABLE user_links
+    ADD CONSTRAINT user_links_discord_id_key UNIQUE (discord_id),
+    ADD CONSTRAINT user_links_telegram_id_key UNIQUE (telegram_id);
+
+ALTER TABLE user_links
+    DROP COLUMN IF EXISTS deleted_at;
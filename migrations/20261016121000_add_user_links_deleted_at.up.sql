@@ -0,0 +1,15 @@
+ALTER TABLE user_links
+    ADD COLUMN deleted_at timestamptz;
+
+-- Removed links are kept around, so the ids only have to be unique among the active ones
+ALTER TABLE user_links
+    DROP CONSTRAINT user_links_discord_id_key,
+    DROP CONSTRAINT user_links_telegram_id_key;
+
+CREATE UNIQUE INDEX user_links_active_discord_id_key ON user_links (discord_id)
+WHERE
+    deleted_at IS NULL;
+
+CREATE UNIQUE INDEX user_links_active_telegram_id_key ON user_links (telegram_id)
+WHERE
+    deleted_at IS NULL;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS role_group_mappings (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    role_id uuid NOT NULL UNIQUE REFERENCES allowed_roles (id) ON DELETE CASCADE,
+    group_id uuid NOT NULL REFERENCES telegram_groups (id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    updated_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_role_group_mappings_group_id ON role_group_mappings (group_id);
@@ -0,0 +1,5 @@
+ALTER TABLE user_links
+    DROP COLUMN discord_access_token,
+    DROP COLUMN discord_refresh_token,
+    DROP COLUMN discord_token_expires_at,
+    DROP COLUMN discord_roles;
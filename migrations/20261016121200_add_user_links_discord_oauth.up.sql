@@ -0,0 +1,5 @@
+ALTER TABLE user_links
+    ADD COLUMN discord_access_token text,
+    ADD COLUMN discord_refresh_token text,
+    ADD COLUMN discord_token_expires_at timestamptz,
+    ADD COLUMN discord_roles bigint[];
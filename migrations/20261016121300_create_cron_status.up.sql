@@ -0,0 +1,5 @@
+-- Single row table, the check keeps a second row from ever being inserted
+CREATE TABLE cron_status (
+    id boolean PRIMARY KEY DEFAULT true CHECK (id),
+    last_successful_cycle_at timestamptz NOT NULL
+);
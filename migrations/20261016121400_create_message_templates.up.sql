@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS message_templates (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    name varchar(50) NOT NULL,
+    content text NOT NULL,
+    -- Templates without a guild apply to every guild that doesn't override them
+    guild_id uuid REFERENCES allowed_guilds (id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    updated_at timestamptz NOT NULL DEFAULT NOW(),
+    UNIQUE NULLS NOT DISTINCT (name, guild_id)
+);
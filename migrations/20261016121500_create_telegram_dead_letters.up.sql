@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS telegram_dead_letters (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    action_kind varchar(20) NOT NULL,
+    -- Broadcasts are not tied to a user
+    telegram_id bigint,
+    group_id bigint NOT NULL,
+    last_error text NOT NULL,
+    failed_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_telegram_dead_letters_failed_at ON telegram_dead_letters (failed_at DESC);
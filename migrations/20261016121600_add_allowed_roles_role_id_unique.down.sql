@@ -0,0 +1 @@
+ALTER TABLE allowed_roles DROP CONSTRAINT IF EXISTS allowed_roles_role_id_key;
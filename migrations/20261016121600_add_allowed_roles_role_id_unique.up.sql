@@ -0,0 +1,35 @@
+-- Role ids were only kept unique by the bot checking before inserting, rows added by hand may
+-- have repeated them. The oldest row of each role is kept
+
+-- Mappings of the removed rows would be dropped with them by the cascade. A role can only have
+-- one mapping, so the oldest mapping among the repeated rows is kept and moved to the oldest row
+DELETE FROM role_group_mappings WHERE id IN (
+    SELECT mapping_id FROM (
+        SELECT
+            m.id AS mapping_id,
+            row_number() OVER (
+                PARTITION BY r.role_id
+                ORDER BY m.created_at, m.id
+            ) AS position
+        FROM role_group_mappings m
+        JOIN allowed_roles r ON r.id = m.role_id
+    ) mappings
+    WHERE position > 1
+);
+
+UPDATE role_group_mappings m SET role_id = keeper.id
+FROM allowed_roles r, allowed_roles keeper
+WHERE m.role_id = r.id
+    AND keeper.role_id = r.role_id
+    AND (keeper.created_at, keeper.id) < (r.created_at, r.id)
+    AND NOT EXISTS (
+        SELECT 1 FROM allowed_roles older
+        WHERE older.role_id = keeper.role_id
+            AND (older.created_at, older.id) < (keeper.created_at, keeper.id)
+    );
+
+DELETE FROM allowed_roles newer USING allowed_roles older
+WHERE newer.role_id = older.role_id
+    AND (newer.created_at, newer.id) > (older.created_at, older.id);
+
+ALTER TABLE allowed_roles ADD CONSTRAINT allowed_roles_role_id_key UNIQUE (role_id);
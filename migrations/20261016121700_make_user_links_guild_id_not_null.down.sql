@@ -0,0 +1,6 @@
+ALTER TABLE user_links
+    DROP CONSTRAINT user_links_guild_id_fkey,
+    ADD CONSTRAINT user_links_guild_id_fkey FOREIGN KEY (guild_id) REFERENCES allowed_guilds (id) ON DELETE SET NULL;
+
+ALTER TABLE user_links
+    ALTER COLUMN guild_id DROP NOT NULL;
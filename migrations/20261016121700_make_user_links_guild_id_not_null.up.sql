@@ -0,0 +1,18 @@
+-- Links made after guild_id was added were never given a guild, they were all made through the
+-- default guild, or the oldest one when it doesn't exist
+UPDATE
+    user_links
+SET
+    guild_id = COALESCE(
+        (SELECT id FROM allowed_guilds WHERE name = 'Server do Felpinho' LIMIT 1),
+        (SELECT id FROM allowed_guilds ORDER BY created_at LIMIT 1))
+WHERE
+    guild_id IS NULL;
+
+ALTER TABLE user_links
+    ALTER COLUMN guild_id SET NOT NULL;
+
+-- A link can't outlive its guild anymore, removing a guild removes its links
+ALTER TABLE user_links
+    DROP CONSTRAINT user_links_guild_id_fkey,
+    ADD CONSTRAINT user_links_guild_id_fkey FOREIGN KEY (guild_id) REFERENCES allowed_guilds (id) ON DELETE CASCADE;
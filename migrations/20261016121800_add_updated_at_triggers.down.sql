@@ -0,0 +1,7 @@
+DROP TRIGGER IF EXISTS trg_set_updated_at ON allowed_channels;
+DROP TRIGGER IF EXISTS trg_set_updated_at ON allowed_guilds;
+DROP TRIGGER IF EXISTS trg_set_updated_at ON allowed_roles;
+DROP TRIGGER IF EXISTS trg_set_updated_at ON feature_flags;
+DROP TRIGGER IF EXISTS trg_set_updated_at ON message_templates;
+DROP TRIGGER IF EXISTS trg_set_updated_at ON role_group_mappings;
+DROP TRIGGER IF EXISTS trg_set_updated_at ON telegram_groups;
@@ -0,0 +1,36 @@
+-- The setup migration only added the trigger to the tables that existed back then, the tables
+-- created after it had updated_at set by hand in each query
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON allowed_channels
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON allowed_guilds
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON allowed_roles
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON feature_flags
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON message_templates
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON role_group_mappings
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER trg_set_updated_at
+    BEFORE UPDATE ON telegram_groups
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
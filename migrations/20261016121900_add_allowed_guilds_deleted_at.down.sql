@@ -0,0 +1,5 @@
+DELETE FROM allowed_guilds
+WHERE deleted_at IS NOT NULL;
+
+ALTER TABLE allowed_guilds
+    DROP COLUMN deleted_at;
@@ -0,0 +1,4 @@
+-- Removed guilds are kept so the links of their users, soft deleted along with them, are not
+-- deleted by the cascade
+ALTER TABLE allowed_guilds
+    ADD COLUMN deleted_at timestamptz;
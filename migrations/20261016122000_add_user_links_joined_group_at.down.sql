@@ -0,0 +1,2 @@
+ALTER TABLE user_links
+    DROP COLUMN joined_group_at;
@@ -0,0 +1,13 @@
+-- `added_to_group_at` is set when the invite is sent, joining the group is tracked on its own so
+-- links of users that never accepted their invite can be told apart
+ALTER TABLE user_links
+    ADD COLUMN joined_group_at timestamptz;
+
+-- Joins were not tracked before, invited users are assumed to have joined so they are never
+-- cleaned up as pending
+UPDATE
+    user_links
+SET
+    joined_group_at = added_to_group_at
+WHERE
+    added_to_group_at IS NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE user_links
+    DROP COLUMN telegram_group_id;
@@ -0,0 +1,4 @@
+-- Users are invited to the group mapped to their role, which isn't always the guild's group, so
+-- the group each user was invited to is kept to act on it later
+ALTER TABLE user_links
+    ADD COLUMN telegram_group_id bigint;
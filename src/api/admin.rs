@@ -0,0 +1,846 @@
+use std::sync::atomic::Ordering;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use sqlx::types::Uuid;
+
+use super::AppState;
+use super::error::{ApiError, Result};
+use crate::database::migrations::{self, MigrationRecord};
+use crate::database::models::{
+    AllowedChannel, AllowedGuild, AllowedRole, FeatureFlag, UserLink, UserLinkUpdatePayload,
+};
+use crate::services::discord::DiscordService;
+use crate::utils::pagination::{CursorPage, CursorParams, Page, PaginationParams};
+
+// Discord and Telegram ids are serialized as strings since they don't fit in a JS number
+#[derive(Debug, Serialize)]
+pub struct GuildDto {
+    id: String,
+    guild_id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<AllowedGuild> for GuildDto {
+    fn from(guild: AllowedGuild) -> Self {
+        Self {
+            id: guild.id.to_string(),
+            guild_id: guild.guild_id.to_string(),
+            name: guild.name,
+            created_at: guild.created_at,
+            updated_at: guild.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleDto {
+    id: String,
+    role_id: String,
+    name: String,
+    is_admin: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<AllowedRole> for RoleDto {
+    fn from(role: AllowedRole) -> Self {
+        Self {
+            id: role.id.to_string(),
+            role_id: role.role_id.to_string(),
+            name: role.name,
+            is_admin: role.is_admin,
+            created_at: role.created_at,
+            updated_at: role.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelDto {
+    id: String,
+    channel_id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<AllowedChannel> for ChannelDto {
+    fn from(channel: AllowedChannel) -> Self {
+        Self {
+            id: channel.id.to_string(),
+            channel_id: channel.channel_id.to_string(),
+            name: channel.name,
+            created_at: channel.created_at,
+            updated_at: channel.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberDto {
+    id: String,
+    discord_id: String,
+    telegram_id: String,
+    created_at: DateTime<Utc>,
+    added_to_group_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserLink> for MemberDto {
+    fn from(user: UserLink) -> Self {
+        Self {
+            id: user.id.to_string(),
+            discord_id: user.discord_id.to_string(),
+            telegram_id: user.telegram_id.to_string(),
+            created_at: user.created_at,
+            added_to_group_at: user.added_to_group_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    enabled: bool,
+}
+
+pub async fn get_maintenance(
+    State(state): State<AppState<impl DiscordService>>,
+) -> Json<MaintenanceStatus> {
+    let enabled = state.maintenance.load(Ordering::Relaxed);
+    Json(MaintenanceStatus { enabled })
+}
+
+pub async fn set_maintenance(
+    State(state): State<AppState<impl DiscordService>>,
+    Json(status): Json<MaintenanceStatus>,
+) -> Json<MaintenanceStatus> {
+    state.maintenance.store(status.enabled, Ordering::Relaxed);
+    tracing::warn!(enabled = status.enabled, "Maintenance mode changed");
+    Json(status)
+}
+
+pub async fn list_migrations(
+    State(state): State<AppState<impl DiscordService>>,
+) -> Result<Json<Vec<MigrationRecord>>> {
+    let mut conn = state.pool.acquire().await?;
+    let migrations = migrations::history(conn.as_mut()).await?;
+    Ok(Json(migrations))
+}
+
+pub async fn list_flags(
+    State(state): State<AppState<impl DiscordService>>,
+) -> Result<Json<Vec<FeatureFlag>>> {
+    let mut conn = state.pool.acquire().await?;
+    let flags = FeatureFlag::get_all(conn.as_mut()).await?;
+    Ok(Json(flags))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetFlagBody {
+    enabled: bool,
+}
+
+pub async fn set_flag(
+    State(state): State<AppState<impl DiscordService>>,
+    Path(name): Path<String>,
+    Json(body): Json<SetFlagBody>,
+) -> Result<Json<FeatureFlag>> {
+    let valid_name = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    if !valid_name {
+        return Err(ApiError::bad_request(format!("invalid flag name {name}")));
+    }
+
+    let mut conn = state.pool.acquire().await?;
+    let flag = FeatureFlag::set(conn.as_mut(), &name, body.enabled).await?;
+
+    tracing::warn!(flag = %flag.name, enabled = flag.enabled, "Feature flag changed");
+
+    Ok(Json(flag))
+}
+
+pub async fn list_guilds(
+    State(state): State<AppState<impl DiscordService>>,
+) -> Result<Json<Vec<GuildDto>>> {
+    let mut conn = state.pool.acquire().await?;
+    let guilds = AllowedGuild::get_guilds(conn.as_mut()).await?;
+    Ok(Json(guilds.into_iter().map(GuildDto::from).collect()))
+}
+
+pub async fn list_guild_roles(
+    State(state): State<AppState<impl DiscordService>>,
+    Path(guild_id): Path<i64>,
+) -> Result<Json<Vec<RoleDto>>> {
+    let mut conn = state.pool.acquire().await?;
+    get_allowed_guild(conn.as_mut(), guild_id).await?;
+    let roles = AllowedRole::get_roles(conn.as_mut()).await?;
+    Ok(Json(roles.into_iter().map(RoleDto::from).collect()))
+}
+
+pub async fn list_guild_channels(
+    State(state): State<AppState<impl DiscordService>>,
+    Path(guild_id): Path<i64>,
+) -> Result<Json<Vec<ChannelDto>>> {
+    let mut conn = state.pool.acquire().await?;
+    get_allowed_guild(conn.as_mut(), guild_id).await?;
+    let channels = AllowedChannel::get_channels(conn.as_mut()).await?;
+    Ok(Json(channels.into_iter().map(ChannelDto::from).collect()))
+}
+
+/// Members are paged with a cursor, unless the query asks for an `offset`
+#[derive(Debug, Deserialize)]
+pub struct MembersQuery {
+    offset: Option<i64>,
+    cursor: Option<String>,
+}
+
+pub async fn list_guild_members(
+    State(state): State<AppState<impl DiscordService>>,
+    Path(guild_id): Path<i64>,
+    Query(query): Query<MembersQuery>,
+    Query(offset_params): Query<PaginationParams>,
+    Query(cursor_params): Query<CursorParams>,
+) -> Result<Response> {
+    let mut conn = state.pool.acquire().await?;
+    let guild = get_allowed_guild(conn.as_mut(), guild_id).await?;
+
+    match (query.offset, query.cursor) {
+        (Some(_), Some(_)) => {
+            let message = String::from("offset and cursor can't be used together");
+            Err(ApiError::BadRequest { message })
+        }
+        (Some(_), None) => {
+            let (users, total) =
+                UserLink::get_paginated(conn.as_mut(), guild.id, offset_params).await?;
+            let members = users.into_iter().map(MemberDto::from).collect();
+            Ok(Json(Page::from((members, total, offset_params))).into_response())
+        }
+        (None, _) => {
+            let (users, next_cursor) =
+                UserLink::get_users_page(conn.as_mut(), guild.id, cursor_params).await?;
+            let members = users.into_iter().map(MemberDto::from).collect();
+            Ok(Json(CursorPage::new(members, next_cursor)).into_response())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LookupQuery {
+    discord_id: Option<i64>,
+    telegram_id: Option<i64>,
+}
+
+pub async fn lookup_user(
+    State(state): State<AppState<impl DiscordService>>,
+    Query(query): Query<LookupQuery>,
+) -> Result<Json<MemberDto>> {
+    let mut conn = state.pool.acquire().await?;
+
+    let user = match (query.discord_id, query.telegram_id) {
+        (Some(discord_id), None) => UserLink::find_by_discord_id(conn.as_mut(), discord_id).await?,
+        (None, Some(telegram_id)) => {
+            UserLink::find_by_telegram_id(conn.as_mut(), telegram_id).await?
+        }
+        _ => {
+            let message = String::from("exactly one of discord_id or telegram_id is required");
+            return Err(ApiError::BadRequest { message });
+        }
+    };
+
+    let Some(user) = user else {
+        let message = String::from("no linked user found");
+        return Err(ApiError::NotFound { message });
+    };
+
+    Ok(Json(MemberDto::from(user)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemberBody {
+    telegram_id: Option<i64>,
+    discord_id: Option<i64>,
+}
+
+/// Re-links an account in place, e.g. when a user moves to a new Telegram account
+pub async fn update_member(
+    State(state): State<AppState<impl DiscordService>>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateMemberBody>,
+) -> Result<Json<MemberDto>> {
+    let Ok(id) = Uuid::parse_str(&id) else {
+        return Err(ApiError::bad_request(format!("invalid user id {id}")));
+    };
+
+    if body.telegram_id.is_none() && body.discord_id.is_none() {
+        let message = String::from("at least one of discord_id or telegram_id is required");
+        return Err(ApiError::BadRequest { message });
+    }
+
+    let mut conn = state.pool.acquire().await?;
+    if UserLink::find_by_id(conn.as_mut(), id).await?.is_none() {
+        let message = String::from("no linked user found");
+        return Err(ApiError::NotFound { message });
+    }
+
+    let payload = UserLinkUpdatePayload {
+        telegram_id: body.telegram_id,
+        discord_id: body.discord_id,
+    };
+
+    let user = match UserLink::update(conn.as_mut(), id, payload).await {
+        Ok(user) => user,
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            let message = String::from("id is already linked to another user");
+            return Err(ApiError::BadRequest { message });
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    tracing::warn!(
+        user_link_id = %id,
+        discord_id = user.discord_id,
+        telegram_id = user.telegram_id,
+        "User link updated by admin"
+    );
+
+    Ok(Json(MemberDto::from(user)))
+}
+
+async fn get_allowed_guild(conn: &mut PgConnection, guild_id: i64) -> Result<AllowedGuild> {
+    let Some(guild) = AllowedGuild::find_by_guild_id(conn, guild_id).await? else {
+        let message = format!("guild {guild_id} is not an allowed guild");
+        return Err(ApiError::NotFound { message });
+    };
+
+    Ok(guild)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::api::router;
+    use crate::database::models::{CronStatus, UserLink, UserLinkPayload};
+    use crate::env::Env;
+    use crate::services::discord::DiscordServiceImpl;
+    use crate::test_helpers::default_guild_id;
+
+    const SECRET: &str = "admin_secret";
+    const GUILD_ID: i64 = 258648784039313408;
+
+    fn make_state(pool: PgPool) -> AppState<DiscordServiceImpl> {
+        let (cron_sender, _) = tokio::sync::mpsc::unbounded_channel();
+        let (telegram_sender, _) = tokio::sync::mpsc::unbounded_channel();
+        let mut env = Env::empty();
+        env.cron_secret = SECRET.to_string();
+
+        AppState {
+            telegram_sender,
+            cron_sender,
+            env: Arc::new(env),
+            pool,
+            discord_service: Arc::new(DiscordServiceImpl::new()),
+            maintenance: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn get(pool: PgPool, uri: &str, token: Option<&str>) -> (StatusCode, Option<Value>) {
+        send(make_state(pool), Request::get(uri), token, Body::empty()).await
+    }
+
+    async fn send(
+        state: AppState<DiscordServiceImpl>,
+        mut request: axum::http::request::Builder,
+        token: Option<&str>,
+        body: Body,
+    ) -> (StatusCode, Option<Value>) {
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        let response = router(state)
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    #[sqlx::test]
+    async fn test_missing_token_is_rejected(pool: PgPool) {
+        let (status, _) = get(pool, "/api/guilds", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn test_invalid_token_is_rejected(pool: PgPool) {
+        let (status, body) = get(pool, "/api/guilds", Some("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            body,
+            Some(json!({ "error": "Unauthorized: invalid admin token" }))
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_admin_errors_are_json(pool: PgPool) {
+        let (status, body) = get(pool, "/admin/flags", Some(&format!("{SECRET}x"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            body,
+            Some(json!({ "error": "Unauthorized: invalid admin token" }))
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_guilds(pool: PgPool) {
+        let (status, body) = get(pool, "/api/guilds", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let guilds = body.unwrap();
+        let guild = guilds
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|guild| guild["guild_id"] == "258648784039313408")
+            .unwrap();
+
+        assert_eq!(guild["name"], "Server do Felpinho");
+        assert!(guild["id"].is_string());
+        assert!(guild["created_at"].is_string());
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_roles(pool: PgPool) {
+        let uri = format!("/api/guilds/{GUILD_ID}/roles");
+        let (status, body) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let roles = body.unwrap();
+        let role = roles
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|role| role["role_id"] == "277212035652124672")
+            .unwrap();
+
+        assert_eq!(role["name"], "FELPS");
+        assert_eq!(role["is_admin"], true);
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_channels(pool: PgPool) {
+        let uri = format!("/api/guilds/{GUILD_ID}/channels");
+        let (status, body) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let channels = body.unwrap();
+        assert!(
+            channels
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|channel| channel["channel_id"] == "1140461199553740872")
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let payload = UserLinkPayload::new(123, 456, guild_id);
+        UserLink::create_link(&mut conn, payload).await.unwrap();
+        let other_guild_id: Uuid =
+            sqlx::query_scalar("SELECT id FROM allowed_guilds WHERE guild_id <> $1")
+                .bind(GUILD_ID)
+                .fetch_one(conn.as_mut())
+                .await
+                .unwrap();
+        let payload = UserLinkPayload::new(789, 1011, other_guild_id);
+        UserLink::create_link(&mut conn, payload).await.unwrap();
+
+        let uri = format!("/api/guilds/{GUILD_ID}/members");
+        let (status, body) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let page = body.unwrap();
+        assert!(page["next_cursor"].is_null());
+        let members = page["items"].as_array().unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0]["discord_id"], "123");
+        assert_eq!(members[0]["telegram_id"], "456");
+        assert!(members[0]["added_to_group_at"].is_null());
+    }
+
+    async fn members_page(pool: PgPool, query: &str) -> (Vec<String>, Option<String>) {
+        let uri = format!("/api/guilds/{GUILD_ID}/members?{query}");
+        let (status, body) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let page = body.unwrap();
+        let discord_ids = page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| member["discord_id"].as_str().unwrap().to_string())
+            .collect();
+        let next_cursor = page["next_cursor"].as_str().map(String::from);
+        (discord_ids, next_cursor)
+    }
+
+    async fn create_guild_members(pool: &PgPool, count: i64) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        for discord_id in 1..=count {
+            let payload = UserLinkPayload::new(discord_id, discord_id * 10, guild_id);
+            UserLink::create_link(&mut conn, payload).await.unwrap();
+        }
+
+        // Spread the creation dates so the pages have a known order
+        sqlx::query(
+            "UPDATE user_links
+            SET guild_id = (SELECT id FROM allowed_guilds WHERE guild_id = $1),
+                created_at = NOW() + make_interval(secs => discord_id)",
+        )
+        .bind(GUILD_ID)
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members_first_page(pool: PgPool) {
+        create_guild_members(&pool, 5).await;
+
+        let (members, next_cursor) = members_page(pool, "limit=2").await;
+        assert_eq!(members, vec!["1", "2"]);
+        assert!(next_cursor.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members_middle_page(pool: PgPool) {
+        create_guild_members(&pool, 5).await;
+
+        let (_, cursor) = members_page(pool.clone(), "limit=2").await;
+        let query = format!("limit=2&cursor={}", cursor.unwrap());
+        let (members, next_cursor) = members_page(pool, &query).await;
+        assert_eq!(members, vec!["3", "4"]);
+        assert!(next_cursor.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members_last_page(pool: PgPool) {
+        create_guild_members(&pool, 4).await;
+
+        let (_, cursor) = members_page(pool.clone(), "limit=2").await;
+        let query = format!("limit=2&cursor={}", cursor.unwrap());
+        let (members, next_cursor) = members_page(pool, &query).await;
+        assert_eq!(members, vec!["3", "4"]);
+        assert!(next_cursor.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members_is_paginated(pool: PgPool) {
+        create_guild_members(&pool, 3).await;
+
+        let uri = format!("/api/guilds/{GUILD_ID}/members?offset=1&limit=1");
+        let (status, body) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let page = body.unwrap();
+        assert_eq!(page["items"].as_array().unwrap().len(), 1);
+        assert_eq!(page["items"][0]["discord_id"], "2");
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["offset"], 1);
+        assert_eq!(page["limit"], 1);
+        assert_eq!(page["has_more"], true);
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members_rejects_offset_with_cursor(pool: PgPool) {
+        create_guild_members(&pool, 3).await;
+
+        let (_, cursor) = members_page(pool.clone(), "limit=1").await;
+        let uri = format!(
+            "/api/guilds/{GUILD_ID}/members?offset=1&cursor={}",
+            cursor.unwrap()
+        );
+        let (status, _) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn test_list_guild_members_rejects_invalid_cursor(pool: PgPool) {
+        let uri = format!("/api/guilds/{GUILD_ID}/members?cursor=nope");
+        let (status, _) = get(pool, &uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn test_lookup_by_discord_id(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let payload = UserLinkPayload::new(123, 456, guild_id);
+        UserLink::create_link(&mut conn, payload).await.unwrap();
+
+        let (status, body) = get(pool, "/api/lookup?discord_id=123", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["telegram_id"], "456");
+    }
+
+    #[sqlx::test]
+    async fn test_lookup_by_telegram_id(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let payload = UserLinkPayload::new(123, 456, guild_id);
+        UserLink::create_link(&mut conn, payload).await.unwrap();
+
+        let (status, body) = get(pool, "/api/lookup?telegram_id=456", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["discord_id"], "123");
+    }
+
+    #[sqlx::test]
+    async fn test_lookup_unknown_user(pool: PgPool) {
+        let (status, _) = get(pool, "/api/lookup?discord_id=1", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_lookup_requires_exactly_one_id(pool: PgPool) {
+        let (status, _) = get(pool.clone(), "/api/lookup", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let uri = "/api/lookup?discord_id=1&telegram_id=2";
+        let (status, _) = get(pool, uri, Some(SECRET)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    async fn patch_member(pool: PgPool, id: &str, body: Value) -> (StatusCode, Option<Value>) {
+        let request = Request::patch(format!("/api/users/{id}"))
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = Body::from(body.to_string());
+        send(make_state(pool), request, Some(SECRET), body).await
+    }
+
+    #[sqlx::test]
+    async fn test_update_member(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(123, 456, guild_id))
+            .await
+            .unwrap();
+
+        let id = user.id.to_string();
+        let (status, body) = patch_member(pool, &id, json!({ "telegram_id": 789 })).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = body.unwrap();
+        assert_eq!(body["telegram_id"], "789");
+        assert_eq!(body["discord_id"], "123");
+    }
+
+    #[sqlx::test]
+    async fn test_update_member_rejects_taken_id(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(123, 456, guild_id))
+            .await
+            .unwrap();
+        UserLink::create_link(&mut conn, UserLinkPayload::new(321, 654, guild_id))
+            .await
+            .unwrap();
+
+        let id = user.id.to_string();
+        let (status, _) = patch_member(pool, &id, json!({ "telegram_id": 654 })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn test_update_unknown_member(pool: PgPool) {
+        let id = Uuid::new_v4().to_string();
+        let (status, _) = patch_member(pool.clone(), &id, json!({ "telegram_id": 1 })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = patch_member(pool, "not-a-uuid", json!({ "telegram_id": 1 })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn test_unknown_guild(pool: PgPool) {
+        let (status, _) = get(pool, "/api/guilds/1/roles", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_maintenance_toggle(pool: PgPool) {
+        let state = make_state(pool);
+
+        let (status, body) = send(
+            state.clone(),
+            Request::get("/admin/maintenance"),
+            Some(SECRET),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["enabled"], false);
+
+        let request =
+            Request::put("/admin/maintenance").header(header::CONTENT_TYPE, "application/json");
+        let (status, body) = send(
+            state.clone(),
+            request,
+            Some(SECRET),
+            Body::from(r#"{"enabled":true}"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["enabled"], true);
+        assert!(state.maintenance.load(Ordering::Relaxed));
+    }
+
+    #[sqlx::test]
+    async fn test_maintenance_requires_auth(pool: PgPool) {
+        let request =
+            Request::put("/admin/maintenance").header(header::CONTENT_TYPE, "application/json");
+        let state = make_state(pool);
+        let (status, _) = send(
+            state.clone(),
+            request,
+            None,
+            Body::from(r#"{"enabled":true}"#),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(!state.maintenance.load(Ordering::Relaxed));
+    }
+
+    #[sqlx::test]
+    async fn test_maintenance_blocks_routes(pool: PgPool) {
+        let state = make_state(pool);
+        state.maintenance.store(true, Ordering::Relaxed);
+
+        let (status, body) = send(
+            state.clone(),
+            Request::get("/oauth/start?telegram_id=1"),
+            None,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.unwrap()["error"], "maintenance");
+
+        let (status, _) = send(
+            state.clone(),
+            Request::get("/api/guilds"),
+            Some(SECRET),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (status, _) = send(state.clone(), Request::get("/health"), None, Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(
+            state,
+            Request::get("/admin/maintenance"),
+            Some(SECRET),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn test_health_reports_last_successful_cycle(pool: PgPool) {
+        let (status, body) = get(pool.clone(), "/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = body.unwrap();
+        assert_eq!(body["status"], "ok");
+        assert!(body["last_successful_cycle_at"].is_null());
+
+        let mut conn = pool.acquire().await.unwrap();
+        let recorded_at = CronStatus::record_success(conn.as_mut()).await.unwrap();
+
+        let (_, body) = get(pool, "/health", None).await;
+        let reported = body.unwrap()["last_successful_cycle_at"]
+            .as_str()
+            .unwrap()
+            .parse::<DateTime<Utc>>()
+            .unwrap();
+        assert_eq!(reported, recorded_at);
+    }
+
+    #[sqlx::test]
+    async fn test_list_migrations(pool: PgPool) {
+        let (status, body) = get(pool, "/admin/migrations", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = body.unwrap();
+        let migrations = body.as_array().unwrap();
+        let latest = migrations.last().unwrap();
+
+        assert_eq!(
+            latest["version"].as_i64(),
+            migrations::latest_embedded_version()
+        );
+        assert_eq!(latest["success"], true);
+        assert!(latest["description"].is_string());
+    }
+
+    async fn put_flag(pool: PgPool, name: &str, enabled: bool) -> (StatusCode, Option<Value>) {
+        let request = Request::put(format!("/admin/flags/{name}"))
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = Body::from(json!({ "enabled": enabled }).to_string());
+        send(make_state(pool), request, Some(SECRET), body).await
+    }
+
+    #[sqlx::test]
+    async fn test_set_and_list_flags(pool: PgPool) {
+        let (status, body) = put_flag(pool.clone(), "cron_dry_run", true).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["enabled"], true);
+
+        let (status, body) = get(pool, "/admin/flags", Some(SECRET)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = body.unwrap();
+        let flags = body.as_array().unwrap();
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0]["name"], "cron_dry_run");
+        assert_eq!(flags[0]["enabled"], true);
+    }
+
+    #[sqlx::test]
+    async fn test_invalid_flag_name_is_rejected(pool: PgPool) {
+        let (status, _) = put_flag(pool, "Dry-Run", true).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
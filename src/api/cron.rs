@@ -1,12 +1,21 @@
+use std::time::Duration;
+
 use axum::Json;
 use axum::extract::{Query, State};
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
 
 use super::AppState;
 use super::error::{ApiError, Result};
-use crate::messages::CronAction;
+use super::middleware::secret_matches;
+use crate::cron::VerificationStats;
+use crate::messages::{CronAction, CronOptions};
 use crate::services::discord::DiscordService;
 
+/// How long a dry run request waits for the cycle, a busy or stuck cron task would otherwise
+/// keep the request open forever
+const DRY_RUN_TIMEOUT: Duration = Duration::from_secs(300);
+
 #[derive(Debug, Serialize)]
 pub struct CronResponse {
     ok: bool,
@@ -17,20 +26,244 @@ pub struct CronQuery {
     secret: String,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct TriggerCronBody {
+    guild_id: Option<i64>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TriggerCronResponse {
+    ok: bool,
+    dry_run: bool,
+    stats: Option<VerificationStats>,
+}
+
 pub async fn cron_start(
     State(state): State<AppState<impl DiscordService>>,
     Query(params): Query<CronQuery>,
 ) -> Result<Json<CronResponse>> {
-    if state.env.cron_secret != params.secret {
+    if !secret_matches(&params.secret, &state.env.cron_secret) {
         return Err(ApiError::ForbiddenRequest {
             message: String::from("invalid cron secret"),
         });
     }
 
-    if state.cron_sender.send(CronAction::Execute).is_err() {
+    tracing::warn!("GET /cron is deprecated, use POST /admin/cron/trigger instead");
+
+    let action = CronAction::Execute {
+        options: CronOptions::default(),
+        actor_discord_id: None,
+        done: None,
+    };
+
+    if state.cron_sender.send(action).is_err() {
         let message = String::from("failed start cron job manually");
         return Err(ApiError::InternalError { message });
     }
 
     Ok(Json(CronResponse { ok: true }))
 }
+
+/// Manually triggers a verification cycle, bypassing the cooldown.
+///
+/// Dry runs wait for the cycle to finish so the response can report who would be removed.
+pub async fn trigger_cron(
+    State(state): State<AppState<impl DiscordService>>,
+    body: Option<Json<TriggerCronBody>>,
+) -> Result<Json<TriggerCronResponse>> {
+    let Json(body) = body.unwrap_or_default();
+    let options = CronOptions {
+        force: true,
+        dry_run: body.dry_run,
+        guild_id: body.guild_id,
+    };
+
+    let (done_sender, done_receiver) = oneshot::channel();
+    let action = CronAction::Execute {
+        options,
+        actor_discord_id: None,
+        done: options.dry_run.then_some(done_sender),
+    };
+
+    if state.cron_sender.send(action).is_err() {
+        let message = String::from("failed start cron job manually");
+        return Err(ApiError::InternalError { message });
+    }
+
+    if !options.dry_run {
+        return Ok(Json(TriggerCronResponse {
+            ok: true,
+            dry_run: false,
+            stats: None,
+        }));
+    }
+
+    let Ok(result) = tokio::time::timeout(DRY_RUN_TIMEOUT, done_receiver).await else {
+        return Err(ApiError::Timeout {
+            message: String::from("dry run cron job did not finish in time"),
+        });
+    };
+
+    let stats = result
+        .map_err(|_| ApiError::InternalError {
+            message: String::from("cron job finished without reporting a result"),
+        })?
+        .map_err(|e| {
+            tracing::error!(error = %e, "dry run cron job failed");
+            ApiError::InternalError {
+                message: String::from("dry run cron job failed"),
+            }
+        })?;
+
+    Ok(Json(TriggerCronResponse {
+        ok: true,
+        dry_run: true,
+        stats: Some(stats),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use tokio::sync::mpsc::UnboundedReceiver;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::api::oauth::OAuthStartQueryParams;
+    use crate::api::router;
+    use crate::cron::WouldRemove;
+    use crate::env::Env;
+    use crate::services::discord::DiscordServiceImpl;
+    use crate::test_helpers::TestContext;
+
+    const SECRET: &str = "admin_secret";
+
+    fn make_state(pool: PgPool) -> (AppState<DiscordServiceImpl>, UnboundedReceiver<CronAction>) {
+        let mut env = Env::empty();
+        env.cron_secret = SECRET.to_string();
+
+        let params = OAuthStartQueryParams { telegram_id: 1 };
+        let context = TestContext::with_discord_service(pool, params, DiscordServiceImpl::new())
+            .with_env(env);
+
+        (context.state.0, context.cron_receiver)
+    }
+
+    async fn trigger(
+        state: AppState<DiscordServiceImpl>,
+        token: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Option<Value>) {
+        let mut request = Request::post("/admin/cron/trigger")
+            .header(header::AUTHORIZATION, format!("Bearer {token}"));
+
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+
+        let response = router(state)
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    #[sqlx::test]
+    async fn test_trigger_requires_admin_token(pool: PgPool) {
+        let (state, mut cron_receiver) = make_state(pool);
+
+        let (status, _) = trigger(state, "wrong", None).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(cron_receiver.try_recv().is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_trigger_without_body_forces_a_full_run(pool: PgPool) {
+        let (state, mut cron_receiver) = make_state(pool);
+
+        let (status, body) = trigger(state, SECRET, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.unwrap()["dry_run"], false);
+
+        let CronAction::Execute { options, done, .. } = cron_receiver.try_recv().unwrap();
+        assert!(options.force);
+        assert!(!options.dry_run);
+        assert!(options.guild_id.is_none());
+        assert!(done.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_dry_run_returns_stats(pool: PgPool) {
+        let (state, mut cron_receiver) = make_state(pool);
+
+        tokio::spawn(async move {
+            let CronAction::Execute { options, done, .. } = cron_receiver.recv().await.unwrap();
+            assert!(options.dry_run);
+            assert_eq!(options.guild_id, Some(12345));
+
+            let stats = VerificationStats {
+                guilds_verified: 0,
+                users_checked: 3,
+                users_removed: 1,
+                removed_from_group_count: 0,
+                users_left: 0,
+                users_flagged: 0,
+                users_restricted: 0,
+                users_reinvited: 0,
+                users_failed: 0,
+                users_quarantined: 0,
+                would_remove: vec![WouldRemove {
+                    discord_id: 258648784039313408,
+                    telegram_id: 42,
+                }],
+            };
+            done.unwrap().send(Ok(stats)).unwrap();
+        });
+
+        let body = json!({ "guild_id": 12345, "dry_run": true });
+        let (status, body) = trigger(state, SECRET, Some(body)).await;
+        let body = body.unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["dry_run"], true);
+        assert_eq!(body["stats"]["users_checked"], 3);
+        assert_eq!(body["stats"]["users_removed"], 1);
+        assert_eq!(
+            body["stats"]["would_remove"],
+            json!([{ "discord_id": "258648784039313408", "telegram_id": "42" }])
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_dry_run_times_out_when_cron_does_not_answer(pool: PgPool) {
+        // Keeps the cron task alive without ever answering, like a cycle that never ends
+        let (state, _cron_receiver) = make_state(pool);
+        tokio::time::pause();
+
+        let body = json!({ "dry_run": true });
+        let (status, body) = trigger(state, SECRET, Some(body)).await;
+
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            body.unwrap()["error"],
+            "Timed out: dry run cron job did not finish in time"
+        );
+    }
+}
@@ -1,7 +1,10 @@
+use axum::Json;
 use axum::http::StatusCode;
 use axum::response::{Html, IntoResponse, Response};
 use derive_more::{Display, Error, From};
+use serde_json::json;
 
+use super::middleware::{current_request_id, wants_json_errors};
 use crate::templates::oauth_error_page;
 
 #[derive(Debug, Display, Error, From)]
@@ -19,11 +22,27 @@ pub enum ApiError {
     #[from]
     Database(sqlx::Error),
 
+    #[display("Telegram API error: {_0}")]
+    #[from]
+    Telegram(teloxide::RequestError),
+
     #[display("Internal server error: {message}")]
     InternalError { message: String },
 
     #[display("Bad request: {message}")]
     BadRequest { message: String },
+
+    #[display("Unauthorized: {message}")]
+    Unauthorized { message: String },
+
+    #[display("Not found: {message}")]
+    NotFound { message: String },
+
+    #[display("Too many requests: {message}")]
+    TooManyRequests { message: String },
+
+    #[display("Timed out: {message}")]
+    Timeout { message: String },
 }
 
 impl ApiError {
@@ -48,9 +67,14 @@ impl IntoResponse for ApiError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Database error occurred".to_string(),
             ),
+            ApiError::Telegram(_) => (StatusCode::BAD_GATEWAY, self.to_string()),
             ApiError::ForbiddenRequest { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
             ApiError::BadRequest { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
             ApiError::InternalError { .. } => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            ApiError::Unauthorized { .. } => (StatusCode::UNAUTHORIZED, self.to_string()),
+            ApiError::NotFound { .. } => (StatusCode::NOT_FOUND, self.to_string()),
+            ApiError::TooManyRequests { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            ApiError::Timeout { .. } => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
         };
 
         match &self {
@@ -63,6 +87,9 @@ impl IntoResponse for ApiError {
             ApiError::Http(e) => {
                 tracing::error!(error = %e, "HTTP client error");
             }
+            ApiError::Telegram(e) => {
+                tracing::error!(error = %e, "Telegram API error");
+            }
             ApiError::ForbiddenRequest { message } => {
                 tracing::warn!(message = %message, "Forbidden request");
             }
@@ -72,12 +99,84 @@ impl IntoResponse for ApiError {
             ApiError::BadRequest { message } => {
                 tracing::error!(message = %message, "Bad request");
             }
+            ApiError::Unauthorized { message } => {
+                tracing::warn!(message = %message, "Unauthorized request");
+            }
+            ApiError::NotFound { message } => {
+                tracing::warn!(message = %message, "Resource not found");
+            }
+            ApiError::TooManyRequests { message } => {
+                tracing::warn!(message = %message, "Rate limited request");
+            }
+            ApiError::Timeout { message } => {
+                tracing::error!(message = %message, "Request timed out");
+            }
         }
 
-        let body = Html(oauth_error_page(&error_message).into_string());
+        if wants_json_errors() {
+            return (status, Json(json!({ "error": error_message }))).into_response();
+        }
+
+        let request_id = current_request_id();
+        let body = Html(oauth_error_page(&error_message, request_id.as_deref()).into_string());
 
         (status, body).into_response()
     }
 }
 
 pub type Result<T> = std::result::Result<T, ApiError>;
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::ChatId;
+    use tracing_test::traced_test;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::api::middleware::{JSON_ERRORS, REQUEST_ID};
+
+    #[test]
+    #[traced_test]
+    fn test_telegram_error_is_bad_gateway() {
+        let error = ApiError::from(teloxide::RequestError::MigrateToChatId(ChatId(-100)));
+        assert_eq!(
+            error.to_string(),
+            "Telegram API error: The group has been migrated to a supergroup with ID #-100"
+        );
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert!(logs_contain("Telegram API error"));
+    }
+
+    #[tokio::test]
+    async fn test_error_page_shows_request_id() {
+        let request_id = Uuid::parse_str("1a2b3c4d-0000-0000-0000-000000000000").unwrap();
+        let error = ApiError::bad_request(String::from("invalid state"));
+
+        let response = REQUEST_ID
+            .scope(request_id, async { error.into_response() })
+            .await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(html.contains("invalid state"));
+        assert!(html.contains("<code>1a2b3c4d</code>"));
+    }
+
+    #[tokio::test]
+    async fn test_json_errors() {
+        let error = ApiError::bad_request(String::from("invalid state"));
+
+        let response = JSON_ERRORS.scope((), async { error.into_response() }).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body, json!({ "error": "Bad request: invalid state" }));
+    }
+}
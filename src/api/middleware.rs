@@ -1,11 +1,78 @@
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
-use axum::extract::{MatchedPath, Request};
+use axum::Json;
+use axum::extract::{ConnectInfo, MatchedPath, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
 use axum::middleware::Next;
-use axum::response::Response;
+use axum::response::{IntoResponse, Response};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use uuid::Uuid;
 
-pub async fn trace_requests(request: Request, next: Next) -> Response {
+use super::error::{ApiError, Result};
+use crate::env::Env;
+
+/// Length of the request id shown to users, the start of the full id logged by `trace_requests`
+const SHORT_REQUEST_ID_LEN: usize = 8;
+
+tokio::task_local! {
+    /// Id of the request being handled, set by `trace_requests`
+    pub static REQUEST_ID: Uuid;
+}
+
+tokio::task_local! {
+    /// Set by `json_errors` on the routes used by scripts, so errors aren't rendered as a page
+    pub static JSON_ERRORS: ();
+}
+
+/// Whether errors of the request being handled are answered with JSON instead of HTML
+pub fn wants_json_errors() -> bool {
+    JSON_ERRORS.try_with(|_| ()).is_ok()
+}
+
+pub async fn json_errors(request: Request, next: Next) -> Response {
+    JSON_ERRORS.scope((), next.run(request)).await
+}
+
+/// Short id of the request being handled, for users to quote when reporting a problem
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID
+        .try_with(|id| id.simple().to_string()[..SHORT_REQUEST_ID_LEN].to_string())
+        .ok()
+}
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+/// Resolves the address of the client that made the request.
+///
+/// Forwarded headers are only honored when the app is configured to sit behind a proxy,
+/// otherwise anyone could spoof them. The last `X-Forwarded-For` entry is used since it is
+/// the one appended by our proxy, earlier entries come from the client.
+fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    trust_proxy_headers: bool,
+) -> Option<IpAddr> {
+    if !trust_proxy_headers {
+        return peer;
+    }
+
+    let forwarded_for = header_str(headers, X_FORWARDED_FOR)
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    let real_ip = header_str(headers, X_REAL_IP).and_then(|ip| ip.trim().parse().ok());
+
+    forwarded_for.or(real_ip).or(peer)
+}
+
+pub async fn trace_requests(State(env): State<Arc<Env>>, request: Request, next: Next) -> Response {
     let start = Instant::now();
     let method = request.method().clone();
     let uri = request.uri().clone();
@@ -14,6 +81,13 @@ pub async fn trace_requests(request: Request, next: Next) -> Response {
         .get::<MatchedPath>()
         .map(|mp| mp.as_str())
         .unwrap_or(uri.path());
+    let user_agent = header_str(request.headers(), header::USER_AGENT).map(String::from);
+    let referer = header_str(request.headers(), header::REFERER).map(String::from);
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let client_ip = client_ip(request.headers(), peer, env.trust_proxy_headers);
 
     let request_id = Uuid::new_v4();
     let span = tracing::info_span!(
@@ -21,35 +95,274 @@ pub async fn trace_requests(request: Request, next: Next) -> Response {
         method = %method,
         path = %path,
         request_id = %request_id,
+        user_agent = user_agent,
+        referer = referer,
+        client_ip = client_ip.map(tracing::field::display),
     );
     let _guard = span.enter();
 
     tracing::info!("Received http request");
 
-    let response = next.run(request).await;
+    let response = REQUEST_ID.scope(request_id, next.run(request)).await;
     let duration = start.elapsed();
     let status = response.status();
+    let content_length = header_str(response.headers(), header::CONTENT_LENGTH);
 
     match status {
         _ if status.is_server_error() => tracing::event!(
             tracing::Level::ERROR,
             status = %status,
             duration_ms = duration.as_millis(),
+            content_length = content_length,
             "Request completed"
         ),
         _ if status.is_client_error() => tracing::event!(
             tracing::Level::WARN,
             status = %status,
             duration_ms = duration.as_millis(),
+            content_length = content_length,
             "Request completed"
         ),
         _ => tracing::event!(
             tracing::Level::INFO,
             status = %status,
             duration_ms = duration.as_millis(),
+            content_length = content_length,
             "Request completed"
         ),
     }
 
     response
 }
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Compares in constant time so the time taken doesn't tell how much of the secret was guessed
+pub fn secret_matches(given: &str, secret: &str) -> bool {
+    given.as_bytes().ct_eq(secret.as_bytes()).into()
+}
+
+pub async fn require_admin(
+    State(env): State<Arc<Env>>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let token = header_str(request.headers(), header::AUTHORIZATION)
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if !token.is_some_and(|token| secret_matches(token, &env.cron_secret)) {
+        return Err(ApiError::Unauthorized {
+            message: String::from("invalid admin token"),
+        });
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Past this many tracked addresses the limiter drops the ones whose quota already refilled,
+/// so a public endpoint can't grow it forever
+const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Limits requests per client address, used on public endpoints that hit the database
+#[derive(Clone)]
+pub struct IpRateLimit {
+    limiter: Arc<DefaultKeyedRateLimiter<IpAddr>>,
+    trust_proxy_headers: bool,
+}
+
+impl IpRateLimit {
+    pub fn per_minute(requests: NonZeroU32, trust_proxy_headers: bool) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter::keyed(Quota::per_minute(requests))),
+            trust_proxy_headers,
+        }
+    }
+}
+
+pub async fn rate_limit_by_ip(
+    State(rate_limit): State<IpRateLimit>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+
+    if let Some(ip) = client_ip(request.headers(), peer, rate_limit.trust_proxy_headers) {
+        let limiter = &rate_limit.limiter;
+        if limiter.len() > MAX_TRACKED_IPS {
+            limiter.retain_recent();
+        }
+
+        if limiter.check_key(&ip).is_err() {
+            return Err(ApiError::TooManyRequests {
+                message: format!("rate limit exceeded for {ip}"),
+            });
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+const MAINTENANCE_EXEMPT_PATHS: &[&str] = &["/health", "/admin/maintenance"];
+
+pub async fn maintenance_mode(
+    State(maintenance): State<Arc<AtomicBool>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+
+    if maintenance.load(Ordering::Relaxed) && !MAINTENANCE_EXEMPT_PATHS.contains(&path) {
+        tracing::warn!(path = %path, "Rejecting request during maintenance");
+        let body = Json(json!({ "error": "maintenance" }));
+        return (StatusCode::SERVICE_UNAVAILABLE, body).into_response();
+    }
+
+    next.run(request).await
+}
+
+pub fn cors_layer(env: &Env) -> CorsLayer {
+    let origins = env
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(origin) => Some(origin),
+            Err(e) => {
+                tracing::warn!(origin = %origin, error = %e, "Ignoring invalid CORS origin");
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn preflight(origin: &str) -> Response {
+        let mut env = Env::empty();
+        env.cors_allowed_origins = vec!["https://dashboard.example.com".to_string()];
+
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(cors_layer(&env));
+
+        let request = Request::options("/")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+
+        app.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowed_origin() {
+        let response = preflight("https://dashboard.example.com").await;
+        let headers = response.headers();
+
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dashboard.example.com"
+        );
+        assert!(headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+    }
+
+    #[tokio::test]
+    async fn test_cors_disallowed_origin() {
+        let response = preflight("https://evil.example.com").await;
+        let headers = response.headers();
+
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    fn forwarded_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("6.6.6.6, 203.0.113.7"),
+        );
+        headers.insert(X_REAL_IP, HeaderValue::from_static("198.51.100.1"));
+        headers
+    }
+
+    #[test]
+    fn test_secret_matches() {
+        assert!(secret_matches("admin_secret", "admin_secret"));
+        assert!(!secret_matches("admin_secreT", "admin_secret"));
+        assert!(!secret_matches("admin", "admin_secret"));
+        assert!(!secret_matches("", "admin_secret"));
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_headers_by_default() {
+        let peer = Some("10.0.0.1".parse().unwrap());
+
+        let ip = client_ip(&forwarded_headers(), peer, false);
+
+        assert_eq!(ip, peer);
+    }
+
+    #[test]
+    fn test_client_ip_behind_trusted_proxy() {
+        let peer = Some("10.0.0.1".parse().unwrap());
+        let mut headers = forwarded_headers();
+
+        let ip = client_ip(&headers, peer, true);
+        assert_eq!(ip, Some("203.0.113.7".parse().unwrap()));
+
+        headers.remove(X_FORWARDED_FOR);
+        let ip = client_ip(&headers, peer, true);
+        assert_eq!(ip, Some("198.51.100.1".parse().unwrap()));
+
+        let ip = client_ip(&HeaderMap::new(), peer, true);
+        assert_eq!(ip, peer);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_by_ip() {
+        let rate_limit = IpRateLimit::per_minute(NonZeroU32::new(2).unwrap(), false);
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(
+            axum::middleware::from_fn_with_state(rate_limit, rate_limit_by_ip),
+        );
+
+        let request = |ip: &str| {
+            let peer: SocketAddr = format!("{ip}:4242").parse().unwrap();
+            let mut request = Request::get("/").body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer));
+            app.clone().oneshot(request)
+        };
+
+        assert_eq!(
+            request("203.0.113.7").await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            request("203.0.113.7").await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            request("203.0.113.7").await.unwrap().status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            request("198.51.100.1").await.unwrap().status(),
+            StatusCode::OK
+        );
+    }
+}
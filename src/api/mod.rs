@@ -1,22 +1,40 @@
+mod admin;
 mod cron;
 pub mod error;
 mod middleware;
-mod oauth;
+pub mod oauth;
 
+use std::net::SocketAddr;
+use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-use axum::routing::get;
-use axum::{Router, middleware as axum_middleware};
-use cron::cron_start;
-use middleware::trace_requests;
-use oauth::{oauth_callback, oauth_start};
+use admin::{
+    get_maintenance, list_flags, list_guild_channels, list_guild_members, list_guild_roles,
+    list_guilds, list_migrations, lookup_user, set_flag, set_maintenance, update_member,
+};
+use axum::extract::State;
+use axum::routing::{get, patch, post, put};
+use axum::{Json, Router, middleware as axum_middleware};
+use chrono::{DateTime, Utc};
+use cron::{cron_start, trigger_cron};
+use middleware::{
+    IpRateLimit, cors_layer, json_errors, maintenance_mode, rate_limit_by_ip, require_admin,
+    trace_requests,
+};
+use oauth::{oauth_callback, oauth_check, oauth_start};
+use serde::Serialize;
 use sqlx::PgPool;
 use tokio::sync::mpsc::UnboundedSender;
 
+use crate::database::models::CronStatus;
 use crate::env::Env;
 use crate::messages::{CronAction, TelegramAction};
 use crate::services::discord::{DiscordService, DiscordServiceImpl};
 
+/// Requests per minute a single address can make to the public link check
+const LINK_CHECK_REQUESTS_PER_MINUTE: NonZeroU32 = NonZeroU32::new(10).expect("10 is not zero");
+
 #[derive(Debug, Clone)]
 pub struct AppState<D>
 where
@@ -27,6 +45,7 @@ where
     pub env: Arc<Env>,
     pub pool: PgPool,
     pub discord_service: Arc<D>,
+    pub maintenance: Arc<AtomicBool>,
 }
 
 pub async fn init(
@@ -45,14 +64,10 @@ pub async fn init(
         pool,
         env: env.clone(),
         discord_service,
+        maintenance: Arc::new(AtomicBool::new(false)),
     };
 
-    let app = Router::new()
-        .route("/oauth/start", get(oauth_start))
-        .route("/oauth/callback", get(oauth_callback))
-        .route("/cron", get(cron_start))
-        .layer(axum_middleware::from_fn(trace_requests))
-        .with_state(app_state);
+    let app = router(app_state);
 
     let bind_addr = format!("0.0.0.0:{}", env.port);
     let listener = tokio::net::TcpListener::bind(&bind_addr)
@@ -65,7 +80,89 @@ pub async fn init(
     let listener_addr = listener.local_addr().unwrap();
     tracing::info!(address = %listener_addr, "API service ready and listening");
 
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
     if let Err(e) = axum::serve(listener, app).await {
         tracing::error!(error = %e, "API service failed");
     }
 }
+
+fn router<D>(state: AppState<D>) -> Router
+where
+    D: DiscordService + Clone + 'static,
+{
+    let admin_auth = axum_middleware::from_fn_with_state(state.env.clone(), require_admin);
+    let cors = cors_layer(&state.env);
+    let link_check_limit = axum_middleware::from_fn_with_state(
+        IpRateLimit::per_minute(
+            LINK_CHECK_REQUESTS_PER_MINUTE,
+            state.env.trust_proxy_headers,
+        ),
+        rate_limit_by_ip,
+    );
+
+    let api_routes = Router::new()
+        .route("/guilds", get(list_guilds))
+        .route("/guilds/{id}/roles", get(list_guild_roles))
+        .route("/guilds/{id}/channels", get(list_guild_channels))
+        .route("/guilds/{id}/members", get(list_guild_members))
+        .route("/lookup", get(lookup_user))
+        .route("/users/{id}", patch(update_member))
+        .route_layer(admin_auth.clone())
+        .layer(axum_middleware::from_fn(json_errors))
+        .layer(cors.clone());
+
+    let admin_routes = Router::new()
+        .route("/maintenance", get(get_maintenance).put(set_maintenance))
+        .route("/cron/trigger", post(trigger_cron))
+        .route("/migrations", get(list_migrations))
+        .route("/flags", get(list_flags))
+        .route("/flags/{name}", put(set_flag))
+        .route_layer(admin_auth)
+        .layer(axum_middleware::from_fn(json_errors))
+        .layer(cors);
+
+    Router::new()
+        .route("/health", get(health))
+        .route("/oauth/start", get(oauth_start))
+        .route("/oauth/callback", get(oauth_callback))
+        .route("/oauth/check", get(oauth_check).layer(link_check_limit))
+        .route(
+            "/cron",
+            get(cron_start).layer(axum_middleware::from_fn(json_errors)),
+        )
+        .nest("/api", api_routes)
+        .nest("/admin", admin_routes)
+        .layer(axum_middleware::from_fn_with_state(
+            state.maintenance.clone(),
+            maintenance_mode,
+        ))
+        .layer(axum_middleware::from_fn_with_state(
+            state.env.clone(),
+            trace_requests,
+        ))
+        .with_state(state)
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    status: &'static str,
+    /// Monitoring should alert once this is older than twice the verification interval
+    last_successful_cycle_at: Option<DateTime<Utc>>,
+}
+
+/// Stays up while the database is unreachable, only the last cycle is left out
+async fn health(State(state): State<AppState<impl DiscordService>>) -> Json<Health> {
+    let last_successful_cycle_at = match state.pool.acquire().await {
+        Ok(mut conn) => CronStatus::last_successful_cycle_at(conn.as_mut()).await,
+        Err(e) => Err(e),
+    }
+    .unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Failed to read the last successful verification cycle");
+        None
+    });
+
+    Json(Health {
+        status: "ok",
+        last_successful_cycle_at,
+    })
+}
@@ -1,16 +1,24 @@
+use axum::Json;
 use axum::extract::{Query, State};
-use axum::response::{Html, Redirect};
-use serde::Deserialize;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::PgConnection;
+use sqlx::types::Uuid;
 use validator::Validate;
 
 use super::AppState;
 use super::error::{ApiError, Result};
-use crate::database::models::oauth_state::OAuthState;
-use crate::database::models::user_links::{UserLink, UserLinkPayload};
+use crate::database::models::{
+    AllowedGuild, DiscordOAuthPayload, OAuthState, RoleGroupMapping, TelegramGroup, UserLink,
+    UserLinkPayload,
+};
 use crate::messages::TelegramAction;
-use crate::services::discord::DiscordService;
-use crate::templates::oauth_success_page;
+use crate::services::discord::{DiscordService, DiscordTokenResponse};
+use crate::templates::{
+    oauth_already_linked_page, oauth_no_group_page, oauth_start_missing_page, oauth_success_page,
+};
+use crate::utils::snowflake;
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct OAuthStartQueryParams {
@@ -18,17 +26,31 @@ pub struct OAuthStartQueryParams {
     pub telegram_id: i64,
 }
 
+/// Query of `/oauth/start`, the telegram id is optional so a bare link gets a helpful page
+/// instead of a validation error
+#[derive(Debug, Deserialize)]
+pub struct OAuthStartQuery {
+    pub telegram_id: Option<i64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OAuthCallbackQueryParams {
     pub code: String,
     pub state: String,
 }
 
-#[tracing::instrument(skip(state), fields(telegram_id = params.telegram_id))]
+#[tracing::instrument(skip(state), fields(telegram_id = query.telegram_id))]
 pub async fn oauth_start(
-    Query(params): Query<OAuthStartQueryParams>,
+    Query(query): Query<OAuthStartQuery>,
     State(state): State<AppState<impl DiscordService>>,
-) -> Result<Redirect> {
+) -> Result<Response> {
+    let Some(telegram_id) = query.telegram_id else {
+        tracing::info!("OAuth flow opened without a telegram id");
+        let page = oauth_start_missing_page(state.env.telegram_bot_username.as_deref());
+        return Ok(Html(page.into_string()).into_response());
+    };
+    let params = OAuthStartQueryParams { telegram_id };
+
     tracing::info!("Starting OAuth flow");
 
     if params.validate().is_err() {
@@ -67,7 +89,41 @@ pub async fn oauth_start(
 
     let discord_oauth_url = state.discord_service.get_oauth_url(&state.env, &token);
     tracing::info!(redirect_url = %discord_oauth_url, "Redirecting to Discord OAuth");
-    Ok(Redirect::to(&discord_oauth_url))
+    Ok(Redirect::to(&discord_oauth_url).into_response())
+}
+
+/// Whether a telegram account is linked, deliberately without the discord account behind it
+#[derive(Debug, Serialize)]
+pub struct LinkStatus {
+    pub linked: bool,
+}
+
+#[tracing::instrument(skip(state), fields(telegram_id = params.telegram_id))]
+pub async fn oauth_check(
+    Query(params): Query<OAuthStartQueryParams>,
+    State(state): State<AppState<impl DiscordService>>,
+) -> Result<Json<LinkStatus>> {
+    if params.validate().is_err() {
+        let message = String::from("invalid telegram id for link check");
+        tracing::warn!("{message}");
+        return Err(ApiError::BadRequest { message });
+    };
+
+    let mut conn = state.pool.acquire().await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to acquire database connection");
+        ApiError::Database(e)
+    })?;
+
+    let link = UserLink::find_by_telegram_id(conn.as_mut(), params.telegram_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to check existing telegram link");
+            ApiError::Database(e)
+        })?;
+
+    Ok(Json(LinkStatus {
+        linked: link.is_some(),
+    }))
 }
 
 #[tracing::instrument(skip(state), fields(state_token = %params.state))]
@@ -77,15 +133,18 @@ pub async fn oauth_callback(
 ) -> Result<Html<String>> {
     tracing::info!("Processing OAuth callback");
 
-    let mut tx = match state.pool.acquire().await {
+    // Any failure before the commit rolls back the consumed state, so the user can try again
+    let mut tx = match state.pool.begin().await {
         Ok(tx) => tx,
         Err(e) => {
-            tracing::error!(error = %e, "Failed to acquire database connection");
+            tracing::error!(error = %e, "Failed to begin database transaction");
             return Err(ApiError::Database(e));
         }
     };
 
-    let oauth_state = get_oauth_state(tx.as_mut(), &params.state).await?;
+    let Some(oauth_state) = get_oauth_state(tx.as_mut(), &params.state).await? else {
+        return find_completed_link(tx.as_mut(), &params.state).await;
+    };
 
     let telegram_id = oauth_state.telegram_id;
     tracing::info!(telegram_id = %telegram_id, "Found valid OAuth state");
@@ -97,7 +156,7 @@ pub async fn oauth_callback(
 
     let discord_user = state
         .discord_service
-        .get_user_info(discord_token.access_token)
+        .get_user_info(discord_token.access_token.clone())
         .await?;
 
     tracing::info!(
@@ -113,13 +172,50 @@ pub async fn oauth_callback(
         Err(_) => return Err(ApiError::discord_api("Invalid discord id".into())),
     };
 
+    if let Some(min_age_days) = state.env.min_account_age_days {
+        check_account_age(discord_id, min_age_days, Utc::now())?;
+    }
+
     if let Err(e) = can_link_accounts(tx.as_mut(), discord_id).await {
         tracing::warn!("{e}");
         return Err(e);
     }
 
-    let user_link = create_user_link(tx.as_mut(), discord_id, telegram_id).await?;
-    let action = TelegramAction::InviteUser { telegram_id };
+    // With `guilds.members.read` the roles are read with the user's token, which also works in
+    // guilds the bot isn't in
+    let member_token = discord_token
+        .grants_member_roles()
+        .then_some(discord_token.access_token.as_str());
+
+    // Resolved before linking, dropping the transaction gives the state back so a user without a
+    // group can retry once it is configured
+    let Some(invite) = invite_group(tx.as_mut(), &state, discord_id, member_token).await? else {
+        return Ok(Html(oauth_no_group_page().into_string()));
+    };
+    let group_id = invite.telegram_group_id;
+    let user_link = create_user_link(tx.as_mut(), discord_id, telegram_id, &invite).await?;
+
+    if discord_token.grants_member_roles() {
+        store_member_roles_grant(tx.as_mut(), &user_link, &discord_token, invite.roles).await?;
+    }
+
+    let invite_message = TelegramGroup::find_invite_message(tx.as_mut(), group_id).await?;
+
+    if let Err(e) = UserLink::mark_added_to_group(tx.as_mut(), &user_link.id).await {
+        tracing::error!(error = %e, "Failed to mark user as added to group");
+        return Err(ApiError::Database(e));
+    }
+
+    if let Err(e) = tx.commit().await {
+        tracing::error!(error = %e, "Failed to commit user link");
+        return Err(ApiError::Database(e));
+    }
+
+    let action = TelegramAction::InviteUser {
+        telegram_id,
+        group_id,
+        invite_message,
+    };
 
     match state.telegram_sender.send(action) {
         Ok(_) => tracing::info!(telegram_id = %telegram_id, "Sent telegram invite action"),
@@ -130,11 +226,6 @@ pub async fn oauth_callback(
         ),
     }
 
-    if let Err(e) = UserLink::mark_added_to_group(tx.as_mut(), &user_link.id).await {
-        tracing::error!(error = %e, "Failed to mark user as added to group");
-        return Err(ApiError::Database(e));
-    }
-
     tracing::info!(
         discord_id = %discord_id,
         telegram_id = %telegram_id,
@@ -146,19 +237,194 @@ pub async fn oauth_callback(
     Ok(Html(success_html.into_string()))
 }
 
-async fn get_oauth_state(conn: &mut PgConnection, token: &str) -> Result<OAuthState> {
-    match OAuthState::get_and_delete(conn, token).await {
-        Ok(Some(oauth_state)) => Ok(oauth_state),
-        Ok(None) => {
-            let message = "Invalid or expired authorization request".to_string();
-            tracing::warn!("{message}");
-            Err(ApiError::ForbiddenRequest { message })
-        }
-        Err(e) => {
-            tracing::error!(error = %e, "Failed to retrieve OAuth state");
-            Err(ApiError::Database(e))
-        }
+async fn get_oauth_state(conn: &mut PgConnection, token: &str) -> Result<Option<OAuthState>> {
+    OAuthState::consume(conn, token).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to retrieve OAuth state");
+        ApiError::Database(e)
+    })
+}
+
+/// Double clicks and redirect retries hit the callback with a state that was already consumed,
+/// when that flow ended up linking the accounts we show that they are linked instead of failing.
+/// The page has no identifiers since whoever holds the state didn't have to log in to Discord.
+async fn find_completed_link(conn: &mut PgConnection, token: &str) -> Result<Html<String>> {
+    let consumed_state = OAuthState::find_consumed(conn, token).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to retrieve consumed OAuth state");
+        ApiError::Database(e)
+    })?;
+
+    let user_link = match consumed_state {
+        Some(consumed_state) => UserLink::find_by_telegram_id(conn, consumed_state.telegram_id)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to check existing telegram link");
+                ApiError::Database(e)
+            })?,
+        None => None,
+    };
+
+    let Some(user_link) = user_link else {
+        let message = "Invalid or expired authorization request".to_string();
+        tracing::warn!("{message}");
+        return Err(ApiError::ForbiddenRequest { message });
+    };
+
+    tracing::info!(
+        discord_id = %user_link.discord_id,
+        telegram_id = %user_link.telegram_id,
+        "OAuth callback repeated for already linked accounts"
+    );
+
+    Ok(Html(oauth_already_linked_page().into_string()))
+}
+
+/// Where a newly linked user is invited to, along with the roles that decided it
+struct InviteGroup {
+    telegram_group_id: i64,
+    /// Guild the user is verified in from now on
+    guild_id: Uuid,
+    /// `None` when no guild had to be checked
+    roles: Option<Vec<i64>>,
+}
+
+/// Resolves the telegram group a newly linked user is invited to. The first allowed guild the
+/// user is a member of decides it: the group mapped to one of their roles, or the guild's first
+/// group when none of their roles is mapped. `None` when none of the user's guilds has a group.
+///
+/// Like the cron, deployments without any row in `telegram_groups` use the group configured in
+/// the environment, linking the user to the oldest guild.
+async fn invite_group(
+    conn: &mut PgConnection,
+    state: &AppState<impl DiscordService>,
+    discord_id: i64,
+    member_token: Option<&str>,
+) -> Result<Option<InviteGroup>> {
+    let has_groups = !TelegramGroup::get_all(conn)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to fetch telegram groups");
+            ApiError::Database(e)
+        })?
+        .is_empty();
+
+    if !has_groups {
+        let guild = AllowedGuild::find_oldest(conn).await.map_err(|e| {
+            tracing::error!(error = %e, "Failed to fetch the oldest allowed guild");
+            ApiError::Database(e)
+        })?;
+
+        let Some(guild) = guild else {
+            let message = "No Discord server is allowed to link accounts".to_string();
+            tracing::warn!(discord_id = %discord_id, "{message}");
+            return Err(ApiError::ForbiddenRequest { message });
+        };
+
+        return Ok(Some(InviteGroup {
+            telegram_group_id: state.env.telegram_group_id,
+            guild_id: guild.id,
+            roles: None,
+        }));
     }
+
+    let guilds = AllowedGuild::get_guilds(conn).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to fetch allowed guilds");
+        ApiError::Database(e)
+    })?;
+
+    for guild in guilds {
+        let group = TelegramGroup::find_by_guild(conn, guild.id)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to fetch telegram group for guild");
+                ApiError::Database(e)
+            })?;
+
+        let Some(group) = group else {
+            continue;
+        };
+
+        let discord = &state.discord_service;
+        let roles = match member_token {
+            Some(token) => {
+                discord
+                    .get_user_guild_member(token.to_string(), guild.guild_id)
+                    .await?
+            }
+            None => {
+                discord
+                    .get_member_roles(state.env.clone(), guild.guild_id, discord_id)
+                    .await?
+            }
+        };
+
+        let Some(roles) = roles else {
+            continue;
+        };
+
+        let mappings = RoleGroupMapping::get_all_for_guild(conn, guild.id)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to fetch role group mappings");
+                ApiError::Database(e)
+            })?;
+
+        let telegram_group_id = mappings
+            .iter()
+            .find(|mapping| roles.contains(&mapping.discord_role_id))
+            .map_or(group.telegram_group_id, |mapping| mapping.telegram_group_id);
+
+        return Ok(Some(InviteGroup {
+            telegram_group_id,
+            guild_id: guild.id,
+            roles: Some(roles),
+        }));
+    }
+
+    tracing::warn!(
+        discord_id = %discord_id,
+        "No telegram group is configured for the user's Discord servers"
+    );
+    Ok(None)
+}
+
+/// Keeps the tokens of a `guilds.members.read` grant so the cron can read the user's roles
+/// later, along with the roles read now
+async fn store_member_roles_grant(
+    conn: &mut PgConnection,
+    user_link: &UserLink,
+    token: &DiscordTokenResponse,
+    roles: Option<Vec<i64>>,
+) -> Result<()> {
+    let Some(refresh_token) = token.refresh_token.clone() else {
+        tracing::warn!("Discord granted the member roles scope without a refresh token");
+        return Ok(());
+    };
+
+    // Without an expiry the token is refreshed the first time it is used
+    let now = Utc::now();
+    let payload = DiscordOAuthPayload {
+        access_token: token.access_token.clone(),
+        refresh_token,
+        expires_at: token.expires_at(now).unwrap_or(now),
+    };
+
+    UserLink::set_discord_oauth(conn, &user_link.id, payload)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to store discord oauth tokens");
+            ApiError::Database(e)
+        })?;
+
+    if let Some(roles) = roles {
+        UserLink::set_discord_roles(conn, &user_link.id, &roles)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to store discord roles");
+                ApiError::Database(e)
+            })?;
+    }
+
+    Ok(())
 }
 
 async fn can_link_accounts(conn: &mut PgConnection, discord_id: i64) -> Result<bool> {
@@ -171,131 +437,48 @@ async fn can_link_accounts(conn: &mut PgConnection, discord_id: i64) -> Result<b
     }
 }
 
+/// Throwaway accounts farming invites are usually brand new, so accounts younger than
+/// `min_age_days` are turned away
+fn check_account_age(discord_id: i64, min_age_days: u32, now: DateTime<Utc>) -> Result<()> {
+    let Some(created_at) = snowflake::created_at(discord_id) else {
+        return Err(ApiError::discord_api("Invalid discord id".into()));
+    };
+
+    if now - created_at < chrono::TimeDelta::days(min_age_days.into()) {
+        let message = format!("Discord account must be at least {min_age_days} days old");
+        tracing::warn!(discord_id = %discord_id, %created_at, "{message}");
+        return Err(ApiError::ForbiddenRequest { message });
+    }
+
+    Ok(())
+}
+
 async fn create_user_link(
     conn: &mut PgConnection,
     discord_id: i64,
     telegram_id: i64,
+    invite: &InviteGroup,
 ) -> Result<UserLink> {
     can_link_accounts(conn, discord_id).await?;
-    let payload = UserLinkPayload::new(discord_id, telegram_id);
+    let payload = UserLinkPayload::new(discord_id, telegram_id, invite.guild_id)
+        .with_telegram_group(invite.telegram_group_id);
     let user_link = UserLink::create_link(conn, payload).await?;
     Ok(user_link)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
+    use axum::http::{StatusCode, header};
     use sqlx::PgPool;
-    use tokio::sync::mpsc::UnboundedReceiver;
 
     use super::*;
     use crate::env::Env;
-    use crate::messages::CronAction;
-    use crate::services::discord::{DiscordService, DiscordTokenResponse, DiscordUser};
-    use crate::utils::BoxFuture;
-
-    struct TestContext<D: DiscordService> {
-        params: Query<OAuthStartQueryParams>,
-        state: State<AppState<D>>,
-        _cron_receiver: UnboundedReceiver<CronAction>,
-        _telegram_receiver: UnboundedReceiver<TelegramAction>,
-    }
-
-    #[derive(Debug, Clone)]
-    struct MockDiscordService {
-        discord_user: DiscordUser,
-        should_fail_token: bool,
-        should_fail_user_info: bool,
-    }
-
-    impl MockDiscordService {
-        fn new() -> Self {
-            Self {
-                discord_user: DiscordUser {
-                    id: "123".to_string(),
-                    username: "test_user".to_string(),
-                },
-                should_fail_token: false,
-                should_fail_user_info: false,
-            }
-        }
-
-        fn with_failing_token(mut self) -> Self {
-            self.should_fail_token = true;
-            self
-        }
-
-        fn with_failing_user_info(mut self) -> Self {
-            self.should_fail_user_info = true;
-            self
-        }
-    }
-
-    impl DiscordService for MockDiscordService {
-        fn get_oauth_url(&self, env: &Env, token: &str) -> String {
-            format!(
-                "https://discord.com/api/oauth2/authorize?client_id={}&redirect_uri={}&response_type=code&scope=identify&state={}",
-                &env.discord_client_id,
-                urlencoding::encode(&env.discord_oauth_redirect),
-                token,
-            )
-        }
+    use crate::test_helpers::{MockDiscordService, TestContext, default_guild_id, setup_test};
 
-        fn get_access_token(
-            &self,
-            _: Arc<Env>,
-            _: String,
-        ) -> BoxFuture<Result<DiscordTokenResponse>> {
-            let should_fail = self.should_fail_token;
-            Box::pin(async move {
-                if should_fail {
-                    Err(ApiError::discord_api("Failed to get access token".into()))
-                } else {
-                    Ok(DiscordTokenResponse {
-                        access_token: "sample_access_token".into(),
-                    })
-                }
-            })
-        }
-
-        fn get_user_info(&self, _: String) -> BoxFuture<Result<DiscordUser>> {
-            let should_fail = self.should_fail_user_info;
-            let user = self.discord_user.clone();
-            Box::pin(async move {
-                if should_fail {
-                    Err(ApiError::discord_api("Failed to get user info".into()))
-                } else {
-                    Ok(user)
-                }
-            })
-        }
-    }
-
-    fn setup_test(
-        pool: PgPool,
-        params: OAuthStartQueryParams,
-        discord_service: MockDiscordService,
-    ) -> TestContext<MockDiscordService> {
-        let params = Query(params);
-        let (cron_sender, _cron_receiver) = tokio::sync::mpsc::unbounded_channel();
-        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
-        let env = Arc::new(Env::empty());
-
-        let state = State(AppState {
-            telegram_sender,
-            cron_sender,
-            env,
-            pool,
-            discord_service: Arc::new(discord_service),
-        });
-
-        TestContext {
-            params,
-            state,
-            _cron_receiver,
-            _telegram_receiver,
-        }
+    fn start_query(Query(params): Query<OAuthStartQueryParams>) -> Query<OAuthStartQuery> {
+        Query(OAuthStartQuery {
+            telegram_id: Some(params.telegram_id),
+        })
     }
 
     #[sqlx::test]
@@ -306,7 +489,7 @@ mod tests {
             MockDiscordService::new(),
         );
 
-        let result = oauth_start(setup.params, setup.state).await;
+        let result = oauth_start(start_query(setup.params), setup.state).await;
 
         assert!(result.is_err());
         assert!(matches!(result, Err(ApiError::BadRequest { .. })));
@@ -324,14 +507,39 @@ mod tests {
             MockDiscordService::new(),
         );
 
-        let result = oauth_start(setup.params, setup.state).await.unwrap();
-        assert!(matches!(result, Redirect { .. }));
+        let result = oauth_start(start_query(setup.params), setup.state)
+            .await
+            .unwrap();
+        assert_eq!(result.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[sqlx::test]
+    async fn test_missing_telegram_id_shows_bot_link(pool: PgPool) {
+        let mut env = Env::empty();
+        env.telegram_bot_username = Some("felbot".to_string());
+        let setup = TestContext::with_discord_service(
+            pool,
+            OAuthStartQueryParams { telegram_id: 1 },
+            MockDiscordService::new(),
+        )
+        .with_env(env);
+
+        let query = Query(OAuthStartQuery { telegram_id: None });
+        let response = oauth_start(query, setup.state).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("https://t.me/felbot"));
     }
 
     #[sqlx::test]
     async fn test_already_linked_account(pool: PgPool) {
         let mut conn = pool.acquire().await.unwrap();
-        let payload = UserLinkPayload::new(123, 456);
+        let guild_id = default_guild_id(&mut conn).await;
+        let payload = UserLinkPayload::new(123, 456, guild_id);
         UserLink::create_link(&mut conn, payload).await.unwrap();
 
         let setup = setup_test(
@@ -340,11 +548,33 @@ mod tests {
             MockDiscordService::new(),
         );
 
-        let result = oauth_start(setup.params, setup.state).await;
+        let result = oauth_start(start_query(setup.params), setup.state).await;
         assert!(result.is_err());
         assert!(matches!(result, Err(ApiError::ForbiddenRequest { .. })));
     }
 
+    #[sqlx::test]
+    async fn test_check_link_status(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        UserLink::create_link(&mut conn, UserLinkPayload::new(123, 456, guild_id))
+            .await
+            .unwrap();
+
+        let check = |telegram_id| {
+            let setup = setup_test(
+                pool.clone(),
+                OAuthStartQueryParams { telegram_id },
+                MockDiscordService::new(),
+            );
+            oauth_check(setup.params, setup.state)
+        };
+
+        assert!(check(456).await.unwrap().linked);
+        assert!(!check(789).await.unwrap().linked);
+        assert!(matches!(check(-1).await, Err(ApiError::BadRequest { .. })));
+    }
+
     #[sqlx::test]
     async fn test_successful_callback(pool: PgPool) {
         let mut conn = pool.acquire().await.unwrap();
@@ -371,6 +601,266 @@ mod tests {
         assert!(html.0.contains("test_user"));
     }
 
+    #[sqlx::test]
+    async fn test_full_oauth_flow(pool: PgPool) {
+        let mut setup = setup_test(
+            pool.clone(),
+            OAuthStartQueryParams { telegram_id: 777 },
+            MockDiscordService::new(),
+        );
+
+        let query = Query(OAuthStartQuery {
+            telegram_id: Some(777),
+        });
+        let redirect = oauth_start(query, setup.state.clone()).await.unwrap();
+        assert_eq!(redirect.status(), StatusCode::SEE_OTHER);
+
+        let token: String =
+            sqlx::query_scalar("SELECT state_token FROM oauth_states WHERE telegram_id = $1")
+                .bind(777_i64)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let location = redirect.headers()[header::LOCATION].to_str().unwrap();
+        assert!(location.ends_with(&format!("state={token}")));
+
+        let html = oauth_callback(
+            Query(OAuthCallbackQueryParams {
+                code: "mock_code".to_string(),
+                state: token,
+            }),
+            setup.state.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(html.0, oauth_success_page("test_user").into_string());
+
+        setup.assert_telegram_received(TelegramAction::InviteUser {
+            telegram_id: 777,
+            group_id: 0,
+            invite_message: None,
+        });
+        setup.assert_no_telegram_action();
+
+        let mut conn = pool.acquire().await.unwrap();
+        let link = UserLink::find_by_telegram_id(&mut conn, 777)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.discord_id, 123);
+        assert_eq!(link.guild_id, default_guild_id(&mut conn).await);
+        assert!(link.added_to_group_at.is_some());
+    }
+
+    async fn map_felpinho_group(pool: &PgPool, telegram_group_id: i64) {
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, $1, 'Grupo do Felps' FROM allowed_guilds WHERE guild_id = 258648784039313408",
+        )
+        .bind(telegram_group_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn callback(setup: &TestContext<MockDiscordService>) -> Result<Html<String>> {
+        let mut conn = setup.state.pool.acquire().await.unwrap();
+        OAuthState::create(&mut conn, 777, "test_token")
+            .await
+            .unwrap();
+
+        oauth_callback(
+            Query(OAuthCallbackQueryParams {
+                code: "test_code".to_string(),
+                state: "test_token".to_string(),
+            }),
+            setup.state.clone(),
+        )
+        .await
+    }
+
+    #[sqlx::test]
+    async fn test_callback_invites_to_guild_group(pool: PgPool) {
+        map_felpinho_group(&pool, -1001234567890).await;
+        let discord_service = MockDiscordService::new().with_guilds(vec![258648784039313408]);
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let mut setup = setup_test(pool, params, discord_service);
+
+        let html = callback(&setup).await.unwrap();
+        assert!(html.0.contains("test_user"));
+
+        setup.assert_telegram_received(TelegramAction::InviteUser {
+            telegram_id: 777,
+            group_id: -1001234567890,
+            invite_message: None,
+        });
+    }
+
+    /// Maps the admin role to a second group of the guild, the first one stays the default
+    async fn map_admin_role_group(pool: &PgPool) {
+        map_felpinho_group(pool, -1001234567890).await;
+        map_felpinho_group(pool, -1009876543210).await;
+        sqlx::query(
+            "INSERT INTO role_group_mappings (role_id, group_id)
+            SELECT allowed_roles.id, telegram_groups.id FROM allowed_roles, telegram_groups
+            WHERE allowed_roles.role_id = 258661569200652289
+            AND telegram_groups.telegram_group_id = -1009876543210",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_callback_invites_to_role_group(pool: PgPool) {
+        map_admin_role_group(&pool).await;
+        let discord_service = MockDiscordService::new()
+            .with_guilds(vec![258648784039313408])
+            .with_roles(vec![649703184033513493, 258661569200652289]);
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let mut setup = setup_test(pool.clone(), params, discord_service);
+
+        let html = callback(&setup).await.unwrap();
+        assert!(html.0.contains("test_user"));
+
+        setup.assert_telegram_received(TelegramAction::InviteUser {
+            telegram_id: 777,
+            group_id: -1009876543210,
+            invite_message: None,
+        });
+
+        let mut conn = pool.acquire().await.unwrap();
+        let user = UserLink::find_by_telegram_id(&mut conn, 777)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.telegram_group_id, Some(-1009876543210));
+    }
+
+    #[sqlx::test]
+    async fn test_callback_without_mapped_role_uses_first_group(pool: PgPool) {
+        map_admin_role_group(&pool).await;
+        let discord_service = MockDiscordService::new()
+            .with_guilds(vec![258648784039313408])
+            .with_roles(vec![649703184033513493]);
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let mut setup = setup_test(pool, params, discord_service);
+
+        let html = callback(&setup).await.unwrap();
+        assert!(html.0.contains("test_user"));
+
+        setup.assert_telegram_received(TelegramAction::InviteUser {
+            telegram_id: 777,
+            group_id: -1001234567890,
+            invite_message: None,
+        });
+    }
+
+    #[sqlx::test]
+    async fn test_callback_stores_member_roles_grant(pool: PgPool) {
+        map_felpinho_group(&pool, -1001234567890).await;
+        let discord_service = MockDiscordService::new()
+            .with_guilds(vec![258648784039313408])
+            .with_roles(vec![649703184033513493])
+            .with_member_roles_scope();
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let setup = setup_test(pool.clone(), params, discord_service);
+
+        let html = callback(&setup).await.unwrap();
+        assert!(html.0.contains("test_user"));
+
+        let mut conn = pool.acquire().await.unwrap();
+        let link = UserLink::find_by_telegram_id(&mut conn, 777)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            link.discord_refresh_token.as_deref(),
+            Some("sample_refresh_token")
+        );
+        assert!(link.discord_token_expires_at.unwrap() > Utc::now());
+        assert_eq!(link.discord_roles, Some(vec![649703184033513493]));
+        assert!(!format!("{link:?}").contains("sample_refresh_token"));
+    }
+
+    #[sqlx::test]
+    async fn test_callback_links_user_to_guild_of_group(pool: PgPool) {
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -1001234567890, 'Grupo Teste' FROM allowed_guilds
+            WHERE guild_id = 1355012226355957780",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let discord_service = MockDiscordService::new().with_guilds(vec![1355012226355957780]);
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let setup = setup_test(pool.clone(), params, discord_service);
+
+        let html = callback(&setup).await.unwrap();
+        assert!(html.0.contains("test_user"));
+
+        let mut conn = pool.acquire().await.unwrap();
+        let link = UserLink::find_by_telegram_id(&mut conn, 777)
+            .await
+            .unwrap()
+            .unwrap();
+        let guild = AllowedGuild::find_by_guild_id(&mut conn, 1355012226355957780)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.guild_id, guild.id);
+    }
+
+    #[sqlx::test]
+    async fn test_invite_never_accepted_is_cleaned_up(pool: PgPool) {
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let setup = setup_test(pool.clone(), params, MockDiscordService::new());
+        let html = callback(&setup).await.unwrap();
+        assert!(html.0.contains("test_user"));
+
+        let mut conn = pool.acquire().await.unwrap();
+        let link = UserLink::find_by_telegram_id(&mut conn, 777)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(link.added_to_group_at.is_some());
+        assert!(link.joined_group_at.is_none());
+
+        sqlx::query("UPDATE user_links SET created_at = NOW() - INTERVAL '30 days'")
+            .execute(conn.as_mut())
+            .await
+            .unwrap();
+        let cutoff = Utc::now() - chrono::Duration::days(7);
+        let deleted = UserLink::delete_stale_pending(&mut conn, cutoff)
+            .await
+            .unwrap();
+
+        assert_eq!(deleted, 1);
+    }
+
+    #[sqlx::test]
+    async fn test_callback_without_guild_group(pool: PgPool) {
+        map_felpinho_group(&pool, -1001234567890).await;
+        let discord_service = MockDiscordService::new().with_guilds(vec![1355012226355957780]);
+        let params = OAuthStartQueryParams { telegram_id: 777 };
+        let mut setup = setup_test(pool.clone(), params, discord_service);
+
+        let result = callback(&setup).await;
+
+        let html = result.unwrap();
+        assert!(html.0.contains("No Group Available"));
+        setup.assert_no_telegram_action();
+
+        let mut conn = pool.acquire().await.unwrap();
+        let link = UserLink::find_by_telegram_id(&mut conn, 777).await.unwrap();
+        assert!(link.is_none());
+        let consumed = OAuthState::find_consumed(&mut conn, "test_token")
+            .await
+            .unwrap();
+        assert!(consumed.is_none());
+    }
+
     #[sqlx::test]
     async fn test_invalid_state(pool: PgPool) {
         let setup = setup_test(
@@ -423,7 +913,7 @@ mod tests {
         let token = "test_token".to_string();
         OAuthState::create(&mut conn, 123, &token).await.unwrap();
 
-        let setup = setup_test(
+        let mut setup = setup_test(
             pool,
             OAuthStartQueryParams { telegram_id: 123 },
             MockDiscordService::new().with_failing_user_info(),
@@ -434,11 +924,108 @@ mod tests {
                 code: "test_code".to_string(),
                 state: token,
             }),
-            setup.state,
+            setup.state.clone(),
         )
         .await;
 
         assert!(result.is_err());
         assert!(matches!(result, Err(ApiError::DiscordApi { .. })));
+        setup.assert_no_telegram_action();
+    }
+
+    #[sqlx::test]
+    async fn test_repeated_callback(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let token = "test_token".to_string();
+        OAuthState::create(&mut conn, 123, &token).await.unwrap();
+
+        let setup = setup_test(
+            pool,
+            OAuthStartQueryParams { telegram_id: 123 },
+            MockDiscordService::new(),
+        );
+
+        let params = || {
+            Query(OAuthCallbackQueryParams {
+                code: "test_code".to_string(),
+                state: token.clone(),
+            })
+        };
+
+        let first = oauth_callback(params(), setup.state.clone()).await;
+        assert!(first.is_ok());
+
+        let second = oauth_callback(params(), setup.state).await;
+        let html = second.unwrap();
+        assert_eq!(html.0, oauth_already_linked_page().into_string());
+        assert!(!html.0.contains("123"));
+    }
+
+    #[sqlx::test]
+    async fn test_failed_callback_keeps_state(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let token = "test_token".to_string();
+        OAuthState::create(&mut conn, 123, &token).await.unwrap();
+
+        let setup = setup_test(
+            pool,
+            OAuthStartQueryParams { telegram_id: 123 },
+            MockDiscordService::new().with_failing_token(),
+        );
+
+        let params = || {
+            Query(OAuthCallbackQueryParams {
+                code: "test_code".to_string(),
+                state: token.clone(),
+            })
+        };
+
+        let first = oauth_callback(params(), setup.state.clone()).await;
+        assert!(matches!(first, Err(ApiError::DiscordApi { .. })));
+
+        let consumed = OAuthState::find_consumed(&mut conn, &token).await.unwrap();
+        assert!(consumed.is_none());
+
+        // The state is still valid, so the retry reaches Discord again
+        let second = oauth_callback(params(), setup.state).await;
+        assert!(matches!(second, Err(ApiError::DiscordApi { .. })));
+    }
+
+    #[sqlx::test]
+    async fn test_repeated_callback_without_link(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let token = "test_token".to_string();
+        OAuthState::create(&mut conn, 123, &token).await.unwrap();
+        OAuthState::consume(&mut conn, &token).await.unwrap();
+
+        let setup = setup_test(
+            pool,
+            OAuthStartQueryParams { telegram_id: 123 },
+            MockDiscordService::new(),
+        );
+
+        let result = oauth_callback(
+            Query(OAuthCallbackQueryParams {
+                code: "test_code".to_string(),
+                state: token,
+            }),
+            setup.state,
+        )
+        .await;
+        assert!(matches!(result, Err(ApiError::ForbiddenRequest { .. })));
+    }
+
+    #[test]
+    fn test_check_account_age() {
+        // Created 2016-12-14T17:37:55Z
+        let discord_id = 258648784039313408;
+        let created_at = snowflake::created_at(discord_id).unwrap();
+
+        let old_enough = created_at + chrono::TimeDelta::days(30);
+        assert!(check_account_age(discord_id, 30, old_enough).is_ok());
+
+        let too_new = old_enough - chrono::TimeDelta::seconds(1);
+        let result = check_account_age(discord_id, 30, too_new);
+        assert!(matches!(result, Err(ApiError::ForbiddenRequest { .. })));
     }
 }
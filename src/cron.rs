@@ -1,32 +1,86 @@
+use std::collections::HashSet;
+use std::num::NonZeroU32;
 use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
-use poise::serenity_prelude::{GuildId, Http, UserId};
+use chrono::{DateTime, Utc};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use poise::serenity_prelude::{self as serenity, GuildId, Http, Member, UserId};
+use serde::Serialize;
+use serde_json::json;
 use sqlx::{PgConnection, PgPool};
+use teloxide::types::ChatMemberStatus;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-use crate::database::models::allowed_guilds::AllowedGuild;
-use crate::database::models::allowed_roles::AllowedRole;
-use crate::database::models::user_links::UserLink;
+use crate::api::error::ApiError;
+use crate::database::models::{
+    AllowedGuild, AllowedRole, AuditEntry, CronStatus, DiscordOAuthPayload, FeatureFlag,
+    OAuthState, RemovalPolicy, TelegramGroup, UserLink,
+};
 use crate::env::Env;
 use crate::error::{AppError, Result};
-use crate::messages::{CronAction, TelegramAction};
+use crate::messages::{CronAction, CronOptions, TelegramAction};
+use crate::services::admin_notifier::AdminNotifier;
+use crate::services::discord::{DiscordService, DiscordServiceImpl};
+use crate::services::telegram::{TelegramService, TelegramServiceImpl};
+use crate::services::telegram_groups::TelegramGroupCache;
+use crate::utils::db_retry::with_db_retry;
 use crate::utils::with_tx;
 
+/// Feature flag that turns every cycle into a dry run, e.g. while roles are being reworked
+const DRY_RUN_FLAG: &str = "cron_dry_run";
+/// Audit log action of manually triggered verifications
+const MANUAL_RUN_ACTION: &str = "manual_verification";
+/// Times a removal is retried after a transient database error
+const DB_MAX_RETRIES: u32 = 3;
+/// OAuth tokens this close to expiring are refreshed before being used
+const TOKEN_REFRESH_MARGIN: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+
 /// Configuration for role verification service
 #[derive(Debug, Clone)]
 pub struct RoleVerificationConfig {
-    /// Delay between API calls to avoid rate limiting (in milliseconds)
-    pub api_delay_ms: u64,
+    /// Time between Discord requests once the burst allowance is used up
+    pub api_delay: Duration,
+    /// Discord requests that can go out back to back before `api_delay` applies
+    pub max_concurrency: NonZeroU32,
     /// How often to run the job automatically (in seconds)
     pub schedule_interval_secs: u64,
+    /// Guilds verified more recently than this are skipped unless the run is forced (in seconds)
+    pub verification_cooldown_secs: u64,
+    /// Only report which users would be removed, without removing them
+    pub dry_run: bool,
+    /// How long users of a `restrict` guild stay read only before being removed
+    pub restrict_grace_period: Duration,
+    /// Users added to the group within this many hours are left out of the verification, their
+    /// invite may not have been processed yet
+    pub skip_recently_added_hours: u64,
+    /// Also ask telegram whether users that still have the roles are in the group, inviting
+    /// the ones that left it again. Costs a telegram request per user
+    pub check_telegram_membership: bool,
 }
 
 impl Default for RoleVerificationConfig {
     fn default() -> Self {
         Self {
-            api_delay_ms: 250,
+            api_delay: Duration::from_millis(250),
+            max_concurrency: NonZeroU32::new(4).expect("4 is not zero"),
             schedule_interval_secs: 24 * 60 * 60,
+            verification_cooldown_secs: 60 * 60,
+            dry_run: false,
+            restrict_grace_period: Duration::from_secs(3 * 24 * 60 * 60),
+            skip_recently_added_hours: 2,
+            check_telegram_membership: false,
+        }
+    }
+}
+
+impl RoleVerificationConfig {
+    pub fn from_env(env: &Env) -> Self {
+        Self {
+            api_delay: Duration::from_millis(env.cron_api_delay_ms),
+            max_concurrency: env.cron_max_concurrency,
+            ..Default::default()
         }
     }
 }
@@ -36,42 +90,150 @@ struct CronContext {
     env: Arc<Env>,
     pool: PgPool,
     telegram_sender: UnboundedSender<TelegramAction>,
+    admin_notifier: AdminNotifier,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
     config: RoleVerificationConfig,
+    rate_limiter: Arc<DefaultDirectRateLimiter>,
+    cycle_count: Arc<AtomicU64>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn init(
     env: Arc<Env>,
     pool: PgPool,
     cron_receiver: UnboundedReceiver<CronAction>,
     telegram_sender: UnboundedSender<TelegramAction>,
+    admin_notifier: AdminNotifier,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
     config: RoleVerificationConfig,
 ) {
     let context = CronContext {
         env,
         pool,
         telegram_sender,
+        admin_notifier,
+        telegram_groups,
+        telegram_service,
+        rate_limiter: Arc::new(discord_rate_limiter(&config)),
         config,
+        cycle_count: Arc::new(AtomicU64::new(0)),
     };
 
-    tokio::spawn(manual_trigger_runner(context.clone(), cron_receiver));
-    cron_job_runner(context).await;
+    // Both runners live in the same task so aborting the cron handle stops manual triggers too
+    tokio::join!(
+        manual_trigger_runner(context.clone(), cron_receiver),
+        cron_job_runner(context),
+    );
 }
 
-async fn manual_trigger_runner(ctx: CronContext, mut cron_receiver: UnboundedReceiver<CronAction>) {
-    while (cron_receiver.recv().await).is_some() {
-        tracing::info!("executing manually triggered cron job");
-        let Ok(mut conn) = ctx.pool.acquire().await else {
-            tracing::error!("failed to acquire pool connection, skipping cron job");
-            continue;
+/// Verifies every allowed guild once, for running the verification from an external scheduler.
+///
+/// Every guild is attempted even if an earlier one fails; the last failure is returned.
+pub async fn run_once(
+    env: Arc<Env>,
+    pool: PgPool,
+    telegram_sender: UnboundedSender<TelegramAction>,
+    admin_notifier: AdminNotifier,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
+    config: RoleVerificationConfig,
+) -> Result<()> {
+    let context = CronContext {
+        env,
+        pool,
+        telegram_sender,
+        admin_notifier,
+        telegram_groups,
+        telegram_service,
+        rate_limiter: Arc::new(discord_rate_limiter(&config)),
+        config,
+        cycle_count: Arc::new(AtomicU64::new(0)),
+    };
+
+    let guilds = {
+        let mut conn = context.pool.acquire().await?;
+        AllowedGuild::get_guilds(conn.as_mut()).await?
+    };
+
+    let mut result = Ok(());
+    for guild in guilds {
+        let options = CronOptions {
+            force: true,
+            dry_run: false,
+            guild_id: Some(guild.guild_id),
         };
 
-        run_cron_job(
-            ctx.env.clone(),
-            conn.as_mut(),
-            ctx.telegram_sender.clone(),
-            ctx.config.clone(),
-        )
-        .await;
+        if let Err(e) = run_cron_job(&context, options).await {
+            tracing::error!(error = %e, guild_id = guild.guild_id, "Guild verification failed");
+            result = Err(e);
+        }
+    }
+
+    result
+}
+
+/// Token bucket shared by every Discord request made while verifying users
+fn discord_rate_limiter(config: &RoleVerificationConfig) -> DefaultDirectRateLimiter {
+    let quota = Quota::with_period(config.api_delay)
+        .expect("api delay is validated to be at least 20ms")
+        .allow_burst(config.max_concurrency);
+    RateLimiter::direct(quota)
+}
+
+async fn manual_trigger_runner(ctx: CronContext, mut cron_receiver: UnboundedReceiver<CronAction>) {
+    while let Some(CronAction::Execute {
+        options,
+        actor_discord_id,
+        done,
+    }) = cron_receiver.recv().await
+    {
+        tracing::info!(
+            ?options,
+            ?actor_discord_id,
+            "executing manually triggered cron job"
+        );
+        let result = run_cron_job(&ctx, options).await;
+        record_manual_run(&ctx.pool, actor_discord_id, options, &result).await;
+
+        let requester_gone = done.is_some_and(|done| done.send(result).is_err());
+        if requester_gone {
+            tracing::debug!("cron job requester stopped waiting for the result");
+        }
+    }
+
+    tracing::warn!("Every cron action sender was dropped, manual triggers are disabled");
+}
+
+/// Keeps a durable record of who asked for a manual run and what it did, failing to write it
+/// doesn't affect the run
+async fn record_manual_run(
+    pool: &PgPool,
+    actor_discord_id: Option<i64>,
+    options: CronOptions,
+    result: &Result<VerificationStats>,
+) {
+    let outcome = match result {
+        Ok(stats) => json!({ "stats": stats }),
+        Err(e) => json!({ "error": e.to_string() }),
+    };
+    let details = json!({
+        "force": options.force,
+        "dry_run": options.dry_run,
+        "guild_id": options.guild_id.map(|id| id.to_string()),
+        "outcome": outcome,
+    });
+
+    let recorded = match pool.acquire().await {
+        Ok(mut conn) => {
+            AuditEntry::record(conn.as_mut(), actor_discord_id, MANUAL_RUN_ACTION, &details).await
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = recorded {
+        tracing::error!(error = %e, "Failed to record manual verification in the audit log");
     }
 }
 
@@ -86,43 +248,121 @@ async fn cron_job_runner(ctx: CronContext) {
 
     loop {
         scheduler.tick().await;
-        let Ok(mut conn) = ctx.pool.acquire().await else {
-            tracing::error!("faield to acquire pool connection, skipping cron job");
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            continue;
-        };
+        // The outcome of the cycle is already logged by run_cron_job
+        run_cron_job(&ctx, CronOptions::default()).await.ok();
+    }
+}
 
-        run_cron_job(
-            ctx.env.clone(),
-            conn.as_mut(),
-            ctx.telegram_sender.clone(),
-            ctx.config.clone(),
-        )
-        .await;
+fn log_pool_stats(pool: &PgPool, stage: &'static str) {
+    tracing::debug!(
+        stage = stage,
+        pool_size = pool.size(),
+        pool_idle = pool.num_idle(),
+        pool_max = pool.options().get_max_connections(),
+        "Database pool stats"
+    );
+}
+
+async fn run_cron_job(ctx: &CronContext, options: CronOptions) -> Result<VerificationStats> {
+    let cycle = ctx.cycle_count.fetch_add(1, Ordering::Relaxed) + 1;
+    let result = run_cron_cycle(ctx, options).await;
+
+    match &result {
+        Ok(stats) => {
+            // Dry runs and cycles that skipped every guild verified nobody
+            if stats.guilds_verified > 0 {
+                record_successful_cycle(&ctx.pool).await;
+            }
+
+            if stats.users_quarantined > 0 {
+                ctx.admin_notifier
+                    .notify_duplicate_links(cycle, stats.users_quarantined)
+                    .await;
+            }
+            if stats.users_flagged > 0 {
+                ctx.admin_notifier
+                    .notify_flagged_users(cycle, stats.users_flagged)
+                    .await;
+            }
+        }
+        Err(e) => {
+            ctx.admin_notifier
+                .notify_cron_failure(cycle, &e.to_string())
+                .await;
+        }
     }
+
+    result
 }
 
-async fn run_cron_job(
-    env: Arc<Env>,
-    pool: &mut PgConnection,
-    telegram_sender: UnboundedSender<TelegramAction>,
-    config: RoleVerificationConfig,
-) {
+/// Lets `/health` report when the last cycle succeeded, a failure here only costs monitoring
+/// some accuracy so it doesn't fail the cycle
+async fn record_successful_cycle(pool: &PgPool) {
+    let recorded = match pool.acquire().await {
+        Ok(mut conn) => CronStatus::record_success(conn.as_mut()).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = recorded {
+        tracing::error!(error = %e, "Failed to record successful verification cycle");
+    }
+}
+
+async fn run_cron_cycle(ctx: &CronContext, options: CronOptions) -> Result<VerificationStats> {
     let cycle_start = Instant::now();
-    tracing::info!("Starting role verification cycle");
+    let mut config = ctx.config.clone();
+    config.dry_run |= options.dry_run;
+
+    log_pool_stats(&ctx.pool, "cycle_start");
+
+    let mut conn = ctx.pool.acquire().await.map_err(|e| {
+        tracing::error!(error = %e, "failed to acquire pool connection, skipping cron job");
+        AppError::Database(e)
+    })?;
+
+    config.dry_run |= FeatureFlag::is_enabled(conn.as_mut(), DRY_RUN_FLAG)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to read dry run feature flag");
+            AppError::Database(e)
+        })?;
+
+    tracing::info!(dry_run = config.dry_run, "Starting role verification cycle");
 
-    let stats = with_tx(pool, async |tx| {
-        check_user_roles(env.clone(), tx, telegram_sender, config).await
+    if !config.dry_run {
+        match OAuthState::cleanup_expired(conn.as_mut()).await {
+            Ok(deleted) => tracing::info!(deleted = deleted, "Expired OAuth states cleaned up"),
+            Err(e) => tracing::error!(error = %e, "Failed to clean up expired OAuth states"),
+        }
+    }
+
+    let stats = with_tx(conn.as_mut(), async |tx| {
+        check_user_roles(
+            ctx.env.clone(),
+            tx,
+            ctx.telegram_sender.clone(),
+            &ctx.telegram_groups,
+            &ctx.telegram_service,
+            ctx.rate_limiter.clone(),
+            config,
+            options,
+        )
+        .await
     })
     .await;
 
     let cycle_duration = cycle_start.elapsed();
 
-    match stats {
+    match &stats {
         Ok(stats) => tracing::info!(
             duration_ms = cycle_duration.as_millis(),
             users_checked = stats.users_checked,
             users_removed = stats.users_removed,
+            removed_from_group_count = stats.removed_from_group_count,
+            users_left = stats.users_left,
+            users_flagged = stats.users_flagged,
+            users_restricted = stats.users_restricted,
+            users_reinvited = stats.users_reinvited,
             users_failed = stats.users_failed,
             "Role verification cycle completed successfully"
         ),
@@ -132,41 +372,96 @@ async fn run_cron_job(
             "Role verification cycle failed"
         ),
     };
+
+    // Release the connection first so the idle count reflects the pool after the cycle
+    drop(conn);
+    log_pool_stats(&ctx.pool, "cycle_end");
+
+    stats
 }
 
-#[derive(Debug, Default)]
-struct VerificationStats {
-    users_checked: u32,
-    users_removed: u32,
-    users_failed: u32,
+#[derive(Debug, Default, Serialize)]
+pub struct VerificationStats {
+    /// Guilds whose users were verified and marked as such, always 0 on dry runs
+    pub guilds_verified: u32,
+    pub users_checked: u32,
+    pub users_removed: u32,
+    /// Links soft deleted after their user was removed from the telegram group, unlike
+    /// `users_removed` this leaves out dry runs
+    pub removed_from_group_count: u32,
+    /// Users that are no longer members of the Discord guild
+    pub users_left: u32,
+    /// Users without the required roles that were kept because of a `notify_only` guild
+    pub users_flagged: u32,
+    /// Users of a `restrict` guild that are read only until their grace period is over
+    pub users_restricted: u32,
+    /// Users with the roles that had left the telegram group and were invited again
+    pub users_reinvited: u32,
+    pub users_failed: u32,
+    /// Users skipped because their telegram or discord id is linked more than once
+    pub users_quarantined: u32,
+    /// Users a dry run found without the required roles
+    pub would_remove: Vec<WouldRemove>,
 }
 
+// Discord and Telegram ids are serialized as strings since they don't fit in a JS number
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WouldRemove {
+    #[serde(serialize_with = "serialize_id")]
+    pub discord_id: i64,
+    #[serde(serialize_with = "serialize_id")]
+    pub telegram_id: i64,
+}
+
+fn serialize_id<S: serde::Serializer>(
+    id: &i64,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(id)
+}
+
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip_all)]
 async fn check_user_roles(
     env: Arc<Env>,
     conn: &mut PgConnection,
     telegram_sender: UnboundedSender<TelegramAction>,
+    telegram_groups: &TelegramGroupCache,
+    telegram_service: &impl TelegramService,
+    rate_limiter: Arc<DefaultDirectRateLimiter>,
     config: RoleVerificationConfig,
+    options: CronOptions,
 ) -> Result<VerificationStats> {
     let start_time = Instant::now();
     let mut stats = VerificationStats::default();
 
-    let discord_client = Http::new(&env.discord_token);
+    let discord_client = RateLimitedHttp {
+        http: Http::new(&env.discord_token),
+        limiter: rate_limiter,
+    };
 
     let allowed_guilds = AllowedGuild::get_guilds(conn).await.map_err(|e| {
         tracing::error!(error = %e, "Failed to fetch allowed guilds from database");
         AppError::Database(e)
     })?;
 
-    let Some(guild) = allowed_guilds
-        .iter()
-        .find(|guild| guild.name == "Server do Felpinho")
-    else {
+    let Some(guild) = allowed_guilds.iter().find(|guild| match options.guild_id {
+        Some(guild_id) => guild.guild_id == guild_id,
+        None => guild.name == "Server do Felpinho",
+    }) else {
         tracing::warn!("No allowed guilds found in database, skipping role verification");
         return Ok(stats);
     };
 
-    let guild_id = GuildId::new(guild.guild_id as u64);
+    let cooldown = chrono::Duration::seconds(config.verification_cooldown_secs as i64);
+    if !options.force && verified_recently(guild.last_verified_at, Utc::now(), cooldown) {
+        tracing::info!(
+            guild_id = guild.guild_id,
+            last_verified_at = ?guild.last_verified_at,
+            "Guild was verified recently, skipping role verification"
+        );
+        return Ok(stats);
+    }
 
     let allowed_roles = AllowedRole::get_role_ids(conn).await.map_err(|e| {
         tracing::error!(error = %e, "Failed to fetch allowed roles from database");
@@ -178,28 +473,75 @@ async fn check_user_roles(
         return Ok(stats);
     }
 
-    let users = UserLink::get_all_users(conn).await.map_err(|e| {
-        tracing::error!(error = %e, "Failed to fetch users from database");
+    let Some(telegram_group_id) = telegram_group_id_for(conn, &env, telegram_groups, guild).await?
+    else {
+        tracing::warn!(
+            guild_id = guild.guild_id,
+            "No telegram group configured for guild, skipping role verification"
+        );
+        return Ok(stats);
+    };
+
+    let users = UserLink::get_guild_users(conn, guild.id).await.map_err(|e| {
+        tracing::error!(error = %e, guild_id = guild.guild_id, "Failed to fetch users from database");
         AppError::Database(e)
     })?;
+    let users = quarantine_duplicates(conn, users, &mut stats).await?;
+    let users = skip_recently_added(conn, users, config.skip_recently_added_hours).await?;
+
+    // Only worth a request when some user could still be checked without the bot
+    let guild_id = GuildId::new(guild.guild_id as u64);
+    let bot_in_guild =
+        !users.iter().any(has_discord_oauth) || discord_client.is_in_guild(guild_id).await?;
+    if !bot_in_guild {
+        tracing::warn!(
+            guild_id = guild.guild_id,
+            "Bot is not in the guild, checking users with their own OAuth tokens"
+        );
+    }
+
+    let discord_service = DiscordServiceImpl::new();
+    let members = MemberFetcher {
+        http: &discord_client,
+        discord_service: &discord_service,
+        env: env.clone(),
+        bot_in_guild,
+    };
 
     check_all_users(
-        &discord_client,
+        &members,
         conn,
+        telegram_service,
         telegram_sender,
-        guild_id,
+        guild,
+        telegram_group_id,
         &allowed_roles,
         users,
-        config.api_delay_ms,
+        &config,
         &mut stats,
     )
     .await?;
 
+    if !config.dry_run {
+        AllowedGuild::mark_verified(conn, guild.id)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to mark guild as verified");
+                AppError::Database(e)
+            })?;
+        stats.guilds_verified += 1;
+    }
+
     let total_duration = start_time.elapsed();
     tracing::info!(
         duration_ms = total_duration.as_millis(),
         users_checked = stats.users_checked,
         users_removed = stats.users_removed,
+        removed_from_group_count = stats.removed_from_group_count,
+        users_left = stats.users_left,
+        users_flagged = stats.users_flagged,
+        users_restricted = stats.users_restricted,
+        users_reinvited = stats.users_reinvited,
         users_failed = stats.users_failed,
         "Role verification check completed"
     );
@@ -207,14 +549,154 @@ async fn check_user_roles(
     Ok(stats)
 }
 
+/// Leaves out users whose links share a telegram or discord id with another link, acting on
+/// them could kick the wrong person, so they are only reported until the data is fixed
+async fn quarantine_duplicates(
+    conn: &mut PgConnection,
+    users: Vec<UserLink>,
+    stats: &mut VerificationStats,
+) -> Result<Vec<UserLink>> {
+    let duplicates = UserLink::find_duplicates(conn).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to check user links for duplicates");
+        AppError::Database(e)
+    })?;
+
+    if duplicates.is_empty() {
+        return Ok(users);
+    }
+
+    for (telegram_id, ids) in &duplicates.telegram_ids {
+        tracing::error!(telegram_id = telegram_id, links = ?ids, "Telegram id linked more than once");
+    }
+    for (discord_id, ids) in &duplicates.discord_ids {
+        tracing::error!(discord_id = discord_id, links = ?ids, "Discord id linked more than once");
+    }
+
+    let quarantined = duplicates.link_ids().collect::<HashSet<_>>();
+    let (skipped, users): (Vec<_>, Vec<_>) = users
+        .into_iter()
+        .partition(|user| quarantined.contains(&user.id));
+
+    stats.users_quarantined = skipped.len() as u32;
+    tracing::warn!(
+        users_quarantined = stats.users_quarantined,
+        "Skipping users with duplicated links"
+    );
+
+    Ok(users)
+}
+
+/// Leaves out users added to the group in the last `hours`, removing them before their invite is
+/// processed would kick them right after they linked their accounts
+async fn skip_recently_added(
+    conn: &mut PgConnection,
+    users: Vec<UserLink>,
+    hours: u64,
+) -> Result<Vec<UserLink>> {
+    let since = Utc::now() - chrono::TimeDelta::hours(hours as i64);
+    let recently_added = UserLink::get_recently_added(conn, since)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to fetch recently added users");
+            AppError::Database(e)
+        })?
+        .into_iter()
+        .map(|user| user.discord_id)
+        .collect::<HashSet<_>>();
+
+    if recently_added.is_empty() {
+        return Ok(users);
+    }
+
+    tracing::info!(
+        users_skipped = recently_added.len(),
+        hours = hours,
+        "Skipping recently added users"
+    );
+
+    Ok(users
+        .into_iter()
+        .filter(|user| !recently_added.contains(&user.discord_id))
+        .collect())
+}
+
+/// The telegram group of the guild. Guilds without a group of their own are skipped, except for
+/// deployments without any row in `telegram_groups`: like the OAuth callback, they link every
+/// user to the oldest guild and invite them to the group configured in the environment.
+async fn telegram_group_id_for(
+    conn: &mut PgConnection,
+    env: &Env,
+    telegram_groups: &TelegramGroupCache,
+    guild: &AllowedGuild,
+) -> Result<Option<i64>> {
+    let group = telegram_groups.resolve(conn, guild.id).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to fetch telegram group from database");
+        AppError::Database(e)
+    })?;
+
+    if group.is_some() {
+        return Ok(group);
+    }
+
+    let has_groups = !TelegramGroup::get_all(conn)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to fetch telegram groups from database");
+            AppError::Database(e)
+        })?
+        .is_empty();
+    if has_groups {
+        return Ok(None);
+    }
+
+    let oldest = AllowedGuild::find_oldest(conn).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to fetch the oldest allowed guild from database");
+        AppError::Database(e)
+    })?;
+    if oldest.is_none_or(|oldest| oldest.id != guild.id) {
+        return Ok(None);
+    }
+
+    tracing::debug!(
+        guild_id = guild.guild_id,
+        "No telegram group configured, using the one from the environment"
+    );
+    Ok(Some(env.telegram_group_id))
+}
+
+/// The group the user was invited to, which is the one mapped to their role when there was one.
+/// Links made before the group was kept fall back to the group of the guild
+fn user_group_id(user: &UserLink, guild_group_id: i64) -> i64 {
+    user.telegram_group_id.unwrap_or(guild_group_id)
+}
+
+/// The chat id must be the telegram group, never the discord guild the user was verified in
+fn remove_user_action(user: &UserLink, telegram_group_id: i64) -> TelegramAction {
+    TelegramAction::RemoveUser {
+        telegram_id: user.telegram_id,
+        group_id: telegram_group_id,
+    }
+}
+
+fn verified_recently(
+    last_verified_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    cooldown: chrono::Duration,
+) -> bool {
+    last_verified_at.is_some_and(|last_verified_at| now - last_verified_at < cooldown)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn check_all_users(
-    discord_client: &Http,
+    members: &MemberFetcher<'_, impl DiscordService>,
     conn: &mut PgConnection,
+    telegram_service: &impl TelegramService,
     telegram_sender: UnboundedSender<TelegramAction>,
-    guild_id: GuildId,
+    guild: &AllowedGuild,
+    guild_group_id: i64,
     allowed_roles: &[u64],
     users: Vec<UserLink>,
-    api_delay_ms: u64,
+    config: &RoleVerificationConfig,
     stats: &mut VerificationStats,
 ) -> Result<()> {
     let total_users = users.len();
@@ -231,44 +713,41 @@ async fn check_all_users(
         let _guard = span.enter();
 
         stats.users_checked += 1;
+        let telegram_group_id = user_group_id(&user, guild_group_id);
 
         tracing::debug!("Checking user roles");
 
-        match has_allowed_roles(discord_client, allowed_roles, guild_id, &user).await {
-            Ok(has_roles) => {
-                let check_duration = user_start.elapsed();
-                let duration_ms = check_duration.as_millis();
-
-                if has_roles {
-                    tracing::debug!(duration_ms = duration_ms, "User has valid roles");
-                    continue;
-                }
-
-                tracing::info!(
-                    duration_ms = duration_ms,
-                    "User no longer has required roles"
-                );
-
-                // We send a message to Telegram first to kick the user before removing from DB
-                // This ensures we don't lose track of who to remove if the system crashes
-                let send_result = telegram_sender.send(TelegramAction::RemoveUser {
-                    telegram_id: user.telegram_id,
-                });
+        match member_status(members, conn, allowed_roles, guild, &user).await {
+            Ok(status) => {
+                let duration_ms = user_start.elapsed().as_millis();
+                tracing::debug!(duration_ms = duration_ms, ?status, "User roles checked");
 
-                if let Err(e) = send_result {
-                    tracing::error!(error = %e, "Failed to send telegram remove action");
-                    stats.users_failed += 1;
-                    continue;
-                }
+                handle_member_status(
+                    conn,
+                    &telegram_sender,
+                    &user,
+                    telegram_group_id,
+                    guild,
+                    status,
+                    config,
+                    stats,
+                )
+                .await;
 
-                if let Err(e) = UserLink::delete_by_discord_id(conn, user.discord_id).await {
-                    tracing::error!(error = %e, "Failed to delete user link from database");
-                    stats.users_failed += 1;
-                    continue;
+                if status == MemberStatus::HasRoles
+                    && config.check_telegram_membership
+                    && !config.dry_run
+                {
+                    reinvite_if_left(
+                        conn,
+                        telegram_service,
+                        &telegram_sender,
+                        &user,
+                        telegram_group_id,
+                        stats,
+                    )
+                    .await;
                 }
-
-                stats.users_removed += 1;
-                tracing::info!("User successfully removed from system");
             }
             Err(e) => {
                 let check_duration = user_start.elapsed();
@@ -280,36 +759,1392 @@ async fn check_all_users(
                 stats.users_failed += 1;
             }
         }
+    }
+
+    Ok(())
+}
+
+/// What checking a linked user against their Discord guild found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemberStatus {
+    HasRoles,
+    MissingRoles,
+    /// Discord no longer knows the user as a member of the guild
+    LeftGuild,
+    /// The user isn't a member of a guild they didn't link through, there is nothing to act on
+    NotApplicable,
+}
 
-        if index < total_users - 1 && api_delay_ms > 0 {
-            tokio::time::sleep(tokio::time::Duration::from_millis(api_delay_ms)).await;
+#[allow(clippy::too_many_arguments)]
+async fn handle_member_status(
+    conn: &mut PgConnection,
+    telegram_sender: &UnboundedSender<TelegramAction>,
+    user: &UserLink,
+    telegram_group_id: i64,
+    guild: &AllowedGuild,
+    status: MemberStatus,
+    config: &RoleVerificationConfig,
+    stats: &mut VerificationStats,
+) {
+    match status {
+        MemberStatus::HasRoles => {
+            if user.restricted_at.is_some() && !config.dry_run {
+                lift_restriction(conn, telegram_sender, user, telegram_group_id).await;
+            }
+            return;
+        }
+        MemberStatus::MissingRoles => tracing::info!("User no longer has required roles"),
+        MemberStatus::LeftGuild => {
+            stats.users_left += 1;
+            tracing::info!("User left the Discord server");
+        }
+        MemberStatus::NotApplicable => {
+            tracing::debug!("User is not linked through this Discord server, keeping them");
+            return;
         }
     }
 
-    Ok(())
+    if config.dry_run {
+        stats.users_removed += 1;
+        stats.would_remove.push(WouldRemove {
+            discord_id: user.discord_id,
+            telegram_id: user.telegram_id,
+        });
+        tracing::info!("Dry run, user would be removed from system");
+        return;
+    }
+
+    apply_removal_policy(
+        conn,
+        telegram_sender,
+        user,
+        telegram_group_id,
+        guild.removal_policy,
+        config.restrict_grace_period,
+        stats,
+    )
+    .await;
+}
+
+/// Acts on a user that no longer has any of the allowed roles according to the guild policy
+async fn apply_removal_policy(
+    conn: &mut PgConnection,
+    telegram_sender: &UnboundedSender<TelegramAction>,
+    user: &UserLink,
+    telegram_group_id: i64,
+    policy: RemovalPolicy,
+    grace_period: Duration,
+    stats: &mut VerificationStats,
+) {
+    if policy == RemovalPolicy::NotifyOnly {
+        stats.users_flagged += 1;
+        tracing::warn!("Guild only notifies on missing roles, user was kept");
+        return;
+    }
+
+    if policy == RemovalPolicy::Restrict {
+        match user.restricted_at {
+            None => {
+                restrict_member(conn, telegram_sender, user, telegram_group_id, stats).await;
+                return;
+            }
+            Some(restricted_at) if !grace_period_over(restricted_at, grace_period) => {
+                stats.users_restricted += 1;
+                tracing::info!(%restricted_at, "User is still within the grace period");
+                return;
+            }
+            Some(_) => tracing::info!("Grace period is over, removing restricted user"),
+        }
+    }
+
+    // We send a message to Telegram first to kick the user before removing from DB
+    // This ensures we don't lose track of who to remove if the system crashes
+    let send_result = telegram_sender.send(remove_user_action(user, telegram_group_id));
+
+    if let Err(e) = send_result {
+        tracing::error!(error = %e, "Failed to send telegram remove action");
+        stats.users_failed += 1;
+        return;
+    }
+
+    // Overlapping cycles can make this conflict with another removal of the same user
+    let discord_id = user.discord_id;
+    let removed = with_db_retry(
+        conn,
+        |conn| Box::pin(UserLink::mark_removed_from_group(conn, discord_id)),
+        DB_MAX_RETRIES,
+    )
+    .await;
+
+    if let Err(e) = removed {
+        tracing::error!(error = %e, "Failed to mark user link as removed from group");
+        stats.users_failed += 1;
+        return;
+    }
+
+    stats.users_removed += 1;
+    stats.removed_from_group_count += 1;
+    tracing::info!("User successfully removed from system");
+}
+
+fn grace_period_over(restricted_at: DateTime<Utc>, grace_period: Duration) -> bool {
+    let grace_period = chrono::TimeDelta::from_std(grace_period).unwrap_or(chrono::TimeDelta::MAX);
+    Utc::now() - restricted_at >= grace_period
+}
+
+/// Makes the user read only, recording when so a later cycle knows once the grace period ends
+async fn restrict_member(
+    conn: &mut PgConnection,
+    telegram_sender: &UnboundedSender<TelegramAction>,
+    user: &UserLink,
+    telegram_group_id: i64,
+    stats: &mut VerificationStats,
+) {
+    let action = TelegramAction::RestrictUser {
+        telegram_id: user.telegram_id,
+        group_id: telegram_group_id,
+        read_only: true,
+    };
+
+    if let Err(e) = telegram_sender.send(action) {
+        tracing::error!(error = %e, "Failed to send telegram restrict action");
+        stats.users_failed += 1;
+        return;
+    }
+
+    if let Err(e) = UserLink::set_restricted_at(conn, &user.id, Some(Utc::now())).await {
+        tracing::error!(error = %e, "Failed to mark user link as restricted");
+        stats.users_failed += 1;
+        return;
+    }
+
+    stats.users_restricted += 1;
+    tracing::info!("User restricted until the grace period is over");
+}
+
+/// Gives a restricted user that got the roles back their permissions again
+async fn lift_restriction(
+    conn: &mut PgConnection,
+    telegram_sender: &UnboundedSender<TelegramAction>,
+    user: &UserLink,
+    telegram_group_id: i64,
+) {
+    let action = TelegramAction::RestrictUser {
+        telegram_id: user.telegram_id,
+        group_id: telegram_group_id,
+        read_only: false,
+    };
+
+    if let Err(e) = telegram_sender.send(action) {
+        tracing::error!(error = %e, "Failed to send telegram lift restriction action");
+        return;
+    }
+
+    if let Err(e) = UserLink::set_restricted_at(conn, &user.id, None).await {
+        tracing::error!(error = %e, "Failed to clear user link restriction");
+        return;
+    }
+
+    tracing::info!("User has the roles again, restriction lifted");
+}
+
+/// Invites a user that still has the roles but left the telegram group on their own again
+async fn reinvite_if_left(
+    conn: &mut PgConnection,
+    telegram_service: &impl TelegramService,
+    telegram_sender: &UnboundedSender<TelegramAction>,
+    user: &UserLink,
+    telegram_group_id: i64,
+    stats: &mut VerificationStats,
+) {
+    let status = match telegram_service
+        .get_chat_member_status(telegram_group_id, user.telegram_id)
+        .await
+    {
+        Ok(status) => status,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to check telegram group membership, skipping user");
+            return;
+        }
+    };
+
+    if status != ChatMemberStatus::Left {
+        // Joins the bot missed, the link would be cleaned up as a pending one otherwise
+        if status != ChatMemberStatus::Banned && user.joined_group_at.is_none() {
+            let recorded = UserLink::mark_joined_group(conn, user.telegram_id).await;
+            if let Err(e) = recorded {
+                tracing::error!(error = %e, "Failed to record telegram group join");
+            }
+        }
+        return;
+    }
+
+    let invite_message = TelegramGroup::find_invite_message(conn, telegram_group_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Failed to fetch group invite message, using the default");
+            None
+        });
+    let action = TelegramAction::InviteUser {
+        telegram_id: user.telegram_id,
+        group_id: telegram_group_id,
+        invite_message,
+    };
+
+    if let Err(e) = telegram_sender.send(action) {
+        tracing::error!(error = %e, "Failed to send telegram invite action");
+        stats.users_failed += 1;
+        return;
+    }
+
+    stats.users_reinvited += 1;
+    tracing::info!("User left the telegram group, invite sent again");
+}
+
+/// Discord client that waits on the shared rate limiter before every request
+struct RateLimitedHttp {
+    http: Http,
+    limiter: Arc<DefaultDirectRateLimiter>,
+}
+
+impl RateLimitedHttp {
+    async fn get_member(&self, guild_id: GuildId, user_id: UserId) -> serenity::Result<Member> {
+        self.limiter.until_ready().await;
+        self.http.get_member(guild_id, user_id).await
+    }
+
+    /// Discord answers with a 403 or 404 when the bot was never added to the guild or left it
+    async fn is_in_guild(&self, guild_id: GuildId) -> serenity::Result<bool> {
+        self.limiter.until_ready().await;
+        match self.http.get_guild(guild_id).await {
+            Ok(_) => Ok(true),
+            Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)))
+                if response.status_code == serenity::StatusCode::FORBIDDEN
+                    || response.status_code == serenity::StatusCode::NOT_FOUND =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Reads the roles of linked users, through the bot or with the user's own OAuth token
+struct MemberFetcher<'a, D: DiscordService> {
+    http: &'a RateLimitedHttp,
+    discord_service: &'a D,
+    env: Arc<Env>,
+    bot_in_guild: bool,
+}
+
+/// Where the roles of a linked user are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemberRolesSource {
+    Bot,
+    OAuth,
+}
+
+fn has_discord_oauth(user: &UserLink) -> bool {
+    user.discord_refresh_token.is_some()
+}
+
+/// The bot is preferred since it needs no token. Users who linked with `guilds.members.read`
+/// can still be checked in guilds the bot isn't in, anyone else can't be checked there
+fn member_roles_source(bot_in_guild: bool, user: &UserLink) -> Option<MemberRolesSource> {
+    if bot_in_guild {
+        Some(MemberRolesSource::Bot)
+    } else if has_discord_oauth(user) {
+        Some(MemberRolesSource::OAuth)
+    } else {
+        None
+    }
+}
+
+/// Reads the roles with the user's token, refreshing it first when it is about to expire. The
+/// roles read are kept as the user's snapshot
+async fn oauth_member_roles(
+    conn: &mut PgConnection,
+    discord_service: &impl DiscordService,
+    env: Arc<Env>,
+    user: &UserLink,
+    guild_id: i64,
+) -> Result<Option<Vec<i64>>> {
+    let (Some(access_token), Some(refresh_token)) = (
+        user.discord_access_token.clone(),
+        user.discord_refresh_token.clone(),
+    ) else {
+        return Err(ApiError::discord_api("User has no discord oauth token".into()).into());
+    };
+
+    let now = Utc::now();
+    let expired = user
+        .discord_token_expires_at
+        .is_none_or(|expires_at| expires_at <= now + TOKEN_REFRESH_MARGIN);
+
+    let access_token = if expired {
+        tracing::debug!("Refreshing expired discord oauth token");
+        let token = discord_service
+            .refresh_access_token(env, refresh_token.clone())
+            .await?;
+        let payload = DiscordOAuthPayload {
+            access_token: token.access_token.clone(),
+            // Discord rotates the refresh token, but keep the old one if it didn't send any
+            refresh_token: token.refresh_token.clone().unwrap_or(refresh_token),
+            expires_at: token.expires_at(now).unwrap_or(now),
+        };
+        UserLink::set_discord_oauth(conn, &user.id, payload)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to store refreshed discord oauth token");
+                AppError::Database(e)
+            })?;
+        token.access_token
+    } else {
+        access_token
+    };
+
+    let roles = discord_service
+        .get_user_guild_member(access_token, guild_id)
+        .await?;
+
+    if let Some(roles) = &roles {
+        UserLink::set_discord_roles(conn, &user.id, roles)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to store discord roles snapshot");
+                AppError::Database(e)
+            })?;
+    }
+
+    Ok(roles)
+}
+
+/// Discord answers with a 404 when the user is not a member of the guild anymore
+fn is_unknown_member(error: &serenity::Error) -> bool {
+    matches!(
+        error,
+        serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response))
+            if response.status_code == serenity::StatusCode::NOT_FOUND
+    )
 }
 
 #[tracing::instrument(skip_all, fields(discord_id = user.discord_id))]
-async fn has_allowed_roles(
-    http: &Http,
+async fn member_status(
+    members: &MemberFetcher<'_, impl DiscordService>,
+    conn: &mut PgConnection,
     allowed_roles: &[u64],
-    guild_id: GuildId,
+    guild: &AllowedGuild,
     user: &UserLink,
-) -> Result<bool> {
+) -> Result<MemberStatus> {
+    let guild_id = GuildId::new(guild.guild_id as u64);
     let user_id = UserId::new(user.discord_id as u64);
 
+    // Only the guild the user linked through can tell they left
+    let not_a_member = if user.guild_id == guild.id {
+        MemberStatus::LeftGuild
+    } else {
+        MemberStatus::NotApplicable
+    };
+
     tracing::debug!("Fetching Discord member information");
 
-    let member = http.get_member(guild_id, user_id).await.map_err(|e| {
-        tracing::debug!(error = %e, "Failed to fetch Discord member");
-        e
-    })?;
+    let user_roles: Vec<u64> = match member_roles_source(members.bot_in_guild, user) {
+        Some(MemberRolesSource::Bot) => match members.http.get_member(guild_id, user_id).await {
+            Ok(member) => member.roles.iter().map(|role| role.get()).collect(),
+            Err(e) if is_unknown_member(&e) => return Ok(not_a_member),
+            Err(e) => {
+                tracing::debug!(error = %e, "Failed to fetch Discord member");
+                return Err(e.into());
+            }
+        },
+        Some(MemberRolesSource::OAuth) => {
+            let roles = oauth_member_roles(
+                conn,
+                members.discord_service,
+                members.env.clone(),
+                user,
+                guild_id.get() as i64,
+            )
+            .await?;
+
+            match roles {
+                Some(roles) => roles.into_iter().map(|role| role as u64).collect(),
+                None => return Ok(not_a_member),
+            }
+        }
+        None => {
+            let message = "Bot is not in the guild and the user has no oauth token".to_string();
+            return Err(ApiError::discord_api(message).into());
+        }
+    };
 
-    let user_roles: Vec<u64> = member.roles.iter().map(|role| role.get()).collect();
     // User only needs one of the allowed roles to maintain access
     let has_allowed_role = user_roles
         .iter()
         .any(|role_id| allowed_roles.contains(role_id));
 
-    Ok(has_allowed_role)
+    if has_allowed_role {
+        Ok(MemberStatus::HasRoles)
+    } else {
+        Ok(MemberStatus::MissingRoles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::requests::ResponseResult;
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::database::models::UserLinkPayload;
+    use crate::services::BoxFuture;
+    use crate::test_helpers::{MockDiscordService, default_guild_id};
+
+    #[sqlx::test]
+    async fn test_manual_trigger_signals_completion_and_is_audited(pool: PgPool) {
+        let (cron_sender, cron_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let context = CronContext {
+            env: Arc::new(Env::empty()),
+            pool: pool.clone(),
+            telegram_sender,
+            admin_notifier: AdminNotifier::new(teloxide::Bot::new(""), None),
+            telegram_groups: TelegramGroupCache::default(),
+            telegram_service: TelegramServiceImpl::new(teloxide::Bot::new("")),
+            config: RoleVerificationConfig::default(),
+            rate_limiter: Arc::new(discord_rate_limiter(&RoleVerificationConfig::default())),
+            cycle_count: Arc::new(AtomicU64::new(0)),
+        };
+
+        tokio::spawn(manual_trigger_runner(context, cron_receiver));
+
+        let (done_sender, done_receiver) = oneshot::channel();
+        let action = CronAction::Execute {
+            options: CronOptions::default(),
+            actor_discord_id: Some(42),
+            done: Some(done_sender),
+        };
+        cron_sender.send(action).unwrap();
+
+        let stats = done_receiver.await.unwrap().unwrap();
+        assert_eq!(stats.users_checked, 0);
+        assert_eq!(stats.users_removed, 0);
+        assert_eq!(stats.users_failed, 0);
+        assert!(stats.would_remove.is_empty());
+
+        let (actor, action, users_checked): (Option<i64>, String, i64) = sqlx::query_as(
+            "SELECT actor_discord_id, action, (details->'outcome'->'stats'->>'users_checked')::bigint
+            FROM audit_log",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(actor, Some(42));
+        assert_eq!(action, MANUAL_RUN_ACTION);
+        assert_eq!(users_checked, 0);
+    }
+
+    #[sqlx::test]
+    async fn test_init_consumes_manual_triggers(pool: PgPool) {
+        let (cron_sender, cron_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let handle = tokio::spawn(init(
+            Arc::new(Env::empty()),
+            pool,
+            cron_receiver,
+            telegram_sender,
+            AdminNotifier::new(teloxide::Bot::new(""), None),
+            TelegramGroupCache::default(),
+            TelegramServiceImpl::new(teloxide::Bot::new("")),
+            RoleVerificationConfig::default(),
+        ));
+
+        let (done_sender, done_receiver) = oneshot::channel();
+        let action = CronAction::Execute {
+            options: CronOptions {
+                force: true,
+                dry_run: true,
+                guild_id: None,
+            },
+            actor_discord_id: None,
+            done: Some(done_sender),
+        };
+        cron_sender.send(action).unwrap();
+
+        let stats = tokio::time::timeout(Duration::from_secs(10), done_receiver)
+            .await
+            .expect("manual trigger was not consumed by the cron loop")
+            .unwrap();
+        assert!(stats.is_ok());
+
+        handle.abort();
+    }
+
+    #[sqlx::test]
+    async fn test_run_once_verifies_every_guild(pool: PgPool) {
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        sqlx::query("INSERT INTO allowed_guilds (guild_id, name) VALUES (42, 'Server Novo')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -100 - guild_id, name FROM allowed_guilds
+            WHERE guild_id IN (42, 258648784039313408)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        run_once(
+            Arc::new(Env::empty()),
+            pool.clone(),
+            telegram_sender,
+            AdminNotifier::new(teloxide::Bot::new(""), None),
+            TelegramGroupCache::default(),
+            TelegramServiceImpl::new(teloxide::Bot::new("")),
+            RoleVerificationConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        // Guilds without a telegram group of their own are left alone
+        let unverified: Vec<i64> = sqlx::query_scalar(
+            "SELECT guild_id FROM allowed_guilds WHERE last_verified_at IS NULL",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(unverified, vec![1355012226355957780]);
+    }
+
+    #[test]
+    fn test_would_remove_serializes_ids_as_strings() {
+        let entry = WouldRemove {
+            discord_id: 258648784039313408,
+            telegram_id: 123456789,
+        };
+
+        let value = serde_json::to_value(&entry).unwrap();
+
+        assert_eq!(value["discord_id"], "258648784039313408");
+        assert_eq!(value["telegram_id"], "123456789");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_enforces_configured_rate() {
+        let config = RoleVerificationConfig {
+            api_delay: Duration::from_millis(100),
+            max_concurrency: NonZeroU32::new(10).unwrap(),
+            ..Default::default()
+        };
+        let limiter = discord_rate_limiter(&config);
+
+        for _ in 0..10 {
+            assert!(limiter.check().is_ok());
+        }
+        assert!(limiter.check().is_err());
+
+        // The bucket is empty, so each of these waits roughly 100ms for a new token
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.until_ready().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_never_verified_guild_is_not_skipped() {
+        let cooldown = chrono::Duration::hours(1);
+        assert!(!verified_recently(None, Utc::now(), cooldown));
+    }
+
+    #[test]
+    fn test_recently_verified_guild_is_skipped() {
+        let now = Utc::now();
+        let cooldown = chrono::Duration::hours(1);
+        let last_verified_at = now - chrono::Duration::minutes(10);
+        assert!(verified_recently(Some(last_verified_at), now, cooldown));
+    }
+
+    #[test]
+    fn test_guild_verified_before_cooldown_is_not_skipped() {
+        let now = Utc::now();
+        let cooldown = chrono::Duration::hours(1);
+        let last_verified_at = now - chrono::Duration::hours(2);
+        assert!(!verified_recently(Some(last_verified_at), now, cooldown));
+    }
+
+    #[sqlx::test]
+    async fn test_skip_recently_verified_unless_forced(pool: PgPool) {
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let context = CronContext {
+            env: Arc::new(Env::empty()),
+            pool: pool.clone(),
+            telegram_sender,
+            admin_notifier: AdminNotifier::new(teloxide::Bot::new(""), None),
+            telegram_groups: TelegramGroupCache::default(),
+            telegram_service: TelegramServiceImpl::new(teloxide::Bot::new("")),
+            config: RoleVerificationConfig::default(),
+            rate_limiter: Arc::new(discord_rate_limiter(&RoleVerificationConfig::default())),
+            cycle_count: Arc::new(AtomicU64::new(0)),
+        };
+
+        let last_verified_at = || async {
+            let mut conn = pool.acquire().await.unwrap();
+            AllowedGuild::get_guilds(conn.as_mut())
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|guild| guild.name == "Server do Felpinho")
+                .and_then(|guild| guild.last_verified_at)
+        };
+
+        let forced = CronOptions {
+            force: true,
+            ..Default::default()
+        };
+
+        run_cron_job(&context, CronOptions::default())
+            .await
+            .unwrap();
+        let first = last_verified_at().await.unwrap();
+
+        run_cron_job(&context, CronOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(last_verified_at().await.unwrap(), first);
+
+        run_cron_job(&context, forced).await.unwrap();
+        assert!(last_verified_at().await.unwrap() > first);
+    }
+
+    #[sqlx::test]
+    async fn test_successful_cycle_is_recorded(pool: PgPool) {
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let context = CronContext {
+            env: Arc::new(Env::empty()),
+            pool: pool.clone(),
+            telegram_sender,
+            admin_notifier: AdminNotifier::new(teloxide::Bot::new(""), None),
+            telegram_groups: TelegramGroupCache::default(),
+            telegram_service: TelegramServiceImpl::new(teloxide::Bot::new("")),
+            config: RoleVerificationConfig::default(),
+            rate_limiter: Arc::new(discord_rate_limiter(&RoleVerificationConfig::default())),
+            cycle_count: Arc::new(AtomicU64::new(0)),
+        };
+
+        let last_success = || async {
+            let mut conn = pool.acquire().await.unwrap();
+            CronStatus::last_successful_cycle_at(conn.as_mut())
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(last_success().await, None);
+
+        let options = CronOptions {
+            force: true,
+            ..Default::default()
+        };
+        run_cron_job(&context, options).await.unwrap();
+        let first = last_success().await.unwrap();
+
+        // Skipped by the cooldown, nothing was verified
+        run_cron_job(&context, CronOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(last_success().await, Some(first));
+
+        run_cron_job(&context, options).await.unwrap();
+        assert!(last_success().await.unwrap() > first);
+    }
+
+    #[sqlx::test]
+    async fn test_dry_run_is_not_recorded_as_successful(pool: PgPool) {
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let context = CronContext {
+            env: Arc::new(Env::empty()),
+            pool: pool.clone(),
+            telegram_sender,
+            admin_notifier: AdminNotifier::new(teloxide::Bot::new(""), None),
+            telegram_groups: TelegramGroupCache::default(),
+            telegram_service: TelegramServiceImpl::new(teloxide::Bot::new("")),
+            config: RoleVerificationConfig::default(),
+            rate_limiter: Arc::new(discord_rate_limiter(&RoleVerificationConfig::default())),
+            cycle_count: Arc::new(AtomicU64::new(0)),
+        };
+
+        let mut conn = pool.acquire().await.unwrap();
+        let recorded_at = CronStatus::record_success(conn.as_mut()).await.unwrap();
+
+        let options = CronOptions {
+            force: true,
+            dry_run: true,
+            guild_id: None,
+        };
+        let stats = run_cron_job(&context, options).await.unwrap();
+
+        assert_eq!(stats.guilds_verified, 0);
+        let last_success = CronStatus::last_successful_cycle_at(conn.as_mut())
+            .await
+            .unwrap();
+        assert_eq!(last_success, Some(recorded_at));
+    }
+
+    async fn felpinho(conn: &mut PgConnection) -> AllowedGuild {
+        AllowedGuild::find_by_guild_id(conn, 258648784039313408)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_removal_uses_mapped_telegram_group(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let guild = felpinho(&mut conn).await;
+        let mut env = Env::empty();
+        env.telegram_group_id = -100;
+
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name) VALUES ($1, $2, $3)",
+        )
+        .bind(guild.id)
+        .bind(-1001234567890_i64)
+        .bind("Grupo do Felps")
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+
+        let group_id =
+            telegram_group_id_for(&mut conn, &env, &TelegramGroupCache::default(), &guild)
+                .await
+                .unwrap();
+
+        assert_eq!(group_id, Some(-1001234567890));
+        assert_ne!(group_id, Some(guild.guild_id));
+
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+
+        let TelegramAction::RemoveUser {
+            telegram_id,
+            group_id,
+        } = remove_user_action(&user, group_id.unwrap())
+        else {
+            panic!("expected a remove action");
+        };
+        assert_eq!(telegram_id, 2);
+        assert_eq!(group_id, -1001234567890);
+    }
+
+    #[sqlx::test]
+    async fn test_removal_falls_back_to_configured_telegram_group(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild = felpinho(&mut conn).await;
+        let other_guild = AllowedGuild::find_by_guild_id(&mut conn, 1355012226355957780)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut env = Env::empty();
+        env.telegram_group_id = -100;
+
+        let group_id =
+            telegram_group_id_for(&mut conn, &env, &TelegramGroupCache::default(), &guild)
+                .await
+                .unwrap();
+        assert_eq!(group_id, Some(-100));
+
+        // Only the oldest guild is linked to the configured group
+        let group_id = telegram_group_id_for(
+            &mut conn,
+            &env,
+            &TelegramGroupCache::default(),
+            &other_guild,
+        )
+        .await
+        .unwrap();
+        assert_eq!(group_id, None);
+    }
+
+    #[sqlx::test]
+    async fn test_guild_without_telegram_group_is_skipped(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild = felpinho(&mut conn).await;
+        let mut env = Env::empty();
+        env.telegram_group_id = -100;
+
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -1001234567890, 'Grupo Teste' FROM allowed_guilds
+            WHERE guild_id = 1355012226355957780",
+        )
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+
+        let group_id =
+            telegram_group_id_for(&mut conn, &env, &TelegramGroupCache::default(), &guild)
+                .await
+                .unwrap();
+
+        assert_eq!(group_id, None);
+    }
+
+    #[sqlx::test]
+    async fn test_kick_policy_removes_user(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let mut stats = VerificationStats::default();
+
+        apply_removal_policy(
+            &mut conn,
+            &telegram_sender,
+            &user,
+            -100,
+            RemovalPolicy::Kick,
+            Duration::ZERO,
+            &mut stats,
+        )
+        .await;
+
+        assert_eq!(stats.users_removed, 1);
+        assert_eq!(stats.removed_from_group_count, 1);
+        assert_eq!(stats.users_flagged, 0);
+        assert!(matches!(
+            telegram_receiver.try_recv(),
+            Ok(TelegramAction::RemoveUser {
+                telegram_id: 2,
+                group_id: -100
+            })
+        ));
+        let link = UserLink::find_by_id(&mut conn, user.id).await.unwrap();
+        assert!(link.is_none());
+
+        let (deleted_at, added_to_group_at): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+            sqlx::query_as("SELECT deleted_at, added_to_group_at FROM user_links WHERE id = $1")
+                .bind(user.id)
+                .fetch_one(conn.as_mut())
+                .await
+                .unwrap();
+        assert!(deleted_at.is_some());
+        assert!(added_to_group_at.is_none());
+
+        let relinked = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id)).await;
+        assert!(relinked.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_notify_only_policy_keeps_user(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let mut stats = VerificationStats::default();
+
+        apply_removal_policy(
+            &mut conn,
+            &telegram_sender,
+            &user,
+            -100,
+            RemovalPolicy::NotifyOnly,
+            Duration::ZERO,
+            &mut stats,
+        )
+        .await;
+
+        assert_eq!(stats.users_removed, 0);
+        assert_eq!(stats.users_flagged, 1);
+        assert!(telegram_receiver.try_recv().is_err());
+        let link = UserLink::find_by_id(&mut conn, user.id).await.unwrap();
+        assert!(link.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_recently_added_users_are_skipped(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let recent = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        UserLink::mark_added_to_group(&mut conn, &recent.id)
+            .await
+            .unwrap();
+        let old = UserLink::create_link(&mut conn, UserLinkPayload::new(3, 4, guild_id))
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE user_links SET added_to_group_at = NOW() - INTERVAL '3 hours' WHERE id = $1",
+        )
+        .bind(old.id)
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+        let pending = UserLink::create_link(&mut conn, UserLinkPayload::new(5, 6, guild_id))
+            .await
+            .unwrap();
+
+        let users = UserLink::get_guild_users(&mut conn, guild_id)
+            .await
+            .unwrap();
+        let users = skip_recently_added(&mut conn, users, 2).await.unwrap();
+
+        let mut discord_ids = users.iter().map(|user| user.discord_id).collect::<Vec<_>>();
+        discord_ids.sort();
+        assert_eq!(discord_ids, vec![old.discord_id, pending.discord_id]);
+    }
+
+    async fn reload(conn: &mut PgConnection, user: &UserLink) -> UserLink {
+        UserLink::find_by_id(conn, user.id).await.unwrap().unwrap()
+    }
+
+    async fn link_with_oauth(conn: &mut PgConnection, expires_at: DateTime<Utc>) -> UserLink {
+        let guild_id = default_guild_id(conn).await;
+        let user = UserLink::create_link(conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let payload = DiscordOAuthPayload {
+            access_token: "access_token".to_string(),
+            refresh_token: "refresh_token".to_string(),
+            expires_at,
+        };
+        UserLink::set_discord_oauth(conn, &user.id, payload)
+            .await
+            .unwrap();
+        reload(conn, &user).await
+    }
+
+    #[sqlx::test]
+    async fn test_member_roles_source_falls_back_to_oauth(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let without_oauth = UserLink::create_link(&mut conn, UserLinkPayload::new(3, 4, guild_id))
+            .await
+            .unwrap();
+        let with_oauth = link_with_oauth(&mut conn, Utc::now()).await;
+
+        assert_eq!(
+            member_roles_source(true, &with_oauth),
+            Some(MemberRolesSource::Bot)
+        );
+        assert_eq!(
+            member_roles_source(true, &without_oauth),
+            Some(MemberRolesSource::Bot)
+        );
+        assert_eq!(
+            member_roles_source(false, &with_oauth),
+            Some(MemberRolesSource::OAuth)
+        );
+        assert_eq!(member_roles_source(false, &without_oauth), None);
+    }
+
+    #[sqlx::test]
+    async fn test_oauth_member_roles_refreshes_expired_token(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user = link_with_oauth(&mut conn, Utc::now() - chrono::TimeDelta::hours(1)).await;
+        let discord = MockDiscordService::new()
+            .with_guilds(vec![258648784039313408])
+            .with_roles(vec![649703184033513493]);
+
+        let roles = oauth_member_roles(
+            &mut conn,
+            &discord,
+            Arc::new(Env::empty()),
+            &user,
+            258648784039313408,
+        )
+        .await
+        .unwrap();
+        assert_eq!(roles, Some(vec![649703184033513493]));
+
+        let user = reload(&mut conn, &user).await;
+        assert_eq!(
+            user.discord_access_token.as_deref(),
+            Some("refreshed_access_token")
+        );
+        assert!(user.discord_token_expires_at.unwrap() > Utc::now());
+        assert_eq!(user.discord_roles, Some(vec![649703184033513493]));
+
+        let left = oauth_member_roles(&mut conn, &discord, Arc::new(Env::empty()), &user, 42)
+            .await
+            .unwrap();
+        assert_eq!(left, None);
+    }
+
+    #[sqlx::test]
+    async fn test_restrict_policy_removes_user_after_grace_period(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let grace_period = Duration::from_secs(60 * 60);
+        let mut stats = VerificationStats::default();
+
+        for _ in 0..2 {
+            let user = reload(&mut conn, &user).await;
+            apply_removal_policy(
+                &mut conn,
+                &telegram_sender,
+                &user,
+                -100,
+                RemovalPolicy::Restrict,
+                grace_period,
+                &mut stats,
+            )
+            .await;
+        }
+
+        assert_eq!(stats.users_restricted, 2);
+        assert_eq!(stats.users_removed, 0);
+        assert_eq!(
+            telegram_receiver.try_recv().unwrap(),
+            TelegramAction::RestrictUser {
+                telegram_id: 2,
+                group_id: -100,
+                read_only: true
+            }
+        );
+        assert!(telegram_receiver.try_recv().is_err());
+
+        let two_hours_ago = Utc::now() - chrono::TimeDelta::hours(2);
+        UserLink::set_restricted_at(&mut conn, &user.id, Some(two_hours_ago))
+            .await
+            .unwrap();
+        let user = reload(&mut conn, &user).await;
+
+        apply_removal_policy(
+            &mut conn,
+            &telegram_sender,
+            &user,
+            -100,
+            RemovalPolicy::Restrict,
+            grace_period,
+            &mut stats,
+        )
+        .await;
+
+        assert_eq!(stats.users_removed, 1);
+        assert!(matches!(
+            telegram_receiver.try_recv(),
+            Ok(TelegramAction::RemoveUser { telegram_id: 2, .. })
+        ));
+        let link = UserLink::find_by_id(&mut conn, user.id).await.unwrap();
+        assert!(link.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_restriction_is_lifted_when_roles_are_back(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let guild = felpinho(&mut conn).await;
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        UserLink::set_restricted_at(&mut conn, &user.id, Some(Utc::now()))
+            .await
+            .unwrap();
+        let user = reload(&mut conn, &user).await;
+
+        handle_member_status(
+            &mut conn,
+            &telegram_sender,
+            &user,
+            -100,
+            &guild,
+            MemberStatus::HasRoles,
+            &RoleVerificationConfig::default(),
+            &mut VerificationStats::default(),
+        )
+        .await;
+
+        assert_eq!(
+            telegram_receiver.try_recv().unwrap(),
+            TelegramAction::RestrictUser {
+                telegram_id: 2,
+                group_id: -100,
+                read_only: false
+            }
+        );
+        assert!(reload(&mut conn, &user).await.restricted_at.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_dry_run_flag_keeps_guild_unverified(pool: PgPool) {
+        let (telegram_sender, _telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let context = CronContext {
+            env: Arc::new(Env::empty()),
+            pool: pool.clone(),
+            telegram_sender,
+            admin_notifier: AdminNotifier::new(teloxide::Bot::new(""), None),
+            telegram_groups: TelegramGroupCache::default(),
+            telegram_service: TelegramServiceImpl::new(teloxide::Bot::new("")),
+            config: RoleVerificationConfig::default(),
+            rate_limiter: Arc::new(discord_rate_limiter(&RoleVerificationConfig::default())),
+            cycle_count: Arc::new(AtomicU64::new(0)),
+        };
+
+        let mut conn = pool.acquire().await.unwrap();
+        FeatureFlag::set(&mut conn, DRY_RUN_FLAG, true)
+            .await
+            .unwrap();
+
+        run_cron_job(&context, CronOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(felpinho(&mut conn).await.last_verified_at, None);
+    }
+
+    #[test]
+    fn test_other_discord_errors_are_not_unknown_members() {
+        let error = serenity::Error::Other("gateway closed");
+        assert!(!is_unknown_member(&error));
+    }
+
+    #[sqlx::test]
+    async fn test_member_that_left_guild_is_removed(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let guild = felpinho(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let mut stats = VerificationStats::default();
+
+        handle_member_status(
+            &mut conn,
+            &telegram_sender,
+            &user,
+            -100,
+            &guild,
+            MemberStatus::LeftGuild,
+            &RoleVerificationConfig::default(),
+            &mut stats,
+        )
+        .await;
+
+        assert_eq!(stats.users_left, 1);
+        assert_eq!(stats.users_removed, 1);
+        assert_eq!(stats.users_failed, 0);
+        assert!(matches!(
+            telegram_receiver.try_recv(),
+            Ok(TelegramAction::RemoveUser { telegram_id: 2, .. })
+        ));
+        let link = UserLink::find_by_id(&mut conn, user.id).await.unwrap();
+        assert!(link.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_user_of_another_guild_is_not_removed(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let other_guild = AllowedGuild::find_by_guild_id(&mut conn, 1355012226355957780)
+            .await
+            .unwrap()
+            .unwrap();
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = link_with_oauth(&mut conn, Utc::now() + chrono::TimeDelta::hours(1)).await;
+        let user_id = user.id;
+        let mut stats = VerificationStats::default();
+
+        // The user is only a member of the guild they linked through, the other one answers 404
+        let discord_service = MockDiscordService::new().with_guilds(vec![258648784039313408]);
+        let config = RoleVerificationConfig::default();
+        let http = RateLimitedHttp {
+            http: Http::new(""),
+            limiter: Arc::new(discord_rate_limiter(&config)),
+        };
+        let members = MemberFetcher {
+            http: &http,
+            discord_service: &discord_service,
+            env: Arc::new(Env::empty()),
+            bot_in_guild: false,
+        };
+
+        check_all_users(
+            &members,
+            &mut conn,
+            &MockTelegramService(ChatMemberStatus::Member),
+            telegram_sender,
+            &other_guild,
+            -100,
+            &[649703184033513493],
+            vec![user],
+            &config,
+            &mut stats,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats.users_checked, 1);
+        assert_eq!(stats.users_left, 0);
+        assert_eq!(stats.users_removed, 0);
+        assert!(telegram_receiver.try_recv().is_err());
+        assert!(
+            UserLink::find_by_id(&mut conn, user_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_user_of_mapped_group_is_removed_from_it(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let guild = felpinho(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        // Invited to the group mapped to their role instead of the guild's group
+        let payload = UserLinkPayload::new(1, 2, guild_id).with_telegram_group(-1009876543210);
+        let user = UserLink::create_link(&mut conn, payload).await.unwrap();
+        let payload = DiscordOAuthPayload {
+            access_token: "access_token".to_string(),
+            refresh_token: "refresh_token".to_string(),
+            expires_at: Utc::now() + chrono::TimeDelta::hours(1),
+        };
+        UserLink::set_discord_oauth(&mut conn, &user.id, payload)
+            .await
+            .unwrap();
+        let user = reload(&mut conn, &user).await;
+        let mut stats = VerificationStats::default();
+
+        // The user is still in the guild but lost every allowed role
+        let discord_service = MockDiscordService::new()
+            .with_guilds(vec![258648784039313408])
+            .with_roles(vec![42]);
+        let config = RoleVerificationConfig::default();
+        let http = RateLimitedHttp {
+            http: Http::new(""),
+            limiter: Arc::new(discord_rate_limiter(&config)),
+        };
+        let members = MemberFetcher {
+            http: &http,
+            discord_service: &discord_service,
+            env: Arc::new(Env::empty()),
+            bot_in_guild: false,
+        };
+
+        check_all_users(
+            &members,
+            &mut conn,
+            &MockTelegramService(ChatMemberStatus::Member),
+            telegram_sender,
+            &guild,
+            -100,
+            &[649703184033513493],
+            vec![user],
+            &config,
+            &mut stats,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats.users_removed, 1);
+        assert_eq!(
+            telegram_receiver.try_recv().unwrap(),
+            TelegramAction::RemoveUser {
+                telegram_id: 2,
+                group_id: -1009876543210,
+            }
+        );
+    }
+
+    /// Answers every membership check with the same status
+    #[derive(Debug)]
+    struct MockTelegramService(ChatMemberStatus);
+
+    impl TelegramService for MockTelegramService {
+        fn set_chat_description(&self, _: i64, _: &str) -> BoxFuture<'_, ResponseResult<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_chat_title(&self, _: i64, _: &str) -> BoxFuture<'_, ResponseResult<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn get_chat_member_status(
+            &self,
+            _: i64,
+            _: i64,
+        ) -> BoxFuture<'_, ResponseResult<ChatMemberStatus>> {
+            let status = self.0;
+            Box::pin(async move { Ok(status) })
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_user_that_left_the_group_is_invited_again(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let mut stats = VerificationStats::default();
+
+        let telegram = MockTelegramService(ChatMemberStatus::Left);
+        reinvite_if_left(
+            &mut conn,
+            &telegram,
+            &telegram_sender,
+            &user,
+            -100,
+            &mut stats,
+        )
+        .await;
+
+        assert_eq!(stats.users_reinvited, 1);
+        assert_eq!(
+            telegram_receiver.try_recv().unwrap(),
+            TelegramAction::InviteUser {
+                telegram_id: 2,
+                group_id: -100,
+                invite_message: None,
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_group_member_is_not_invited_again(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let (telegram_sender, mut telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, guild_id))
+            .await
+            .unwrap();
+        let mut stats = VerificationStats::default();
+
+        for status in [ChatMemberStatus::Member, ChatMemberStatus::Banned] {
+            let telegram = MockTelegramService(status);
+            reinvite_if_left(
+                &mut conn,
+                &telegram,
+                &telegram_sender,
+                &user,
+                -100,
+                &mut stats,
+            )
+            .await;
+        }
+
+        assert_eq!(stats.users_reinvited, 0);
+        assert!(telegram_receiver.try_recv().is_err());
+
+        // Seen in the group, so the link is no longer a pending one
+        let user = reload(&mut conn, &user).await;
+        assert!(user.joined_group_at.is_some());
+    }
 }
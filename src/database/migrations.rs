@@ -0,0 +1,138 @@
+use serde::Serialize;
+use sqlx::PgConnection;
+use sqlx::migrate::Migrator;
+use sqlx::types::chrono::{DateTime, Utc};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct MigrationRecord {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    pub success: bool,
+    pub execution_time: i64,
+}
+
+/// Version of the newest migration embedded in the binary
+pub fn latest_embedded_version() -> Option<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .max()
+}
+
+pub async fn latest_applied_version(executor: &mut PgConnection) -> sqlx::Result<Option<i64>> {
+    let version =
+        sqlx::query_scalar!("SELECT version FROM _sqlx_migrations ORDER BY version DESC LIMIT 1")
+            .fetch_optional(executor)
+            .await?;
+
+    Ok(version)
+}
+
+pub async fn history(executor: &mut PgConnection) -> sqlx::Result<Vec<MigrationRecord>> {
+    let migrations = sqlx::query_as!(
+        MigrationRecord,
+        "SELECT version, description, installed_on, success, execution_time
+        FROM _sqlx_migrations
+        ORDER BY version"
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(migrations)
+}
+
+/// Compares the applied schema version against the embedded migrations, only warning on a
+/// mismatch so the app can still come up in a degraded mode instead of refusing to start
+pub async fn check_schema_version(executor: &mut PgConnection) -> sqlx::Result<bool> {
+    let applied = latest_applied_version(executor).await?;
+    let expected = latest_embedded_version();
+
+    if applied != expected {
+        tracing::warn!(
+            applied_version = ?applied,
+            expected_version = ?expected,
+            "Database schema version does not match embedded migrations"
+        );
+        return Ok(false);
+    }
+
+    tracing::info!(version = ?applied, "Database schema is up to date");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_migrated_schema_is_current(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+
+        assert!(check_schema_version(&mut conn).await.unwrap());
+        assert_eq!(
+            latest_applied_version(&mut conn).await.unwrap(),
+            latest_embedded_version()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_missing_migration_is_detected(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let latest = latest_embedded_version().unwrap();
+
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+            .bind(latest)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        assert!(!check_schema_version(&mut conn).await.unwrap());
+
+        let history = history(&mut conn).await.unwrap();
+        assert!(history.iter().all(|migration| migration.version < latest));
+    }
+
+    #[sqlx::test]
+    async fn test_role_dedupe_keeps_mappings(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        MIGRATOR.undo(&mut *conn, 20261016121500).await.unwrap();
+
+        sqlx::query(
+            "INSERT INTO allowed_roles (role_id, name, created_at)
+            VALUES (1, 'Antigo', NOW() - INTERVAL '1 day'), (1, 'Novo', NOW())",
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -100, 'Grupo' FROM allowed_guilds ORDER BY created_at LIMIT 1",
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO role_group_mappings (role_id, group_id)
+            SELECT r.id, g.id FROM allowed_roles r, telegram_groups g WHERE r.name = 'Novo'",
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        MIGRATOR.run(&mut *conn).await.unwrap();
+
+        let mapped: Vec<String> = sqlx::query_scalar(
+            "SELECT r.name FROM role_group_mappings m JOIN allowed_roles r ON r.id = m.role_id",
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(mapped, vec!["Antigo".to_string()]);
+    }
+}
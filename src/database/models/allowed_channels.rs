@@ -78,6 +78,22 @@ impl AllowedChannel {
         Ok(channel)
     }
 
+    pub async fn update_name(
+        executor: &mut sqlx::PgConnection,
+        channel_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE allowed_channels SET name = $2 WHERE channel_id = $1",
+            channel_id,
+            name
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn delete(
         executor: &mut sqlx::PgConnection,
         channel_id: i64,
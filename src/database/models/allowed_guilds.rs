@@ -10,17 +10,174 @@ pub struct AllowedGuild {
     pub name: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub last_verified_at: Option<DateTime<Utc>>,
+    pub removal_policy: RemovalPolicy,
+}
+
+/// A guild along with how much is configured for it. Allowed roles and channels aren't tied to
+/// a guild, so their counts are the same for every guild
+#[derive(Debug, Clone)]
+pub struct GuildWithStats {
+    pub guild: AllowedGuild,
+    pub role_count: i64,
+    pub channel_count: i64,
+    /// Active links of users that joined through the guild
+    pub user_count: i64,
+}
+
+/// What the role verification does with users that lost their roles in a guild
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "removal_policy", rename_all = "snake_case")]
+pub enum RemovalPolicy {
+    /// Remove the user from the telegram group and delete their link
+    #[default]
+    Kick,
+    /// Only report the user, leaving both the group and the link untouched
+    NotifyOnly,
+    /// Make the user read only in the telegram group, removing them if they are still missing
+    /// the roles once the grace period is over
+    Restrict,
 }
 
 impl AllowedGuild {
     pub async fn get_guilds(executor: &mut sqlx::PgConnection) -> Result<Vec<Self>, sqlx::Error> {
-        let guilds = sqlx::query_as!(Self, "SELECT * FROM allowed_guilds")
+        let guilds = sqlx::query_as!(
+            Self,
+            r#"SELECT id, guild_id, name, created_at, updated_at, last_verified_at,
+                removal_policy AS "removal_policy: RemovalPolicy"
+            FROM allowed_guilds
+            WHERE deleted_at IS NULL"#
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(guilds)
+    }
+
+    pub async fn get_guilds_with_stats(
+        executor: &mut sqlx::PgConnection,
+    ) -> Result<Vec<GuildWithStats>, sqlx::Error> {
+        let guilds = sqlx::query_file!("src/database/queries/allowed_guilds_get_with_stats.sql")
             .fetch_all(executor)
-            .await?;
+            .await?
+            .into_iter()
+            .map(|row| GuildWithStats {
+                guild: AllowedGuild {
+                    id: row.id,
+                    guild_id: row.guild_id,
+                    name: row.name,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                    last_verified_at: row.last_verified_at,
+                    removal_policy: row.removal_policy,
+                },
+                role_count: row.role_count,
+                channel_count: row.channel_count,
+                user_count: row.user_count,
+            })
+            .collect();
 
         Ok(guilds)
     }
 
+    pub async fn find_by_guild_id(
+        executor: &mut sqlx::PgConnection,
+        guild_id: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let guild = sqlx::query_as!(
+            Self,
+            r#"SELECT id, guild_id, name, created_at, updated_at, last_verified_at,
+                removal_policy AS "removal_policy: RemovalPolicy"
+            FROM allowed_guilds
+            WHERE guild_id = $1 AND deleted_at IS NULL"#,
+            guild_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(guild)
+    }
+
+    /// The first guild added, deployments that only use the telegram group from the environment
+    /// link every user to it
+    pub async fn find_oldest(
+        executor: &mut sqlx::PgConnection,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let guild = sqlx::query_as!(
+            Self,
+            r#"SELECT id, guild_id, name, created_at, updated_at, last_verified_at,
+                removal_policy AS "removal_policy: RemovalPolicy"
+            FROM allowed_guilds
+            WHERE deleted_at IS NULL
+            ORDER BY created_at, guild_id
+            LIMIT 1"#
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(guild)
+    }
+
+    pub async fn create(
+        executor: &mut sqlx::PgConnection,
+        guild_id: i64,
+        name: &str,
+    ) -> Result<Self, sqlx::Error> {
+        let guild = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO allowed_guilds (guild_id, name)
+            VALUES ($1, $2)
+            RETURNING id, guild_id, name, created_at, updated_at, last_verified_at,
+                removal_policy AS "removal_policy: RemovalPolicy""#,
+            guild_id,
+            name
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(guild)
+    }
+
+    pub async fn update_name(
+        executor: &mut sqlx::PgConnection,
+        id: Uuid,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE allowed_guilds SET name = $2 WHERE id = $1",
+            id,
+            name
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deletes the telegram groups and messages of the guild. The guild itself is only soft
+    /// deleted, the links of its users soft deleted by `UserLink::bulk_unlink_by_guild` would
+    /// go away with it otherwise
+    pub async fn delete(executor: &mut sqlx::PgConnection, id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM telegram_groups WHERE allowed_guild_id = $1",
+            id
+        )
+        .execute(&mut *executor)
+        .await?;
+        sqlx::query!("DELETE FROM message_templates WHERE guild_id = $1", id)
+            .execute(&mut *executor)
+            .await?;
+
+        let result = sqlx::query!(
+            "UPDATE allowed_guilds SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn get_guild_ids(executor: &mut sqlx::PgConnection) -> Result<Vec<u64>, sqlx::Error> {
         let guild_ids = Self::get_guilds(executor)
             .await?
@@ -30,4 +187,99 @@ impl AllowedGuild {
 
         Ok(guild_ids)
     }
+
+    pub async fn mark_verified(
+        executor: &mut sqlx::PgConnection,
+        id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE allowed_guilds SET last_verified_at = NOW() WHERE id = $1",
+            id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_removal_policy(
+        executor: &mut sqlx::PgConnection,
+        id: Uuid,
+        policy: RemovalPolicy,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE allowed_guilds SET removal_policy = $2 WHERE id = $1",
+            id,
+            policy as RemovalPolicy
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_removed_guild_can_be_added_back(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild = AllowedGuild::create(&mut conn, 42, "Server Novo")
+            .await
+            .unwrap();
+
+        assert!(AllowedGuild::delete(&mut conn, guild.id).await.unwrap());
+        assert!(!AllowedGuild::delete(&mut conn, guild.id).await.unwrap());
+        let removed = AllowedGuild::find_by_guild_id(&mut conn, 42).await.unwrap();
+        assert!(removed.is_none());
+
+        // The removed row is left behind for the links that pointed to it
+        let added = AllowedGuild::create(&mut conn, 42, "Server Novo")
+            .await
+            .unwrap();
+        assert_ne!(added.id, guild.id);
+        let found = AllowedGuild::find_by_guild_id(&mut conn, 42)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, added.id);
+    }
+
+    #[sqlx::test]
+    async fn test_get_guilds_with_stats(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query(
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id, deleted_at)
+            SELECT discord_id, discord_id, id, CASE WHEN discord_id = 3 THEN NOW() END
+            FROM allowed_guilds, (VALUES (1), (2), (3)) AS users (discord_id)
+            WHERE guild_id = 258648784039313408",
+        )
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+        let roles: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM allowed_roles")
+            .fetch_one(conn.as_mut())
+            .await
+            .unwrap();
+
+        let guilds = AllowedGuild::get_guilds_with_stats(&mut conn)
+            .await
+            .unwrap();
+        let user_count = |guild_id: i64| {
+            guilds
+                .iter()
+                .find(|stats| stats.guild.guild_id == guild_id)
+                .map(|stats| stats.user_count)
+        };
+
+        assert_eq!(guilds.len(), 2);
+        assert_eq!(user_count(258648784039313408), Some(2));
+        assert_eq!(user_count(1355012226355957780), Some(0));
+        assert!(guilds.iter().all(|stats| stats.role_count == roles));
+        assert!(guilds.iter().all(|stats| stats.channel_count == 2));
+    }
 }
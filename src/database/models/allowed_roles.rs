@@ -14,6 +14,15 @@ pub struct AllowedRole {
     pub updated_at: DateTime<Utc>,
 }
 
+/// How `AllowedRole::get_roles_ordered` sorts the roles it returns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoleOrder {
+    Name,
+    Id,
+    #[default]
+    CreatedAt,
+}
+
 pub struct AllowedRolePayload {
     pub role_id: i64,
     pub name: String,
@@ -39,6 +48,22 @@ impl AllowedRole {
         Ok(role.is_some())
     }
 
+    pub async fn find_by_role_id(
+        executor: &mut PgConnection,
+        role_id: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let role = sqlx::query_as!(
+            Self,
+            "SELECT * FROM allowed_roles WHERE role_id = $1",
+            role_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(role)
+    }
+
+    /// Inserts the role, or overwrites the name and admin flag of the role if it already exists
     pub async fn create(
         executor: &mut PgConnection,
         payload: AllowedRolePayload,
@@ -47,6 +72,7 @@ impl AllowedRole {
             Self,
             "INSERT INTO allowed_roles (role_id, name, is_admin)
             VALUES ($1, $2, $3)
+            ON CONFLICT (role_id) DO UPDATE SET name = $2, is_admin = $3
             RETURNING *",
             payload.role_id,
             payload.name,
@@ -58,6 +84,41 @@ impl AllowedRole {
         Ok(role)
     }
 
+    pub async fn update(
+        executor: &mut PgConnection,
+        payload: AllowedRolePayload,
+    ) -> Result<Self, sqlx::Error> {
+        let role = sqlx::query_as!(
+            Self,
+            "UPDATE allowed_roles SET name = $2, is_admin = $3
+            WHERE role_id = $1
+            RETURNING *",
+            payload.role_id,
+            payload.name,
+            payload.is_admin,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(role)
+    }
+
+    pub async fn update_name(
+        executor: &mut PgConnection,
+        role_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE allowed_roles SET name = $2 WHERE role_id = $1",
+            role_id,
+            name
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_roles(executor: &mut sqlx::PgConnection) -> Result<Vec<Self>, sqlx::Error> {
         let roles = sqlx::query_as!(Self, "SELECT * FROM allowed_roles")
             .fetch_all(executor)
@@ -66,6 +127,34 @@ impl AllowedRole {
         Ok(roles)
     }
 
+    pub async fn get_roles_ordered(
+        executor: &mut PgConnection,
+        order: RoleOrder,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let roles = match order {
+            RoleOrder::Name => {
+                sqlx::query_as!(Self, "SELECT * FROM allowed_roles ORDER BY name, role_id")
+                    .fetch_all(executor)
+                    .await?
+            }
+            RoleOrder::Id => {
+                sqlx::query_as!(Self, "SELECT * FROM allowed_roles ORDER BY role_id")
+                    .fetch_all(executor)
+                    .await?
+            }
+            RoleOrder::CreatedAt => {
+                sqlx::query_as!(
+                    Self,
+                    "SELECT * FROM allowed_roles ORDER BY created_at, role_id"
+                )
+                .fetch_all(executor)
+                .await?
+            }
+        };
+
+        Ok(roles)
+    }
+
     pub async fn get_admin_ids(executor: &mut PgConnection) -> Result<Vec<u64>, sqlx::Error> {
         let admin_roles =
             sqlx::query_as!(Self, "SELECT * FROM allowed_roles WHERE is_admin = TRUE")
@@ -78,6 +167,18 @@ impl AllowedRole {
         Ok(admin_roles)
     }
 
+    /// Ids of the subscriber roles, every allowed role that isn't an admin one
+    pub async fn get_non_admin_ids(executor: &mut PgConnection) -> Result<Vec<u64>, sqlx::Error> {
+        let roles = sqlx::query_as!(Self, "SELECT * FROM allowed_roles WHERE is_admin = FALSE")
+            .fetch_all(executor)
+            .await?
+            .into_iter()
+            .map(|role| role.role_id as u64)
+            .collect_vec();
+
+        Ok(roles)
+    }
+
     pub async fn get_role_ids(executor: &mut sqlx::PgConnection) -> Result<Vec<u64>, sqlx::Error> {
         let role_ids = sqlx::query_as!(Self, "SELECT * FROM allowed_roles")
             .fetch_all(executor)
@@ -0,0 +1,38 @@
+use serde_json::Value;
+use sqlx::PgConnection;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// A durable record of something an admin did through the bot
+#[allow(dead_code)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    /// Discord id of who did it, `None` for actions not tied to a Discord user
+    pub actor_discord_id: Option<i64>,
+    pub action: String,
+    pub details: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub async fn record(
+        executor: &mut PgConnection,
+        actor_discord_id: Option<i64>,
+        action: &str,
+        details: &Value,
+    ) -> sqlx::Result<Self> {
+        let entry = sqlx::query_as!(
+            Self,
+            "INSERT INTO audit_log (actor_discord_id, action, details) VALUES ($1, $2, $3)
+            RETURNING *",
+            actor_discord_id,
+            action,
+            details
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(entry)
+    }
+}
@@ -0,0 +1,30 @@
+use sqlx::PgConnection;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Progress of the role verification, kept in the database so monitoring can tell when the
+/// cron stopped running
+pub struct CronStatus;
+
+impl CronStatus {
+    pub async fn record_success(executor: &mut PgConnection) -> sqlx::Result<DateTime<Utc>> {
+        let recorded_at = sqlx::query_scalar!(
+            "INSERT INTO cron_status (last_successful_cycle_at) VALUES (NOW())
+            ON CONFLICT (id) DO UPDATE SET last_successful_cycle_at = EXCLUDED.last_successful_cycle_at
+            RETURNING last_successful_cycle_at"
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(recorded_at)
+    }
+
+    pub async fn last_successful_cycle_at(
+        executor: &mut PgConnection,
+    ) -> sqlx::Result<Option<DateTime<Utc>>> {
+        let last = sqlx::query_scalar!("SELECT last_successful_cycle_at FROM cron_status")
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(last)
+    }
+}
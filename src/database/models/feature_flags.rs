@@ -0,0 +1,82 @@
+use serde::Serialize;
+use sqlx::PgConnection;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// A runtime toggle, flags without a row are treated as disabled
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FeatureFlag {
+    pub async fn get_all(executor: &mut PgConnection) -> sqlx::Result<Vec<Self>> {
+        let flags = sqlx::query_as!(Self, "SELECT * FROM feature_flags ORDER BY name")
+            .fetch_all(executor)
+            .await?;
+
+        Ok(flags)
+    }
+
+    pub async fn is_enabled(executor: &mut PgConnection, name: &str) -> sqlx::Result<bool> {
+        let enabled =
+            sqlx::query_scalar!("SELECT enabled FROM feature_flags WHERE name = $1", name)
+                .fetch_optional(executor)
+                .await?;
+
+        Ok(enabled.unwrap_or(false))
+    }
+
+    pub async fn set(executor: &mut PgConnection, name: &str, enabled: bool) -> sqlx::Result<Self> {
+        let flag = sqlx::query_as!(
+            Self,
+            "INSERT INTO feature_flags (name, enabled) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE SET enabled = EXCLUDED.enabled
+            RETURNING *",
+            name,
+            enabled
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_missing_flag_is_disabled_until_set(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+
+        assert!(
+            !FeatureFlag::is_enabled(&mut conn, "cron_dry_run")
+                .await
+                .unwrap()
+        );
+
+        FeatureFlag::set(&mut conn, "cron_dry_run", true)
+            .await
+            .unwrap();
+        assert!(
+            FeatureFlag::is_enabled(&mut conn, "cron_dry_run")
+                .await
+                .unwrap()
+        );
+
+        FeatureFlag::set(&mut conn, "cron_dry_run", false)
+            .await
+            .unwrap();
+        assert!(
+            !FeatureFlag::is_enabled(&mut conn, "cron_dry_run")
+                .await
+                .unwrap()
+        );
+        assert_eq!(FeatureFlag::get_all(&mut conn).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+use sqlx::PgConnection;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Text an admin saved in place of one of the bot's default messages
+#[allow(dead_code)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MessageTemplate {
+    pub id: Uuid,
+    pub name: String,
+    /// Telegram HTML, validated when it is saved
+    pub content: String,
+    /// Guild the template applies to, `None` for every guild
+    pub guild_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl MessageTemplate {
+    /// Reply to `/start`, `{usuario}` is replaced by the user's name and the link to link the
+    /// accounts is always added after it
+    pub const WELCOME: &str = "boas_vindas";
+    /// Reply to `/status` for users that didn't link their accounts yet
+    pub const UNLINKED: &str = "nao_vinculado";
+    /// Heading of the invite, the invite link is always added after it
+    pub const INVITE: &str = "convite";
+
+    /// The template of the guild if it has one, otherwise the one shared by every guild
+    pub async fn find_by_name(
+        executor: &mut PgConnection,
+        name: &str,
+        guild_id: Option<Uuid>,
+    ) -> sqlx::Result<Option<Self>> {
+        let template = sqlx::query_as!(
+            Self,
+            "SELECT * FROM message_templates
+            WHERE name = $1 AND (guild_id = $2 OR guild_id IS NULL)
+            ORDER BY guild_id NULLS LAST
+            LIMIT 1",
+            name,
+            guild_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(template)
+    }
+
+    pub async fn get_all(executor: &mut PgConnection) -> sqlx::Result<Vec<Self>> {
+        let templates = sqlx::query_as!(
+            Self,
+            "SELECT * FROM message_templates ORDER BY name, guild_id NULLS FIRST"
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(templates)
+    }
+
+    pub async fn upsert(
+        executor: &mut PgConnection,
+        name: &str,
+        content: &str,
+        guild_id: Option<Uuid>,
+    ) -> sqlx::Result<Self> {
+        let template = sqlx::query_as!(
+            Self,
+            "INSERT INTO message_templates (name, content, guild_id) VALUES ($1, $2, $3)
+            ON CONFLICT (name, guild_id)
+            DO UPDATE SET content = EXCLUDED.content
+            RETURNING *",
+            name,
+            content,
+            guild_id
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(template)
+    }
+
+    pub async fn delete(
+        executor: &mut PgConnection,
+        name: &str,
+        guild_id: Option<Uuid>,
+    ) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM message_templates WHERE name = $1 AND guild_id IS NOT DISTINCT FROM $2",
+            name,
+            guild_id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn felpinho_id(conn: &mut PgConnection) -> Uuid {
+        sqlx::query_scalar("SELECT id FROM allowed_guilds WHERE guild_id = 258648784039313408")
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_guild_template_overrides_global_one(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = felpinho_id(&mut conn).await;
+        let find = async |conn: &mut PgConnection, guild_id| {
+            MessageTemplate::find_by_name(conn, MessageTemplate::INVITE, guild_id)
+                .await
+                .unwrap()
+                .map(|template| template.content)
+        };
+
+        assert_eq!(find(&mut conn, Some(guild_id)).await, None);
+
+        MessageTemplate::upsert(&mut conn, MessageTemplate::INVITE, "Global", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            find(&mut conn, Some(guild_id)).await.as_deref(),
+            Some("Global")
+        );
+
+        MessageTemplate::upsert(
+            &mut conn,
+            MessageTemplate::INVITE,
+            "Felpinho",
+            Some(guild_id),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            find(&mut conn, Some(guild_id)).await.as_deref(),
+            Some("Felpinho")
+        );
+        assert_eq!(find(&mut conn, None).await.as_deref(), Some("Global"));
+
+        let deleted = MessageTemplate::delete(&mut conn, MessageTemplate::INVITE, Some(guild_id))
+            .await
+            .unwrap();
+        assert!(deleted);
+        assert_eq!(
+            find(&mut conn, Some(guild_id)).await.as_deref(),
+            Some("Global")
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_upsert_replaces_global_template(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+
+        MessageTemplate::upsert(&mut conn, MessageTemplate::WELCOME, "Oi", None)
+            .await
+            .unwrap();
+        MessageTemplate::upsert(&mut conn, MessageTemplate::WELCOME, "Olá", None)
+            .await
+            .unwrap();
+
+        let templates = MessageTemplate::get_all(&mut conn).await.unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].content, "Olá");
+    }
+}
@@ -1,5 +1,27 @@
-pub mod allowed_channels;
-pub mod allowed_guilds;
-pub mod allowed_roles;
-pub mod oauth_state;
-pub mod user_links;
+mod allowed_channels;
+mod allowed_guilds;
+mod allowed_roles;
+mod audit_log;
+mod cron_status;
+mod feature_flags;
+mod message_templates;
+mod oauth_state;
+mod role_group_mappings;
+mod telegram_dead_letters;
+mod telegram_groups;
+mod user_links;
+
+pub use allowed_channels::{AllowedChannel, AllowedChannelPayload};
+pub use allowed_guilds::{AllowedGuild, GuildWithStats, RemovalPolicy};
+pub use allowed_roles::{AllowedRole, AllowedRolePayload, RoleOrder};
+pub use audit_log::AuditEntry;
+pub use cron_status::CronStatus;
+pub use feature_flags::FeatureFlag;
+pub use message_templates::MessageTemplate;
+pub use oauth_state::OAuthState;
+pub use role_group_mappings::{MappedGroup, RoleGroupMapping, RoleGroupMappingPayload};
+pub use telegram_dead_letters::TelegramDeadLetter;
+pub use telegram_groups::{TelegramGroup, TelegramGroupPayload};
+pub use user_links::{
+    DiscordOAuthPayload, MAX_EXPORT_ROWS, UserLink, UserLinkPayload, UserLinkUpdatePayload,
+};
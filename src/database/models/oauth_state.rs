@@ -12,6 +12,7 @@ pub struct OAuthState {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
 }
 
 impl OAuthState {
@@ -32,13 +33,17 @@ impl OAuthState {
         Ok(state)
     }
 
-    pub async fn get_and_delete(
+    /// Marks a pending state as consumed. Consumed states are kept until they expire so a
+    /// repeated callback can still find out which telegram account the flow belonged to.
+    pub async fn consume(
         executor: &mut PgConnection,
         token: &str,
     ) -> sqlx::Result<Option<OAuthState>> {
         let result = sqlx::query_as!(
             OAuthState,
-            "DELETE FROM oauth_states WHERE state_token = $1 AND expires_at > NOW() RETURNING *",
+            "UPDATE oauth_states SET consumed_at = NOW()
+            WHERE state_token = $1 AND consumed_at IS NULL AND expires_at > NOW()
+            RETURNING *",
             token
         )
         .fetch_optional(executor)
@@ -46,4 +51,103 @@ impl OAuthState {
 
         Ok(result)
     }
+
+    pub async fn find_consumed(
+        executor: &mut PgConnection,
+        token: &str,
+    ) -> sqlx::Result<Option<OAuthState>> {
+        let result = sqlx::query_as!(
+            OAuthState,
+            "SELECT * FROM oauth_states
+            WHERE state_token = $1 AND consumed_at IS NOT NULL AND expires_at > NOW()",
+            token
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn cleanup_expired(executor: &mut PgConnection) -> sqlx::Result<u64> {
+        let result = sqlx::query!("DELETE FROM oauth_states WHERE expires_at <= NOW()")
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn expire_token(conn: &mut PgConnection, token: &str) {
+        sqlx::query(
+            "UPDATE oauth_states SET expires_at = NOW() - interval '1 minute' WHERE state_token = $1",
+        )
+        .bind(token)
+        .execute(conn)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_create_and_get(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let created = OAuthState::create(&mut conn, 123, "token").await.unwrap();
+        assert_eq!(created.telegram_id, 123);
+        assert_eq!(created.state_token, "token");
+
+        let state = OAuthState::consume(&mut conn, "token").await.unwrap();
+        let state = state.unwrap();
+        assert_eq!(state.id, created.id);
+        assert_eq!(state.telegram_id, 123);
+    }
+
+    #[sqlx::test]
+    async fn test_get_expired_state(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        OAuthState::create(&mut conn, 123, "token").await.unwrap();
+        expire_token(&mut conn, "token").await;
+
+        let state = OAuthState::consume(&mut conn, "token").await.unwrap();
+        assert!(state.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_double_delete(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        OAuthState::create(&mut conn, 123, "token").await.unwrap();
+
+        let first = OAuthState::consume(&mut conn, "token").await.unwrap();
+        assert!(first.is_some());
+
+        let second = OAuthState::consume(&mut conn, "token").await.unwrap();
+        assert!(second.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_token_mismatch(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        OAuthState::create(&mut conn, 123, "token").await.unwrap();
+
+        let state = OAuthState::consume(&mut conn, "other").await.unwrap();
+        assert!(state.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_cleanup_expired(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        OAuthState::create(&mut conn, 1, "expired").await.unwrap();
+        OAuthState::create(&mut conn, 2, "valid").await.unwrap();
+        expire_token(&mut conn, "expired").await;
+
+        let deleted = OAuthState::cleanup_expired(&mut conn).await.unwrap();
+        assert_eq!(deleted, 1);
+
+        let valid = OAuthState::consume(&mut conn, "valid").await.unwrap();
+        assert!(valid.is_some());
+    }
 }
@@ -0,0 +1,109 @@
+use sqlx::PgConnection;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Sends users with an allowed role to a specific telegram group instead of the guild's first one
+#[allow(dead_code)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RoleGroupMapping {
+    pub id: Uuid,
+    pub role_id: Uuid,
+    pub group_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A mapping joined with the role and group it points to
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MappedGroup {
+    pub discord_role_id: i64,
+    pub role_name: String,
+    pub telegram_group_id: i64,
+    pub group_name: String,
+}
+
+#[derive(Debug)]
+pub struct RoleGroupMappingPayload {
+    pub role_id: Uuid,
+    pub group_id: Uuid,
+}
+
+impl RoleGroupMappingPayload {
+    pub fn new(role_id: Uuid, group_id: Uuid) -> Self {
+        Self { role_id, group_id }
+    }
+}
+
+impl RoleGroupMapping {
+    pub async fn create(
+        executor: &mut PgConnection,
+        payload: RoleGroupMappingPayload,
+    ) -> Result<Self, sqlx::Error> {
+        let mapping = sqlx::query_as!(
+            Self,
+            "INSERT INTO role_group_mappings (role_id, group_id)
+            VALUES ($1, $2)
+            RETURNING *",
+            payload.role_id,
+            payload.group_id,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(mapping)
+    }
+
+    /// Whether the role is already mapped to a group, `role_id` is the id of the
+    /// `allowed_roles` row
+    pub async fn exists(executor: &mut PgConnection, role_id: Uuid) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM role_group_mappings WHERE role_id = $1)",
+            role_id
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(exists.unwrap_or_default())
+    }
+
+    /// `role_id` is the id of the `allowed_roles` row, not the discord role id
+    pub async fn find_by_role(
+        executor: &mut PgConnection,
+        role_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let mapping = sqlx::query_as!(
+            Self,
+            "SELECT * FROM role_group_mappings WHERE role_id = $1",
+            role_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(mapping)
+    }
+
+    /// Mappings whose group belongs to the allowed guild, admin roles first so users with both
+    /// kinds of role end up in the admin group
+    pub async fn get_all_for_guild(
+        executor: &mut PgConnection,
+        allowed_guild_id: Uuid,
+    ) -> Result<Vec<MappedGroup>, sqlx::Error> {
+        let mappings = sqlx::query_file_as!(
+            MappedGroup,
+            "src/database/queries/role_group_mappings_get_all_for_guild.sql",
+            allowed_guild_id
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(mappings)
+    }
+
+    pub async fn delete(executor: &mut PgConnection, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM role_group_mappings WHERE id = $1", id)
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use sqlx::PgConnection;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// A Telegram action that still failed after its retries, kept so operators can redo it by hand
+#[allow(dead_code)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TelegramDeadLetter {
+    pub id: Uuid,
+    pub action_kind: String,
+    /// User the action was for, `None` for broadcasts
+    pub telegram_id: Option<i64>,
+    pub group_id: i64,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl TelegramDeadLetter {
+    pub async fn insert(
+        executor: &mut PgConnection,
+        action_kind: &str,
+        telegram_id: Option<i64>,
+        group_id: i64,
+        last_error: &str,
+    ) -> sqlx::Result<Self> {
+        let dead_letter = sqlx::query_as!(
+            Self,
+            "INSERT INTO telegram_dead_letters (action_kind, telegram_id, group_id, last_error)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *",
+            action_kind,
+            telegram_id,
+            group_id,
+            last_error
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(dead_letter)
+    }
+
+    /// Newest failures first
+    pub async fn list_recent(executor: &mut PgConnection, limit: i64) -> sqlx::Result<Vec<Self>> {
+        let dead_letters = sqlx::query_as!(
+            Self,
+            "SELECT * FROM telegram_dead_letters ORDER BY failed_at DESC, id LIMIT $1",
+            limit
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(dead_letters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_insert_and_list_recent(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+
+        let invite = TelegramDeadLetter::insert(&mut conn, "invite", Some(777), -100, "Forbidden")
+            .await
+            .unwrap();
+        assert_eq!(invite.telegram_id, Some(777));
+        assert_eq!(invite.last_error, "Forbidden");
+
+        sqlx::query("UPDATE telegram_dead_letters SET failed_at = NOW() - INTERVAL '1 hour'")
+            .execute(conn.as_mut())
+            .await
+            .unwrap();
+        TelegramDeadLetter::insert(&mut conn, "broadcast", None, -100, "Network error")
+            .await
+            .unwrap();
+
+        let recent = TelegramDeadLetter::list_recent(&mut conn, 10)
+            .await
+            .unwrap();
+        let kinds = recent
+            .iter()
+            .map(|dead_letter| dead_letter.action_kind.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, ["broadcast", "invite"]);
+
+        let recent = TelegramDeadLetter::list_recent(&mut conn, 1).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].telegram_id, None);
+    }
+}
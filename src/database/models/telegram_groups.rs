@@ -0,0 +1,173 @@
+use sqlx::types::Uuid;
+use sqlx::types::chrono::{DateTime, Utc};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TelegramGroup {
+    pub id: Uuid,
+    pub allowed_guild_id: Uuid,
+    pub telegram_group_id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Description the group should have, as last set through the bot
+    pub description: Option<String>,
+    /// Title the group should have, as last set through the bot
+    pub title: Option<String>,
+    /// Replaces the default text of the invite users get for this group
+    pub invite_message: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TelegramGroupPayload {
+    pub allowed_guild_id: Uuid,
+    pub telegram_group_id: i64,
+    pub name: String,
+}
+
+impl TelegramGroupPayload {
+    pub fn new(allowed_guild_id: Uuid, telegram_group_id: i64, name: String) -> Self {
+        Self {
+            allowed_guild_id,
+            telegram_group_id,
+            name,
+        }
+    }
+}
+
+impl TelegramGroup {
+    /// Whether `chat_id` is one of the configured telegram groups
+    pub async fn is_telegram_group(
+        executor: &mut sqlx::PgConnection,
+        chat_id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM telegram_groups WHERE telegram_group_id = $1)",
+            chat_id
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(exists.unwrap_or_default())
+    }
+
+    pub async fn create(
+        executor: &mut sqlx::PgConnection,
+        payload: TelegramGroupPayload,
+    ) -> Result<Self, sqlx::Error> {
+        let group = sqlx::query_file_as!(
+            Self,
+            "src/database/queries/telegram_groups_upsert.sql",
+            payload.allowed_guild_id,
+            payload.telegram_group_id,
+            payload.name,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Returns the oldest telegram group mapped to the given allowed guild
+    pub async fn find_by_guild(
+        executor: &mut sqlx::PgConnection,
+        allowed_guild_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let group = sqlx::query_as!(
+            Self,
+            "SELECT * FROM telegram_groups WHERE allowed_guild_id = $1 ORDER BY created_at LIMIT 1",
+            allowed_guild_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Returns every telegram group, oldest first
+    pub async fn get_all(executor: &mut sqlx::PgConnection) -> Result<Vec<Self>, sqlx::Error> {
+        let groups = sqlx::query_as!(Self, "SELECT * FROM telegram_groups ORDER BY created_at")
+            .fetch_all(executor)
+            .await?;
+
+        Ok(groups)
+    }
+
+    pub async fn find_by_telegram_group_id(
+        executor: &mut sqlx::PgConnection,
+        telegram_group_id: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let group = sqlx::query_as!(
+            Self,
+            "SELECT * FROM telegram_groups WHERE telegram_group_id = $1",
+            telegram_group_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(group)
+    }
+
+    pub async fn update_description(
+        executor: &mut sqlx::PgConnection,
+        telegram_group_id: i64,
+        description: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE telegram_groups SET description = $2 WHERE telegram_group_id = $1",
+            telegram_group_id,
+            description
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_title(
+        executor: &mut sqlx::PgConnection,
+        telegram_group_id: i64,
+        title: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE telegram_groups SET title = $2 WHERE telegram_group_id = $1",
+            telegram_group_id,
+            title
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Custom invite text of the group, `None` if the group is unknown or uses the default
+    pub async fn find_invite_message(
+        executor: &mut sqlx::PgConnection,
+        telegram_group_id: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let message = sqlx::query_scalar!(
+            "SELECT invite_message FROM telegram_groups WHERE telegram_group_id = $1",
+            telegram_group_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(message.flatten())
+    }
+
+    pub async fn update_invite_message(
+        executor: &mut sqlx::PgConnection,
+        telegram_group_id: i64,
+        invite_message: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE telegram_groups SET invite_message = $2 WHERE telegram_group_id = $1",
+            telegram_group_id,
+            invite_message
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -3,31 +3,135 @@ use sqlx::PgConnection;
 use sqlx::prelude::FromRow;
 use sqlx::types::Uuid;
 
+use crate::utils::pagination::{Cursor, CursorParams, PaginationParams};
+
+/// Most links `UserLink::export_for_guild` returns, so an export can't exhaust memory
+pub const MAX_EXPORT_ROWS: i64 = 10_000;
+
 #[allow(dead_code)]
-#[derive(Debug, FromRow)]
+#[derive(FromRow)]
 pub struct UserLink {
     pub id: Uuid,
     pub discord_id: i64,
     pub telegram_id: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the invite to the telegram group was sent
     pub added_to_group_at: Option<DateTime<Utc>>,
+    /// When the user was seen in the telegram group, `None` while the invite is pending
+    pub joined_group_at: Option<DateTime<Utc>>,
+    /// Telegram group the user was invited to, `None` for links made before it was kept, those
+    /// users were invited to the group of their guild
+    pub telegram_group_id: Option<i64>,
     pub last_subscription_check: Option<DateTime<Utc>>,
+    /// Guild the user was verified in when they linked, the cron only checks them there
+    pub guild_id: Uuid,
+    /// When the user was made read only in the telegram group for missing the allowed roles
+    pub restricted_at: Option<DateTime<Utc>>,
+    /// When the user was removed from the telegram group, removed links are left out of reads
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// OAuth tokens of links made with the `guilds.members.read` scope, they let the roles be
+    /// read in guilds the bot can't be in
+    pub discord_access_token: Option<String>,
+    pub discord_refresh_token: Option<String>,
+    pub discord_token_expires_at: Option<DateTime<Utc>>,
+    /// Roles the user had the last time they were read with their OAuth token
+    pub discord_roles: Option<Vec<i64>>,
+}
+
+const REDACTED: &str = "***";
+
+// Written by hand so tracing a link never leaks its OAuth tokens
+impl std::fmt::Debug for UserLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |token: &Option<String>| token.as_ref().map(|_| REDACTED);
+
+        f.debug_struct("UserLink")
+            .field("id", &self.id)
+            .field("discord_id", &self.discord_id)
+            .field("telegram_id", &self.telegram_id)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("added_to_group_at", &self.added_to_group_at)
+            .field("joined_group_at", &self.joined_group_at)
+            .field("telegram_group_id", &self.telegram_group_id)
+            .field("last_subscription_check", &self.last_subscription_check)
+            .field("guild_id", &self.guild_id)
+            .field("restricted_at", &self.restricted_at)
+            .field("deleted_at", &self.deleted_at)
+            .field(
+                "discord_access_token",
+                &redacted(&self.discord_access_token),
+            )
+            .field(
+                "discord_refresh_token",
+                &redacted(&self.discord_refresh_token),
+            )
+            .field("discord_token_expires_at", &self.discord_token_expires_at)
+            .field("discord_roles", &self.discord_roles)
+            .finish()
+    }
+}
+
+/// OAuth tokens granted with `guilds.members.read`
+#[derive(Debug)]
+pub struct DiscordOAuthPayload {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Ids shared by more than one link, each with the links sharing it, oldest first
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DuplicateLinks {
+    pub telegram_ids: Vec<(i64, Vec<Uuid>)>,
+    pub discord_ids: Vec<(i64, Vec<Uuid>)>,
+}
+
+impl DuplicateLinks {
+    pub fn is_empty(&self) -> bool {
+        self.telegram_ids.is_empty() && self.discord_ids.is_empty()
+    }
+
+    /// Every link involved in a duplicate
+    pub fn link_ids(&self) -> impl Iterator<Item = &Uuid> {
+        self.telegram_ids
+            .iter()
+            .chain(&self.discord_ids)
+            .flat_map(|(_, ids)| ids)
+    }
 }
 
 #[derive(Debug)]
 pub struct UserLinkPayload {
     pub discord_id: i64,
     pub telegram_id: i64,
+    pub guild_id: Uuid,
+    pub telegram_group_id: Option<i64>,
 }
 
 impl UserLinkPayload {
-    pub fn new(discord_id: i64, telegram_id: i64) -> Self {
+    pub fn new(discord_id: i64, telegram_id: i64, guild_id: Uuid) -> Self {
         Self {
             discord_id,
             telegram_id,
+            guild_id,
+            telegram_group_id: None,
         }
     }
+
+    /// Keeps the telegram group the user is invited to
+    pub fn with_telegram_group(mut self, telegram_group_id: i64) -> Self {
+        self.telegram_group_id = Some(telegram_group_id);
+        self
+    }
+}
+
+/// Fields of a link to change, `None` keeps the current value
+#[derive(Debug, Default)]
+pub struct UserLinkUpdatePayload {
+    pub telegram_id: Option<i64>,
+    pub discord_id: Option<i64>,
 }
 
 impl UserLink {
@@ -38,12 +142,54 @@ impl UserLink {
         let user_link = sqlx::query_as!(
             UserLink,
             r#"
-            INSERT INTO user_links (discord_id, telegram_id)
-            VALUES ($1, $2)
+            INSERT INTO user_links (discord_id, telegram_id, guild_id, telegram_group_id)
+            VALUES ($1, $2, $3, $4)
             RETURNING *
             "#,
             new_link.discord_id,
             new_link.telegram_id,
+            new_link.guild_id,
+            new_link.telegram_group_id,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(user_link)
+    }
+
+    pub async fn find_by_id(
+        executor: &mut PgConnection,
+        id: Uuid,
+    ) -> sqlx::Result<Option<UserLink>> {
+        let user_link = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(user_link)
+    }
+
+    pub async fn update(
+        executor: &mut PgConnection,
+        id: Uuid,
+        payload: UserLinkUpdatePayload,
+    ) -> sqlx::Result<UserLink> {
+        let user_link = sqlx::query_as!(
+            UserLink,
+            r#"
+            UPDATE user_links
+            SET telegram_id = COALESCE($2, telegram_id),
+                discord_id = COALESCE($3, discord_id),
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+            id,
+            payload.telegram_id,
+            payload.discord_id,
         )
         .fetch_one(executor)
         .await?;
@@ -57,7 +203,7 @@ impl UserLink {
     ) -> sqlx::Result<Option<UserLink>> {
         let user_link = sqlx::query_as!(
             UserLink,
-            "SELECT * FROM user_links WHERE discord_id = $1",
+            "SELECT * FROM user_links WHERE discord_id = $1 AND deleted_at IS NULL",
             discord_id
         )
         .fetch_optional(executor)
@@ -72,7 +218,7 @@ impl UserLink {
     ) -> sqlx::Result<Option<UserLink>> {
         let user_link = sqlx::query_as!(
             UserLink,
-            "SELECT * FROM user_links WHERE telegram_id = $1",
+            "SELECT * FROM user_links WHERE telegram_id = $1 AND deleted_at IS NULL",
             telegram_id
         )
         .fetch_optional(executor)
@@ -92,22 +238,624 @@ impl UserLink {
         Ok(())
     }
 
-    pub async fn get_all_users(executor: &mut PgConnection) -> sqlx::Result<Vec<UserLink>> {
-        let users = sqlx::query_as!(UserLink, "SELECT * FROM user_links")
-            .fetch_all(executor)
-            .await?;
+    /// Records that the user is in the telegram group, only the first time they are seen there
+    pub async fn mark_joined_group(
+        executor: &mut PgConnection,
+        telegram_id: i64,
+    ) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE user_links SET joined_group_at = NOW()
+            WHERE telegram_id = $1 AND joined_group_at IS NULL AND deleted_at IS NULL",
+            telegram_id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Sets or, with `None`, clears when the user was made read only in the telegram group
+    pub async fn set_restricted_at(
+        executor: &mut PgConnection,
+        id: &Uuid,
+        restricted_at: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE user_links SET restricted_at = $2, updated_at = NOW() WHERE id = $1",
+            id,
+            restricted_at
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Active links of users that linked through the guild
+    pub async fn get_guild_users(
+        executor: &mut PgConnection,
+        guild_id: Uuid,
+    ) -> sqlx::Result<Vec<UserLink>> {
+        let users = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE guild_id = $1 AND deleted_at IS NULL",
+            guild_id
+        )
+        .fetch_all(executor)
+        .await?;
 
         Ok(users)
     }
 
-    pub async fn delete_by_discord_id(
+    /// Returns a page of the guild's users, oldest first, along with the guild's total
+    pub async fn get_paginated(
+        executor: &mut PgConnection,
+        guild_id: Uuid,
+        params: PaginationParams,
+    ) -> sqlx::Result<(Vec<UserLink>, i64)> {
+        let users = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE guild_id = $1 AND deleted_at IS NULL
+            ORDER BY created_at, id
+            OFFSET $2 LIMIT $3",
+            guild_id,
+            params.offset,
+            params.limit
+        )
+        .fetch_all(&mut *executor)
+        .await?;
+
+        let total = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM user_links WHERE guild_id = $1 AND deleted_at IS NULL",
+            guild_id
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok((users, total.unwrap_or_default()))
+    }
+
+    /// Returns the page of the guild's users after `params.cursor`, oldest first, along with
+    /// the cursor of the next page when there is one
+    pub async fn get_users_page(
+        executor: &mut PgConnection,
+        guild_id: Uuid,
+        params: CursorParams,
+    ) -> sqlx::Result<(Vec<UserLink>, Option<Cursor>)> {
+        // One extra row is fetched to tell whether a next page exists
+        let mut users = sqlx::query_file_as!(
+            UserLink,
+            "src/database/queries/user_links_get_page.sql",
+            guild_id,
+            params.cursor.map(|cursor| cursor.created_at),
+            params.cursor.map(|cursor| cursor.id),
+            params.limit + 1
+        )
+        .fetch_all(executor)
+        .await?;
+
+        let next_cursor = if users.len() as i64 > params.limit {
+            users.truncate(params.limit as usize);
+            users.last().map(|user| Cursor {
+                created_at: user.created_at,
+                id: user.id,
+            })
+        } else {
+            None
+        };
+
+        Ok((users, next_cursor))
+    }
+
+    /// Users added to the telegram group at or after `since`
+    pub async fn get_recently_added(
+        executor: &mut PgConnection,
+        since: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<UserLink>> {
+        let users = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE added_to_group_at >= $1 AND deleted_at IS NULL",
+            since
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Users that linked at or after `after`, removed links included so cohorts can tell how
+    /// many of them left
+    pub async fn get_added_after(
+        executor: &mut PgConnection,
+        after: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<UserLink>> {
+        let users = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE created_at >= $1 ORDER BY created_at, id",
+            after
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Like `get_added_after`, for users that linked from `start` up to but not including `end`
+    pub async fn get_added_between(
+        executor: &mut PgConnection,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<UserLink>> {
+        let users = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE created_at >= $1 AND created_at < $2
+            ORDER BY created_at, id",
+            start,
+            end
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Returns up to `MAX_EXPORT_ROWS` of the guild's users, oldest first
+    pub async fn export_for_guild(
+        executor: &mut PgConnection,
+        guild_id: Uuid,
+    ) -> sqlx::Result<Vec<UserLink>> {
+        let users = sqlx::query_as!(
+            UserLink,
+            "SELECT * FROM user_links WHERE guild_id = $1 AND deleted_at IS NULL
+            ORDER BY created_at, id
+            LIMIT $2",
+            guild_id,
+            MAX_EXPORT_ROWS
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Finds telegram or discord ids linked more than once, which the unique indexes
+    /// should prevent but data merged by hand can still introduce
+    pub async fn find_duplicates(executor: &mut PgConnection) -> sqlx::Result<DuplicateLinks> {
+        let telegram_ids =
+            sqlx::query_file!("src/database/queries/user_links_duplicate_telegram_ids.sql")
+                .fetch_all(&mut *executor)
+                .await?
+                .into_iter()
+                .map(|row| (row.telegram_id, row.ids))
+                .collect();
+
+        let discord_ids =
+            sqlx::query_file!("src/database/queries/user_links_duplicate_discord_ids.sql")
+                .fetch_all(executor)
+                .await?
+                .into_iter()
+                .map(|row| (row.discord_id, row.ids))
+                .collect();
+
+        Ok(DuplicateLinks {
+            telegram_ids,
+            discord_ids,
+        })
+    }
+
+    /// Deletes links created before `cutoff` whose user never made it into the group, whether
+    /// they were never invited or never accepted the invite
+    pub async fn delete_stale_pending(
+        executor: &mut PgConnection,
+        cutoff: DateTime<Utc>,
+    ) -> sqlx::Result<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM user_links
+            WHERE joined_group_at IS NULL AND created_at < $1 AND deleted_at IS NULL",
+            cutoff
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Soft deletes the user's link once they are removed from the telegram group, keeping the
+    /// row around as a record of the removal. Its OAuth tokens are dropped since nothing will
+    /// use them anymore
+    pub async fn mark_removed_from_group(
         executor: &mut PgConnection,
         discord_id: i64,
     ) -> sqlx::Result<()> {
-        sqlx::query!("DELETE FROM user_links WHERE discord_id = $1", discord_id)
-            .execute(executor)
-            .await?;
+        sqlx::query!(
+            "UPDATE user_links
+            SET deleted_at = NOW(), added_to_group_at = NULL, updated_at = NOW(),
+                discord_access_token = NULL, discord_refresh_token = NULL,
+                discord_token_expires_at = NULL
+            WHERE discord_id = $1 AND deleted_at IS NULL",
+            discord_id
+        )
+        .execute(executor)
+        .await?;
 
         Ok(())
     }
+
+    /// Soft deletes every active link of users that joined through the guild, like
+    /// `mark_removed_from_group` does for a single user. Returns the unlinked users so the caller
+    /// can remove them from their group
+    pub async fn bulk_unlink_by_guild(
+        executor: &mut PgConnection,
+        guild_id: Uuid,
+    ) -> sqlx::Result<Vec<UserLink>> {
+        let unlinked = sqlx::query_as!(
+            UserLink,
+            "UPDATE user_links
+            SET deleted_at = NOW(), added_to_group_at = NULL, updated_at = NOW(),
+                discord_access_token = NULL, discord_refresh_token = NULL,
+                discord_token_expires_at = NULL
+            WHERE guild_id = $1 AND deleted_at IS NULL
+            RETURNING *",
+            guild_id
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(unlinked)
+    }
+
+    pub async fn set_discord_oauth(
+        executor: &mut PgConnection,
+        id: &Uuid,
+        payload: DiscordOAuthPayload,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE user_links
+            SET discord_access_token = $2, discord_refresh_token = $3,
+                discord_token_expires_at = $4, updated_at = NOW()
+            WHERE id = $1",
+            id,
+            payload.access_token,
+            payload.refresh_token,
+            payload.expires_at
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_discord_roles(
+        executor: &mut PgConnection,
+        id: &Uuid,
+        roles: &[i64],
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE user_links SET discord_roles = $2, updated_at = NOW() WHERE id = $1",
+            id,
+            roles
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_helpers::default_guild_id;
+
+    async fn create_guild(conn: &mut PgConnection, guild_id: i64) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO allowed_guilds (guild_id, name) VALUES ($1, 'Test') RETURNING id",
+        )
+        .bind(guild_id)
+        .fetch_one(conn)
+        .await
+        .unwrap()
+    }
+
+    async fn create_guild_user(
+        conn: &mut PgConnection,
+        guild_id: Uuid,
+        discord_id: i64,
+        telegram_id: i64,
+    ) -> UserLink {
+        let payload = UserLinkPayload::new(discord_id, telegram_id, guild_id);
+        UserLink::create_link(conn, payload).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_get_paginated(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let first_guild = create_guild(&mut conn, 1).await;
+        let second_guild = create_guild(&mut conn, 2).await;
+
+        let first_user = create_guild_user(&mut conn, first_guild, 10, 100).await;
+        let second_user = create_guild_user(&mut conn, second_guild, 20, 200).await;
+        let third_user = create_guild_user(&mut conn, first_guild, 30, 300).await;
+
+        let params = PaginationParams::default();
+        let (users, total) = UserLink::get_paginated(&mut conn, first_guild, params)
+            .await
+            .unwrap();
+        let mut ids = users.iter().map(|user| user.id).collect::<Vec<_>>();
+        ids.sort();
+        let mut expected = vec![first_user.id, third_user.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert_eq!(total, 2);
+
+        let (users, total) = UserLink::get_paginated(&mut conn, second_guild, params)
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, second_user.id);
+        assert_eq!(total, 1);
+
+        let params = PaginationParams {
+            offset: 1,
+            limit: 1,
+        };
+        let (users, total) = UserLink::get_paginated(&mut conn, first_guild, params)
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert!(expected.contains(&users[0].id));
+        assert_eq!(total, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_get_users_page(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let first_guild = create_guild(&mut conn, 1).await;
+        let second_guild = create_guild(&mut conn, 2).await;
+
+        let first_user = create_guild_user(&mut conn, first_guild, 10, 100).await;
+        let second_user = create_guild_user(&mut conn, second_guild, 20, 200).await;
+        let third_user = create_guild_user(&mut conn, first_guild, 30, 300).await;
+        sqlx::query("UPDATE user_links SET created_at = NOW() + INTERVAL '1 day' WHERE id = $1")
+            .bind(third_user.id)
+            .execute(conn.as_mut())
+            .await
+            .unwrap();
+
+        let params = CursorParams::default();
+        let (users, next_cursor) = UserLink::get_users_page(&mut conn, first_guild, params)
+            .await
+            .unwrap();
+        let ids = users.iter().map(|user| user.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![first_user.id, third_user.id]);
+        assert!(next_cursor.is_none());
+
+        let (users, _) = UserLink::get_users_page(&mut conn, second_guild, params)
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, second_user.id);
+
+        let params = CursorParams {
+            cursor: None,
+            limit: 1,
+        };
+        let (users, next_cursor) = UserLink::get_users_page(&mut conn, first_guild, params)
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, first_user.id);
+
+        let params = CursorParams {
+            cursor: next_cursor,
+            limit: 1,
+        };
+        let (users, next_cursor) = UserLink::get_users_page(&mut conn, first_guild, params)
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, third_user.id);
+        assert!(next_cursor.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_update_is_partial(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let user = UserLink::create_link(&mut conn, UserLinkPayload::new(10, 100, guild_id))
+            .await
+            .unwrap();
+
+        let payload = UserLinkUpdatePayload {
+            telegram_id: Some(200),
+            ..Default::default()
+        };
+        UserLink::update(&mut conn, user.id, payload).await.unwrap();
+
+        let updated = UserLink::find_by_id(&mut conn, user.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.telegram_id, 200);
+        assert_eq!(updated.discord_id, 10);
+        assert!(updated.updated_at >= user.updated_at);
+
+        let missing = UserLink::find_by_id(&mut conn, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_find_duplicates(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let first = UserLink::create_link(&mut conn, UserLinkPayload::new(10, 100, guild_id))
+            .await
+            .unwrap();
+        UserLink::create_link(&mut conn, UserLinkPayload::new(20, 200, guild_id))
+            .await
+            .unwrap();
+
+        assert!(
+            UserLink::find_duplicates(&mut conn)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        sqlx::query(
+            "DROP INDEX user_links_active_telegram_id_key, user_links_active_discord_id_key",
+        )
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+        let second: Uuid = sqlx::query_scalar(
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id) VALUES (30, 100, $1) RETURNING id",
+        )
+        .bind(guild_id)
+        .fetch_one(conn.as_mut())
+        .await
+        .unwrap();
+        let third: Uuid = sqlx::query_scalar(
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id) VALUES (30, 300, $1) RETURNING id",
+        )
+        .bind(guild_id)
+        .fetch_one(conn.as_mut())
+        .await
+        .unwrap();
+
+        let duplicates = UserLink::find_duplicates(&mut conn).await.unwrap();
+
+        assert_eq!(duplicates.telegram_ids, vec![(100, vec![first.id, second])]);
+        assert_eq!(duplicates.discord_ids, vec![(30, vec![second, third])]);
+        assert_eq!(duplicates.link_ids().count(), 4);
+    }
+
+    #[sqlx::test]
+    async fn test_get_added_between_includes_removed_links(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let now = Utc::now();
+
+        for (discord_id, days_ago) in [(10, 10), (20, 5), (30, 1)] {
+            let payload = UserLinkPayload::new(discord_id, discord_id * 10, guild_id);
+            let user = UserLink::create_link(&mut conn, payload).await.unwrap();
+            sqlx::query("UPDATE user_links SET created_at = $1 WHERE id = $2")
+                .bind(now - chrono::Duration::days(days_ago))
+                .bind(user.id)
+                .execute(conn.as_mut())
+                .await
+                .unwrap();
+        }
+        UserLink::mark_removed_from_group(&mut conn, 20)
+            .await
+            .unwrap();
+
+        let discord_ids =
+            |users: Vec<UserLink>| users.iter().map(|user| user.discord_id).collect::<Vec<_>>();
+
+        let week = now - chrono::Duration::days(7);
+        let added = UserLink::get_added_after(&mut conn, week).await.unwrap();
+        assert_eq!(discord_ids(added), [20, 30]);
+
+        let start = now - chrono::Duration::days(11);
+        let end = now - chrono::Duration::days(1);
+        let added = UserLink::get_added_between(&mut conn, start, end)
+            .await
+            .unwrap();
+        assert_eq!(discord_ids(added), [10, 20]);
+    }
+
+    #[sqlx::test]
+    async fn test_bulk_unlink_by_guild(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let first_guild = create_guild(&mut conn, 1).await;
+        let second_guild = create_guild(&mut conn, 2).await;
+
+        create_guild_user(&mut conn, first_guild, 10, 100).await;
+        create_guild_user(&mut conn, first_guild, 30, 300).await;
+        create_guild_user(&mut conn, second_guild, 20, 200).await;
+        UserLink::mark_removed_from_group(&mut conn, 30)
+            .await
+            .unwrap();
+
+        let unlinked = UserLink::bulk_unlink_by_guild(&mut conn, first_guild)
+            .await
+            .unwrap();
+        let telegram_ids = unlinked
+            .iter()
+            .map(|user| user.telegram_id)
+            .collect::<Vec<_>>();
+        assert_eq!(telegram_ids, vec![100]);
+
+        assert!(
+            UserLink::find_by_discord_id(&mut conn, 10)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            UserLink::find_by_discord_id(&mut conn, 20)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let unlinked = UserLink::bulk_unlink_by_guild(&mut conn, first_guild)
+            .await
+            .unwrap();
+        assert!(unlinked.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_delete_stale_pending(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let mut create = async |discord_id: i64, age_days: i32, joined: bool| {
+            let user = UserLink::create_link(
+                &mut conn,
+                UserLinkPayload::new(discord_id, discord_id, guild_id),
+            )
+            .await
+            .unwrap();
+            sqlx::query(
+                "UPDATE user_links
+                SET created_at = NOW() - make_interval(days => $2),
+                    added_to_group_at = NOW(),
+                    joined_group_at = CASE WHEN $3 THEN NOW() END
+                WHERE id = $1",
+            )
+            .bind(user.id)
+            .bind(age_days)
+            .bind(joined)
+            .execute(conn.as_mut())
+            .await
+            .unwrap();
+            user.id
+        };
+
+        create(1, 30, false).await;
+        let recent_pending = create(2, 1, false).await;
+        let old_joined = create(3, 30, true).await;
+
+        let cutoff = Utc::now() - chrono::Duration::days(7);
+        let deleted = UserLink::delete_stale_pending(&mut conn, cutoff)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+
+        let mut remaining = UserLink::get_guild_users(&mut conn, guild_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.id)
+            .collect::<Vec<_>>();
+        remaining.sort();
+        let mut expected = vec![recent_pending, old_joined];
+        expected.sort();
+        assert_eq!(remaining, expected);
+    }
 }
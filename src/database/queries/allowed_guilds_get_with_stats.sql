@@ -0,0 +1,34 @@
+SELECT
+    allowed_guilds.id,
+    allowed_guilds.guild_id,
+    allowed_guilds.name,
+    allowed_guilds.created_at,
+    allowed_guilds.updated_at,
+    allowed_guilds.last_verified_at,
+    allowed_guilds.removal_policy AS "removal_policy: RemovalPolicy",
+    roles.count AS "role_count!",
+    channels.count AS "channel_count!",
+    COUNT(user_links.id) AS "user_count!"
+FROM
+    allowed_guilds
+    CROSS JOIN (
+        SELECT
+            COUNT(*)
+        FROM
+            allowed_roles) AS roles
+    CROSS JOIN (
+        SELECT
+            COUNT(*)
+        FROM
+            allowed_channels) AS channels
+    LEFT JOIN user_links ON user_links.guild_id = allowed_guilds.id
+        AND user_links.deleted_at IS NULL
+WHERE
+    allowed_guilds.deleted_at IS NULL
+GROUP BY
+    allowed_guilds.id,
+    roles.count,
+    channels.count
+ORDER BY
+    allowed_guilds.created_at,
+    allowed_guilds.id
@@ -0,0 +1,15 @@
+SELECT
+    allowed_roles.role_id AS discord_role_id,
+    allowed_roles.name AS role_name,
+    telegram_groups.telegram_group_id,
+    telegram_groups.name AS group_name
+FROM
+    role_group_mappings
+    JOIN allowed_roles ON allowed_roles.id = role_group_mappings.role_id
+    JOIN telegram_groups ON telegram_groups.id = role_group_mappings.group_id
+WHERE
+    telegram_groups.allowed_guild_id = $1
+ORDER BY
+    allowed_roles.is_admin DESC,
+    allowed_roles.created_at,
+    allowed_roles.role_id
@@ -0,0 +1,8 @@
+INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+    VALUES ($1, $2, $3)
+ON CONFLICT (telegram_group_id)
+    DO UPDATE SET
+        allowed_guild_id = $1,
+        name = $3
+RETURNING
+    *
@@ -0,0 +1,13 @@
+SELECT
+    discord_id,
+    array_agg(id ORDER BY created_at, id) AS "ids!"
+FROM
+    user_links
+WHERE
+    deleted_at IS NULL
+GROUP BY
+    discord_id
+HAVING
+    COUNT(*) > 1
+ORDER BY
+    discord_id
@@ -0,0 +1,13 @@
+SELECT
+    telegram_id,
+    array_agg(id ORDER BY created_at, id) AS "ids!"
+FROM
+    user_links
+WHERE
+    deleted_at IS NULL
+GROUP BY
+    telegram_id
+HAVING
+    COUNT(*) > 1
+ORDER BY
+    telegram_id
@@ -0,0 +1,13 @@
+SELECT
+    *
+FROM
+    user_links
+WHERE
+    guild_id = $1
+    AND deleted_at IS NULL
+    AND ($2::timestamptz IS NULL
+        OR (created_at, id) > ($2, $3::uuid))
+ORDER BY
+    created_at,
+    id
+LIMIT $4
@@ -1,16 +1,16 @@
 use itertools::Itertools;
 use poise::serenity_prelude::{self as serenity};
 
-use super::validate_guild;
-use crate::database::models::allowed_channels::{AllowedChannel, AllowedChannelPayload};
+use super::{strip_mention, validate_guild, validate_name};
+use crate::database::models::{AllowedChannel, AllowedChannelPayload};
 use crate::discord::commands::create_standard_reply;
-use crate::discord::error::{InvalidChannelError, PermissionError, Result};
+use crate::discord::error::{InvalidChannelError, Result};
 use crate::discord::permissions::is_admin;
 use crate::discord::{Context, Error};
 
 #[allow(clippy::result_large_err)]
 fn parse_channel_id(id: &str) -> Result<i64> {
-    id.parse::<i64>().map_err(|_| {
+    strip_mention(id, "<#").parse::<i64>().map_err(|_| {
         let message = "ID do canal inválido".to_string();
         Error::InvalidChannel(InvalidChannelError::new(message))
     })
@@ -34,16 +34,18 @@ async fn validate_channel(ctx: Context<'_>, channel_id: i64) -> Result<(String,
     }
 }
 
+/// Manage the channels the bot commands can be used in
 #[poise::command(
     slash_command,
     rename = "canais",
+    name_localized("en-US", "channels"),
     subcommands("list_channels", "add_channel", "del_channel"),
     check = "is_admin",
     description_localized("pt-BR", "Gerenciar canais permitidos para comandos do bot")
 )]
 pub async fn channels(ctx: Context<'_>) -> Result<()> {
     let message = "Por favor, use um dos subcomandos: `/canais listar` ou `/canais novo`".into();
-    let reply = create_standard_reply(message);
+    let reply = create_standard_reply(&ctx.data().embed, message);
 
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list channels command response");
@@ -53,15 +55,17 @@ pub async fn channels(ctx: Context<'_>) -> Result<()> {
     Ok(())
 }
 
+/// List every channel the bot can be used in
 #[poise::command(
     slash_command,
     rename = "listar",
+    name_localized("en-US", "list"),
     check = "is_admin",
     description_localized("pt-BR", "Lista todos os canais permitidos para uso do bot")
 )]
 async fn list_channels(ctx: Context<'_>) -> Result<()> {
     let formatted_channels = list_channels_inner(&ctx.data().pool).await?;
-    let reply = create_standard_reply(formatted_channels);
+    let reply = create_standard_reply(&ctx.data().embed, formatted_channels);
 
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list channels command response");
@@ -74,23 +78,27 @@ async fn list_channels(ctx: Context<'_>) -> Result<()> {
 async fn list_channels_inner(pool: &sqlx::PgPool) -> Result<String> {
     let mut conn = pool.acquire().await?;
     let allowed_channels = AllowedChannel::get_channels(conn.as_mut()).await?;
+    Ok(format_allowed_channels(&allowed_channels))
+}
 
+fn format_allowed_channels(allowed_channels: &[AllowedChannel]) -> String {
     if allowed_channels.is_empty() {
-        return Ok("Nenhum canal na lista de canais permitidos".to_string());
+        return "Nenhum canal na lista de canais permitidos".to_string();
     }
 
     let formatted_channels = allowed_channels
-        .into_iter()
+        .iter()
         .map(|channel| format!("{} - {}", channel.channel_id, channel.name))
         .join("\n");
 
-    let formatted_channels = format!("Lista de canais permitidos:\n\n{}", formatted_channels);
-    Ok(formatted_channels)
+    format!("Lista de canais permitidos:\n\n{}", formatted_channels)
 }
 
+/// Add a channel to the allowed channels
 #[poise::command(
     slash_command,
     rename = "novo",
+    name_localized("en-US", "add"),
     check = "is_admin",
     description_localized("pt-BR", "Adiciona um novo canal à lista de canais permitidos")
 )]
@@ -105,7 +113,7 @@ async fn add_channel(
         "Canal adicionado com sucesso!\n\n**ID:** {}\n**Nome:** {}",
         new_channel.channel_id, new_channel.name
     );
-    let reply = create_standard_reply(description);
+    let reply = create_standard_reply(&ctx.data().embed, description);
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send add channel command response");
         e
@@ -115,11 +123,15 @@ async fn add_channel(
 }
 
 async fn add_channel_inner(pool: &sqlx::PgPool, id: i64, name: String) -> Result<AllowedChannel> {
+    validate_name(&name, |message| {
+        Error::InvalidChannel(InvalidChannelError::new(message))
+    })?;
+
     let mut conn = pool.acquire().await?;
     let exists = AllowedChannel::exists(conn.as_mut(), id).await?;
     if exists {
         let message = "Canal já existe na lista".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        return Err(Error::InvalidChannel(InvalidChannelError::new(message)));
     }
 
     let payload = AllowedChannelPayload::new(id, name);
@@ -127,9 +139,11 @@ async fn add_channel_inner(pool: &sqlx::PgPool, id: i64, name: String) -> Result
     Ok(new_channel)
 }
 
+/// Remove a channel from the allowed channels
 #[poise::command(
     slash_command,
     rename = "remover",
+    name_localized("en-US", "remove"),
     check = "is_admin",
     description_localized("pt-BR", "Remove um canal da lista de canais permitidos")
 )]
@@ -141,7 +155,7 @@ pub async fn del_channel(
     let channel_name = get_channel_name(ctx, channel_id).await?;
     let description =
         format!("Canal removido com sucesso!\n\nID: {channel_id}\nNome: {channel_name}");
-    let reply = create_standard_reply(description);
+    let reply = create_standard_reply(&ctx.data().embed, description);
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send add channel command response");
         e
@@ -157,7 +171,7 @@ async fn del_channel_inner(pool: &sqlx::PgPool, id: String) -> Result<i64> {
     let exists = AllowedChannel::exists(conn.as_mut(), channel_id).await?;
     if !exists {
         let message = "Canal não encontrado na lista".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        return Err(Error::InvalidChannel(InvalidChannelError::new(message)));
     }
 
     AllowedChannel::delete(conn.as_mut(), channel_id).await?;
@@ -172,7 +186,21 @@ async fn get_channel_name(ctx: Context<'_>, channel_id: i64) -> Result<String> {
 
 #[cfg(test)]
 mod tests {
+    use sqlx::types::Uuid;
+
     use super::*;
+    use crate::discord::commands::MAX_NAME_LEN;
+    use crate::utils::with_tx;
+
+    fn make_channel(channel_id: i64, name: &str) -> AllowedChannel {
+        AllowedChannel {
+            id: Uuid::new_v4(),
+            channel_id,
+            name: name.to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
 
     async fn setup_test_data(pool: &sqlx::PgPool) -> Result<i64> {
         // Create test channel
@@ -212,6 +240,22 @@ mod tests {
         assert_eq!(channel_id.name, "Teste");
     }
 
+    #[test]
+    fn test_format_allowed_channels_empty() {
+        let formatted = format_allowed_channels(&[]);
+        assert_eq!(formatted, "Nenhum canal na lista de canais permitidos");
+    }
+
+    #[test]
+    fn test_format_allowed_channels_with_data() {
+        let channels = [make_channel(1, "Geral"), make_channel(2, "Subs")];
+        let formatted = format_allowed_channels(&channels);
+        assert_eq!(
+            formatted,
+            "Lista de canais permitidos:\n\n1 - Geral\n2 - Subs"
+        );
+    }
+
     #[test]
     fn test_parse_channel_id_valid() {
         let result = parse_channel_id("12345");
@@ -225,6 +269,21 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_channel_id_from_mention() {
+        assert_eq!(parse_channel_id("<#12345>").unwrap(), 12345);
+        assert_eq!(parse_channel_id("  <#12345> ").unwrap(), 12345);
+        assert_eq!(parse_channel_id(" 12345\n").unwrap(), 12345);
+
+        for malformed in ["<#12345", "#12345>", "<@&12345>", "<#>", "<# 12345>"] {
+            let result = parse_channel_id(malformed);
+            assert!(
+                matches!(result, Err(Error::InvalidChannel(_))),
+                "{malformed}"
+            );
+        }
+    }
+
     #[sqlx::test]
     async fn test_channel_not_found(pool: sqlx::PgPool) {
         let non_existent_id = 9999999;
@@ -246,4 +305,49 @@ mod tests {
 
         let _ = del_channel_inner(&pool, test_id.to_string()).await.unwrap();
     }
+
+    #[sqlx::test]
+    async fn test_create_refreshes_updated_at_on_conflict(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let payload = AllowedChannelPayload::new(12345, "Antigo".to_string());
+        let created = AllowedChannel::create(conn.as_mut(), payload)
+            .await
+            .unwrap();
+
+        let payload = AllowedChannelPayload::new(12345, "Novo".to_string());
+        let upserted = AllowedChannel::create(conn.as_mut(), payload)
+            .await
+            .unwrap();
+
+        assert_eq!(upserted.id, created.id);
+        assert_eq!(upserted.name, "Novo");
+        assert!(upserted.updated_at > created.updated_at);
+    }
+
+    #[sqlx::test]
+    async fn test_add_channel_rejects_invalid_name(pool: sqlx::PgPool) {
+        let too_long = "a".repeat(MAX_NAME_LEN + 1);
+        let result = add_channel_inner(&pool, 12345, too_long).await;
+        assert!(matches!(result, Err(Error::InvalidChannel(_))));
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(!AllowedChannel::exists(conn.as_mut(), 12345).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn test_command_transaction_rolls_back_on_error(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+
+        let result: Result<()> = with_tx(conn.as_mut(), async |tx| {
+            let payload = AllowedChannelPayload::new(12345, "Rollback".to_string());
+            AllowedChannel::create(tx, payload).await?;
+
+            let message = "Canal inválido".to_string();
+            Err(Error::InvalidChannel(InvalidChannelError::new(message)))
+        })
+        .await;
+
+        assert!(matches!(result, Err(Error::InvalidChannel(_))));
+        assert!(!AllowedChannel::exists(conn.as_mut(), 12345).await.unwrap());
+    }
 }
@@ -0,0 +1,393 @@
+use itertools::Itertools;
+
+use super::sync::{LiveGuild, Reconciliation, format_reconciliation, live_guild, sync_inner};
+use super::validate_guild;
+use crate::database::models::{AllowedGuild, GuildWithStats, UserLink};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, Result};
+use crate::discord::permissions::is_admin;
+use crate::messages::TelegramAction;
+use crate::services::telegram_groups::TelegramGroupCache;
+
+/// Manage the servers the bot works in
+#[poise::command(
+    slash_command,
+    rename = "servidores",
+    name_localized("en-US", "servers"),
+    subcommands("list_guilds", "sync_guild", "remove_guild"),
+    check = "is_admin",
+    description_localized("pt-BR", "Gerenciar servidores permitidos")
+)]
+pub async fn guilds(ctx: Context<'_>) -> Result<()> {
+    let message =
+        "Por favor, use um dos subcomandos: `/servidores listar`, `/servidores sincronizar`, `/servidores remover`".into();
+    let reply = create_standard_reply(&ctx.data().embed, message);
+
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send guilds command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// List every server the bot works in along with what is configured for it
+#[poise::command(
+    slash_command,
+    rename = "listar",
+    name_localized("en-US", "list"),
+    check = "is_admin",
+    description_localized("pt-BR", "Lista os servidores permitidos e suas configurações")
+)]
+async fn list_guilds(ctx: Context<'_>) -> Result<()> {
+    let formatted_guilds = list_guilds_inner(&ctx.data().pool).await?;
+    let reply = create_standard_reply(&ctx.data().embed, formatted_guilds);
+
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list guilds command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Update the stored name of this server and of the allowed roles and channels
+#[poise::command(
+    slash_command,
+    rename = "sincronizar",
+    name_localized("en-US", "sync"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Atualiza o nome deste servidor e dos cargos e canais permitidos"
+    )
+)]
+async fn sync_guild(ctx: Context<'_>) -> Result<()> {
+    let live = live_guild(&ctx)?;
+    validate_guild(&ctx.data().pool, live.id).await?;
+
+    let synced = sync_guild_inner(&ctx.data().pool, &live).await?;
+    tracing::info!(
+        guild_id = live.id,
+        records_updated = synced.updated(),
+        "Allowed guild synchronized"
+    );
+
+    let reply = create_standard_reply(&ctx.data().embed, format_guild_sync(&synced));
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send sync guild command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// What `/servidores sincronizar` changed
+struct GuildSync {
+    /// Previous and current name of the guild, when it was renamed
+    guild: Option<(String, String)>,
+    roles: Reconciliation,
+    channels: Reconciliation,
+}
+
+impl GuildSync {
+    fn updated(&self) -> usize {
+        usize::from(self.guild.is_some()) + self.roles.renamed.len() + self.channels.renamed.len()
+    }
+}
+
+async fn sync_guild_inner(pool: &sqlx::PgPool, live: &LiveGuild) -> Result<GuildSync> {
+    let mut conn = pool.acquire().await?;
+
+    let mut renamed = None;
+    let stale = AllowedGuild::find_by_guild_id(conn.as_mut(), live.id as i64)
+        .await?
+        .filter(|guild| guild.name != live.name);
+    if let Some(guild) = stale {
+        AllowedGuild::update_name(conn.as_mut(), guild.id, &live.name).await?;
+        renamed = Some((guild.name, live.name.clone()));
+    }
+
+    let (roles, channels) = sync_inner(pool, &live.roles, &live.channels).await?;
+
+    Ok(GuildSync {
+        guild: renamed,
+        roles,
+        channels,
+    })
+}
+
+fn format_guild_sync(synced: &GuildSync) -> String {
+    let guild = match &synced.guild {
+        Some((old_name, new_name)) => format!("{old_name} → {new_name}"),
+        None => "Nome já estava atualizado".to_string(),
+    };
+
+    format!(
+        "Sincronização concluída! Registros atualizados: {}\n\n[SERVIDOR]\n{guild}\n\n[CARGOS]\n{}\n\n[CANAIS]\n{}",
+        synced.updated(),
+        format_reconciliation(&synced.roles),
+        format_reconciliation(&synced.channels)
+    )
+}
+
+/// Stop working in a server, unlinking every user that joined through it
+#[poise::command(
+    slash_command,
+    rename = "remover",
+    name_localized("en-US", "remove"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Remove um servidor permitido e desvincula os usuários que entraram por ele"
+    )
+)]
+async fn remove_guild(
+    ctx: Context<'_>,
+    #[description = "ID do servidor para remover"] id: String,
+) -> Result<()> {
+    let guild_id = parse_guild_id(&id)?;
+    let data = ctx.data();
+
+    let removed = remove_guild_inner(&data.pool, &data.telegram_groups, guild_id).await?;
+
+    // The links are already gone, a user that isn't kicked here is removed by hand instead of
+    // being picked up by the cron
+    let mut kicked = 0;
+    for &(telegram_id, group_id) in &removed.kicks {
+        let action = TelegramAction::RemoveUser {
+            telegram_id,
+            group_id,
+        };
+
+        match data.telegram_sender.send(action) {
+            Ok(_) => kicked += 1,
+            Err(e) => tracing::error!(
+                error = %e,
+                telegram_id = telegram_id,
+                "Failed to send telegram remove action"
+            ),
+        }
+    }
+
+    tracing::info!(
+        guild_id = guild_id,
+        user_id = %ctx.author().id,
+        unlinked = removed.unlinked,
+        kicked = kicked,
+        "Allowed guild removed"
+    );
+
+    let kicked = match removed.telegram_group_id {
+        Some(_) => kicked.to_string(),
+        None => "nenhum, o servidor não tinha grupo do Telegram".to_string(),
+    };
+    let description = format!(
+        "Servidor removido com sucesso!\n\n**ID:** {guild_id}\n**Nome:** {}\n**Usuários desvinculados:** {}\n**Removidos do grupo:** {kicked}",
+        removed.name, removed.unlinked
+    );
+    let reply = create_standard_reply(&data.embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send remove guild command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_guild_id(id: &str) -> Result<i64> {
+    id.parse::<i64>().map_err(|_| {
+        let message = "ID do servidor inválido".to_string();
+        Error::InvalidGuild(InvalidGuildError::new(message))
+    })
+}
+
+/// What was left behind by a removed guild
+#[derive(Debug)]
+struct RemovedGuild {
+    name: String,
+    /// How many links were removed along with the guild
+    unlinked: usize,
+    /// Telegram id of each unlinked user with the group they have to be kicked from
+    kicks: Vec<(i64, i64)>,
+    /// Group of the guild, if it had one
+    telegram_group_id: Option<i64>,
+}
+
+async fn remove_guild_inner(
+    pool: &sqlx::PgPool,
+    telegram_groups: &TelegramGroupCache,
+    guild_id: i64,
+) -> Result<RemovedGuild> {
+    let mut tx = pool.begin().await?;
+
+    let Some(guild) = AllowedGuild::find_by_guild_id(tx.as_mut(), guild_id).await? else {
+        let message = "Esse servidor não está na lista de servidores permitidos".to_string();
+        return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+    };
+
+    // Resolved before the guild is deleted, its groups go away with it
+    let telegram_group_id = telegram_groups.resolve(tx.as_mut(), guild.id).await?;
+    let unlinked = UserLink::bulk_unlink_by_guild(tx.as_mut(), guild.id).await?;
+    AllowedGuild::delete(tx.as_mut(), guild.id).await?;
+
+    // Users invited to a group mapped to their role are kicked from it, not from the guild's group
+    let kicks = unlinked
+        .iter()
+        .filter_map(|user| {
+            let group_id = user.telegram_group_id.or(telegram_group_id)?;
+            Some((user.telegram_id, group_id))
+        })
+        .collect();
+
+    tx.commit().await?;
+
+    let mut conn = pool.acquire().await?;
+    telegram_groups.refresh(conn.as_mut()).await?;
+
+    Ok(RemovedGuild {
+        name: guild.name,
+        unlinked: unlinked.len(),
+        kicks,
+        telegram_group_id,
+    })
+}
+
+async fn list_guilds_inner(pool: &sqlx::PgPool) -> Result<String> {
+    let mut conn = pool.acquire().await?;
+    let guilds = AllowedGuild::get_guilds_with_stats(conn.as_mut()).await?;
+    Ok(format_guilds(&guilds))
+}
+
+fn format_guilds(guilds: &[GuildWithStats]) -> String {
+    let Some(first) = guilds.first() else {
+        return "Nenhum servidor na lista de servidores permitidos".to_string();
+    };
+
+    let formatted_guilds = guilds
+        .iter()
+        .map(|stats| {
+            format!(
+                "{} - {}\n**Usuários vinculados:** {}",
+                stats.guild.guild_id, stats.guild.name, stats.user_count
+            )
+        })
+        .join("\n\n");
+
+    // Roles and channels are shared by every guild, so they are only shown once
+    format!(
+        "Lista de servidores permitidos:\n\n{formatted_guilds}\n\n**Cargos permitidos:** {}\n**Canais permitidos:** {}",
+        first.role_count, first.channel_count
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_list_guilds_shows_stats(pool: sqlx::PgPool) {
+        sqlx::query(
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id)
+            SELECT 1, 2, id FROM allowed_guilds WHERE guild_id = 258648784039313408",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let guilds = list_guilds_inner(&pool).await.unwrap();
+
+        assert!(
+            guilds.contains("258648784039313408 - Server do Felpinho\n**Usuários vinculados:** 1")
+        );
+        assert!(guilds.contains("1355012226355957780 - Server Teste\n**Usuários vinculados:** 0"));
+        assert!(guilds.contains("**Canais permitidos:** 2"));
+    }
+
+    #[sqlx::test]
+    async fn test_sync_guild_updates_names(pool: sqlx::PgPool) {
+        let live = LiveGuild {
+            id: 1355012226355957780,
+            name: "Server Teste renomeado".to_string(),
+            roles: [(277212035652124672, "FELPS renomeado".to_string())].into(),
+            channels: Default::default(),
+        };
+
+        let synced = sync_guild_inner(&pool, &live).await.unwrap();
+
+        assert_eq!(synced.updated(), 2);
+        assert!(format_guild_sync(&synced).contains("Server Teste → Server Teste renomeado"));
+
+        let mut conn = pool.acquire().await.unwrap();
+        let guild = AllowedGuild::find_by_guild_id(conn.as_mut(), 1355012226355957780)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(guild.name, "Server Teste renomeado");
+
+        // Nothing left to update the second time around
+        let synced = sync_guild_inner(&pool, &live).await.unwrap();
+        assert_eq!(synced.updated(), 0);
+    }
+
+    #[sqlx::test]
+    async fn test_remove_guild_unlinks_its_users(pool: sqlx::PgPool) {
+        for query in [
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -100, 'Grupo' FROM allowed_guilds WHERE guild_id = 258648784039313408",
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -200, 'Grupo mapeado' FROM allowed_guilds WHERE guild_id = 258648784039313408",
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id)
+            SELECT 1, 2, id FROM allowed_guilds WHERE guild_id = 258648784039313408",
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id, telegram_group_id)
+            SELECT 3, 4, id, -200 FROM allowed_guilds WHERE guild_id = 258648784039313408",
+        ] {
+            sqlx::query(query).execute(&pool).await.unwrap();
+        }
+
+        let telegram_groups = TelegramGroupCache::default();
+        let removed = remove_guild_inner(&pool, &telegram_groups, 258648784039313408)
+            .await
+            .unwrap();
+
+        assert_eq!(removed.name, "Server do Felpinho");
+        assert_eq!(removed.unlinked, 2);
+        let mut kicks = removed.kicks;
+        kicks.sort();
+        assert_eq!(kicks, vec![(2, -100), (4, -200)]);
+        assert_eq!(removed.telegram_group_id, Some(-100));
+
+        let mut conn = pool.acquire().await.unwrap();
+        let guild = AllowedGuild::find_by_guild_id(conn.as_mut(), 258648784039313408)
+            .await
+            .unwrap();
+        assert!(guild.is_none());
+        assert!(
+            UserLink::find_by_discord_id(conn.as_mut(), 1)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // The link is soft deleted, not taken away by the guild
+        let deleted_at: Option<chrono::DateTime<chrono::Utc>> =
+            sqlx::query_scalar("SELECT deleted_at FROM user_links WHERE discord_id = 1")
+                .fetch_one(conn.as_mut())
+                .await
+                .unwrap();
+        assert!(deleted_at.is_some());
+
+        let result = remove_guild_inner(&pool, &telegram_groups, 258648784039313408).await;
+        assert!(matches!(result, Err(Error::InvalidGuild(_))));
+    }
+
+    #[test]
+    fn test_format_without_guilds() {
+        assert_eq!(
+            format_guilds(&[]),
+            "Nenhum servidor na lista de servidores permitidos"
+        );
+    }
+}
@@ -1,16 +1,29 @@
 use itertools::Itertools;
-use poise::serenity_prelude::RoleId;
+use poise::ChoiceParameter;
+use poise::serenity_prelude::{Role, RoleId};
 
-use super::validate_guild;
-use crate::database::models::allowed_roles::{AllowedRole, AllowedRolePayload};
+use super::{strip_mention, validate_guild, validate_name};
+use crate::database::models::{AllowedRole, AllowedRolePayload, RoleOrder};
 use crate::discord::Context;
 use crate::discord::commands::create_standard_reply;
-use crate::discord::error::{Error, InvalidGuildError, InvalidRoleError, PermissionError, Result};
+use crate::discord::error::{Error, InvalidGuildError, InvalidRoleError, Result};
 use crate::discord::permissions::is_admin;
 
+/// Which allowed roles `/cargos listar` shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ChoiceParameter)]
+enum RoleKind {
+    #[name = "admin"]
+    Admin,
+    #[name = "sub"]
+    Sub,
+    #[default]
+    #[name = "todos"]
+    All,
+}
+
 #[allow(clippy::result_large_err)]
-fn parse_role_id(id: &str) -> Result<i64> {
-    id.parse::<i64>().map_err(|_| {
+pub fn parse_role_id(id: &str) -> Result<i64> {
+    strip_mention(id, "<@&").parse::<i64>().map_err(|_| {
         let message = "ID do cargo inválido".to_string();
         Error::InvalidRole(InvalidRoleError::new(message))
     })
@@ -31,18 +44,20 @@ async fn validate_role(ctx: Context<'_>, role_id: RoleId) -> Result<(String, u64
     Ok((name, guild.id.get()))
 }
 
+/// Manage the roles allowed to use the bot commands
 #[poise::command(
     slash_command,
     rename = "cargos",
+    name_localized("en-US", "roles"),
     check = "is_admin",
-    subcommands("list_roles", "add_role", "del_role"),
+    subcommands("list_roles", "add_role", "edit_role", "del_role", "import_roles"),
     description_localized("pt-BR", "Gerenciar cargos permitidos para comandos do bot")
 )]
 pub async fn roles(ctx: Context<'_>) -> Result<()> {
     let message =
-        "Por favor, use um dos subcomandos: `/cargos listar`, `/cargos novo` ou `/cargos remover`"
+        "Por favor, use um dos subcomandos: `/cargos listar`, `/cargos novo`, `/cargos editar`, `/cargos remover` ou `/cargos importar`"
             .into();
-    let reply = create_standard_reply(message);
+    let reply = create_standard_reply(&ctx.data().embed, message);
 
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list channels command response");
@@ -52,15 +67,22 @@ pub async fn roles(ctx: Context<'_>) -> Result<()> {
     Ok(())
 }
 
+/// List every role allowed to use the bot
 #[poise::command(
     slash_command,
     rename = "listar",
+    name_localized("en-US", "list"),
     check = "is_admin",
     description_localized("pt-BR", "Lista todos os cargos permitidos para uso do bot")
 )]
-async fn list_roles(ctx: Context<'_>) -> Result<()> {
-    let formatted_roles = list_roles_inner(&ctx.data().pool).await?;
-    let reply = create_standard_reply(formatted_roles);
+async fn list_roles(
+    ctx: Context<'_>,
+    #[description = "Ordenar por: name, id, created_at"] order: Option<String>,
+    #[description = "Mostrar apenas cargos de administrador ou de sub"] tipo: Option<RoleKind>,
+) -> Result<()> {
+    let kind = tipo.unwrap_or_default();
+    let formatted_roles = list_roles_inner(&ctx.data().pool, order, kind).await?;
+    let reply = create_standard_reply(&ctx.data().embed, formatted_roles);
 
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list roles command response");
@@ -70,42 +92,64 @@ async fn list_roles(ctx: Context<'_>) -> Result<()> {
     Ok(())
 }
 
-async fn list_roles_inner(pool: &sqlx::PgPool) -> Result<String> {
+async fn list_roles_inner(
+    pool: &sqlx::PgPool,
+    order_by: Option<String>,
+    kind: RoleKind,
+) -> Result<String> {
+    let order = parse_role_order(order_by.as_deref())?;
     let mut conn = pool.acquire().await?;
-    let allowed_roles = AllowedRole::get_roles(conn.as_mut()).await?;
+    let allowed_roles = AllowedRole::get_roles_ordered(conn.as_mut(), order).await?;
+    Ok(format_roles(&allowed_roles, kind))
+}
 
-    if allowed_roles.is_empty() {
-        return Ok("Nenhum cargo na lista de cargos permitidos".to_string());
+#[allow(clippy::result_large_err)]
+fn parse_role_order(order_by: Option<&str>) -> Result<RoleOrder> {
+    match order_by.map(str::trim) {
+        None | Some("created_at") => Ok(RoleOrder::CreatedAt),
+        Some("name") => Ok(RoleOrder::Name),
+        Some("id") => Ok(RoleOrder::Id),
+        Some(_) => {
+            let message = "Ordenação inválida, use name, id ou created_at".to_string();
+            Err(Error::InvalidRole(InvalidRoleError::new(message)))
+        }
     }
+}
 
-    let admin_roles = allowed_roles
-        .iter()
-        .filter(|role| role.is_admin)
-        .collect::<Vec<_>>();
-
-    let non_admin_roles = allowed_roles
-        .iter()
-        .filter(|role| !role.is_admin)
-        .collect::<Vec<_>>();
+fn format_roles(allowed_roles: &[AllowedRole], kind: RoleKind) -> String {
+    if allowed_roles.is_empty() {
+        return "Nenhum cargo configurado".to_string();
+    }
 
-    let formatted_roles = format!(
-        "Lista de cargos permitidos:\n\n[ADMINS]\n{}\n\n[SUBS]\n{}",
-        admin_roles
+    let section = |is_admin: bool| {
+        let roles = allowed_roles
             .iter()
+            .filter(|role| role.is_admin == is_admin)
             .map(|role| format!("{} - {}", role.role_id, role.name))
-            .join("\n"),
-        non_admin_roles
-            .iter()
-            .map(|role| format!("{} - {}", role.role_id, role.name))
-            .join("\n")
-    );
+            .join("\n");
+
+        match roles.is_empty() {
+            true => "(nenhum)".to_string(),
+            false => roles,
+        }
+    };
 
-    Ok(formatted_roles)
+    match kind {
+        RoleKind::All => format!(
+            "Lista de cargos permitidos:\n\n[ADMINS]\n{}\n\n[SUBS]\n{}",
+            section(true),
+            section(false)
+        ),
+        RoleKind::Admin => format!("Lista de cargos permitidos:\n\n[ADMINS]\n{}", section(true)),
+        RoleKind::Sub => format!("Lista de cargos permitidos:\n\n[SUBS]\n{}", section(false)),
+    }
 }
 
+/// Add a role to the allowed roles
 #[poise::command(
     slash_command,
     rename = "novo",
+    name_localized("en-US", "add"),
     check = "is_admin",
     description_localized("pt-BR", "Adiciona um novo cargo à lista de cargos permitidos")
 )]
@@ -118,12 +162,16 @@ async fn add_role(
     let role_name = get_role_name(ctx, role_id).await?;
     let is_admin = admin.unwrap_or_default();
 
-    let new_role = add_role_inner(&ctx.data().pool, role_id, role_name, is_admin).await?;
+    let (role, updated) = add_role_inner(&ctx.data().pool, role_id, role_name, is_admin).await?;
+    let action = match updated {
+        true => "atualizado",
+        false => "adicionado",
+    };
     let description = format!(
-        "Cargo adicionado com sucesso!\n\n**ID:** {}\n**Nome:** {}",
-        new_role.role_id, new_role.name
+        "Cargo {action} com sucesso!\n\n**ID:** {}\n**Nome:** {}",
+        role.role_id, role.name
     );
-    let reply = create_standard_reply(description);
+    let reply = create_standard_reply(&ctx.data().embed, description);
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send add role command response");
         e
@@ -132,27 +180,112 @@ async fn add_role(
     Ok(())
 }
 
+/// Adds the role, or updates it when its name or admin flag changed. Also returns whether the
+/// role was already on the list
 async fn add_role_inner(
     pool: &sqlx::PgPool,
     role_id: i64,
     name: String,
     is_admin: bool,
-) -> Result<AllowedRole> {
-    let mut conn = pool.acquire().await?;
-    let exists = AllowedRole::exists(conn.as_mut(), role_id).await?;
-    if exists {
+) -> Result<(AllowedRole, bool)> {
+    validate_name(&name, |message| {
+        Error::InvalidRole(InvalidRoleError::new(message))
+    })?;
+
+    let mut tx = pool.begin().await?;
+    let existing = AllowedRole::find_by_role_id(tx.as_mut(), role_id).await?;
+
+    let payload = AllowedRolePayload::new(role_id, name, is_admin);
+    let role = AllowedRole::create(tx.as_mut(), payload).await?;
+
+    // Dropping the transaction keeps the previous `updated_at` when nothing changed
+    let unchanged = existing
+        .as_ref()
+        .is_some_and(|existing| existing.name == role.name && existing.is_admin == role.is_admin);
+    if unchanged {
         let message = "Cargo já existe na lista".to_string();
         return Err(Error::InvalidRole(InvalidRoleError::new(message)));
     }
 
-    let payload = AllowedRolePayload::new(role_id, name, is_admin);
-    let new_role = AllowedRole::create(conn.as_mut(), payload).await?;
-    Ok(new_role)
+    tx.commit().await?;
+    Ok((role, existing.is_some()))
 }
 
+/// Change whether an allowed role is an admin role, refreshing its name from the server
+#[poise::command(
+    slash_command,
+    rename = "editar",
+    name_localized("en-US", "edit"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Altera se um cargo permitido é de administrador e atualiza o nome dele"
+    )
+)]
+async fn edit_role(
+    ctx: Context<'_>,
+    #[description = "ID do cargo para editar"] id: String,
+    #[description = "É um cargo de administrador? Vazio mantém como está"] admin: Option<bool>,
+) -> Result<()> {
+    let role_id = parse_role_id(&id)?;
+    let role_name = get_role_name(ctx, role_id).await?;
+
+    let role = edit_role_inner(&ctx.data().pool, role_id, role_name, admin).await?;
+    tracing::info!(
+        role_id = role.role_id,
+        is_admin = role.is_admin,
+        user_id = %ctx.author().id,
+        "Allowed role edited"
+    );
+
+    let kind = if role.is_admin {
+        "Administrador"
+    } else {
+        "Sub"
+    };
+    let description = format!(
+        "Cargo atualizado com sucesso!\n\n**ID:** {}\n**Nome:** {}\n**Tipo:** {kind}",
+        role.role_id, role.name
+    );
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send edit role command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Updates the flag and the name in one statement, a role left out of `is_admin` keeps the
+/// flag it had
+async fn edit_role_inner(
+    pool: &sqlx::PgPool,
+    role_id: i64,
+    name: String,
+    is_admin: Option<bool>,
+) -> Result<AllowedRole> {
+    validate_name(&name, |message| {
+        Error::InvalidRole(InvalidRoleError::new(message))
+    })?;
+
+    let mut tx = pool.begin().await?;
+    let Some(role) = AllowedRole::find_by_role_id(tx.as_mut(), role_id).await? else {
+        let message = "Cargo não encontrado na lista".to_string();
+        return Err(Error::InvalidRole(InvalidRoleError::new(message)));
+    };
+
+    let payload = AllowedRolePayload::new(role_id, name, is_admin.unwrap_or(role.is_admin));
+    let role = AllowedRole::update(tx.as_mut(), payload).await?;
+    tx.commit().await?;
+
+    Ok(role)
+}
+
+/// Remove a role from the allowed roles
 #[poise::command(
     slash_command,
     rename = "remover",
+    name_localized("en-US", "remove"),
     check = "is_admin",
     description_localized("pt-BR", "Remove um cargo da lista de cargos permitidos")
 )]
@@ -163,7 +296,7 @@ pub async fn del_role(
     let role_id = del_role_inner(&ctx.data().pool, id).await?;
     let role_name = get_role_name(ctx, role_id).await?;
     let description = format!("Cargo removido com sucesso!\n\nID: {role_id}\nNome: {role_name}");
-    let reply = create_standard_reply(description);
+    let reply = create_standard_reply(&ctx.data().embed, description);
     ctx.send(reply).await.map_err(|e| {
         tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send add role command response");
         e
@@ -179,15 +312,420 @@ async fn del_role_inner(pool: &sqlx::PgPool, id: String) -> Result<i64> {
     let exists = AllowedRole::exists(conn.as_mut(), role_id).await?;
     if !exists {
         let message = "Cargo não encontrado na lista".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        return Err(Error::InvalidRole(InvalidRoleError::new(message)));
     }
 
     AllowedRole::delete(conn.as_mut(), role_id).await?;
     Ok(role_id)
 }
 
+/// Minimal view of a guild role, enough to decide whether it can be imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GuildRole {
+    id: u64,
+    name: String,
+    managed: bool,
+}
+
+impl From<&Role> for GuildRole {
+    fn from(role: &Role) -> Self {
+        Self {
+            id: role.id.get(),
+            name: role.name.clone(),
+            managed: role.managed,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ImportSummary {
+    created: Vec<String>,
+    skipped: Vec<String>,
+}
+
+/// Add every role of the server as an allowed subscriber role
+#[poise::command(
+    slash_command,
+    rename = "importar",
+    name_localized("en-US", "import"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Adiciona todos os cargos do servidor como cargos de sub permitidos"
+    )
+)]
+async fn import_roles(
+    ctx: Context<'_>,
+    #[description = "Confirma a importação dos cargos listados"] confirmar: Option<bool>,
+) -> Result<()> {
+    let (guild_id, roles) = {
+        let Some(guild) = ctx.guild() else {
+            let message = "Esse comando só pode ser usado em servidores".to_string();
+            return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+        };
+
+        let roles = guild.roles.values().map(GuildRole::from).collect_vec();
+        (guild.id.get(), roles)
+    };
+
+    validate_guild(&ctx.data().pool, guild_id).await?;
+    let roles = importable_roles(roles, guild_id);
+
+    let description = if confirmar.unwrap_or_default() {
+        let summary = import_roles_inner(&ctx.data().pool, &roles).await?;
+        tracing::info!(
+            guild_id = guild_id,
+            created = summary.created.len(),
+            skipped = summary.skipped.len(),
+            "Guild roles imported"
+        );
+        format_import_summary(&summary)
+    } else {
+        format_import_preview(&roles)
+    };
+
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send import roles command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Drops @everyone, which shares the guild id, and roles managed by integrations such as bots
+fn importable_roles(roles: Vec<GuildRole>, guild_id: u64) -> Vec<GuildRole> {
+    roles
+        .into_iter()
+        .filter(|role| role.id != guild_id && !role.managed)
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect()
+}
+
+async fn import_roles_inner(pool: &sqlx::PgPool, roles: &[GuildRole]) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    let mut tx = pool.begin().await?;
+
+    for role in roles {
+        let role_id = role.id as i64;
+        if AllowedRole::exists(tx.as_mut(), role_id).await? {
+            summary.skipped.push(role.name.clone());
+            continue;
+        }
+
+        let payload = AllowedRolePayload::new(role_id, role.name.clone(), false);
+        AllowedRole::create(tx.as_mut(), payload).await?;
+        summary.created.push(role.name.clone());
+    }
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+fn format_import_preview(roles: &[GuildRole]) -> String {
+    if roles.is_empty() {
+        return "Nenhum cargo do servidor pode ser importado".to_string();
+    }
+
+    format!(
+        "Os seguintes cargos serão adicionados como cargos de sub:\n\n{}\n\nUse `/cargos importar confirmar:True` para confirmar",
+        roles
+            .iter()
+            .map(|role| format!("{} - {}", role.id, role.name))
+            .join("\n")
+    )
+}
+
+fn format_import_summary(summary: &ImportSummary) -> String {
+    format!(
+        "Importação concluída!\n\n**Adicionados:** {}\n**Ignorados (já existiam):** {}",
+        summary.created.len(),
+        summary.skipped.len()
+    )
+}
+
 async fn get_role_name(ctx: Context<'_>, role_id: i64) -> Result<String> {
     let (role_name, guild_id) = validate_role(ctx, RoleId::new(role_id as u64)).await?;
     validate_guild(&ctx.data().pool, guild_id).await?;
     Ok(role_name)
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Uuid;
+
+    use super::*;
+
+    fn make_role(role_id: i64, name: &str, is_admin: bool) -> AllowedRole {
+        AllowedRole {
+            id: Uuid::new_v4(),
+            role_id,
+            name: name.to_string(),
+            is_admin,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_format_roles_empty() {
+        let formatted = format_roles(&[], RoleKind::All);
+        assert_eq!(formatted, "Nenhum cargo configurado");
+    }
+
+    #[test]
+    fn test_format_roles_only_subs() {
+        let roles = [make_role(1, "Sub", false)];
+        let formatted = format_roles(&roles, RoleKind::All);
+        assert_eq!(
+            formatted,
+            "Lista de cargos permitidos:\n\n[ADMINS]\n(nenhum)\n\n[SUBS]\n1 - Sub"
+        );
+    }
+
+    #[test]
+    fn test_format_roles_only_admins() {
+        let roles = [make_role(1, "Admin", true)];
+        let formatted = format_roles(&roles, RoleKind::All);
+        assert_eq!(
+            formatted,
+            "Lista de cargos permitidos:\n\n[ADMINS]\n1 - Admin\n\n[SUBS]\n(nenhum)"
+        );
+    }
+
+    #[test]
+    fn test_format_roles_filtered_to_empty_section() {
+        let roles = [make_role(1, "Admin", true)];
+        let formatted = format_roles(&roles, RoleKind::Sub);
+        assert_eq!(formatted, "Lista de cargos permitidos:\n\n[SUBS]\n(nenhum)");
+    }
+
+    #[test]
+    fn test_format_roles_admins_and_subs() {
+        let roles = [
+            make_role(1, "Admin", true),
+            make_role(2, "Sub", false),
+            make_role(3, "Mod", true),
+        ];
+        let formatted = format_roles(&roles, RoleKind::All);
+        assert_eq!(
+            formatted,
+            "Lista de cargos permitidos:\n\n[ADMINS]\n1 - Admin\n3 - Mod\n\n[SUBS]\n2 - Sub"
+        );
+    }
+
+    fn guild_role(id: u64, name: &str, managed: bool) -> GuildRole {
+        GuildRole {
+            id,
+            name: name.to_string(),
+            managed,
+        }
+    }
+
+    #[test]
+    fn test_importable_roles_skips_everyone_and_managed() {
+        let guild_id = 100;
+        let roles = vec![
+            guild_role(guild_id, "@everyone", false),
+            guild_role(1, "Twitch Subscriber", false),
+            guild_role(2, "felbot", true),
+            guild_role(3, "Membro do Youtube", false),
+        ];
+
+        let roles = importable_roles(roles, guild_id);
+
+        assert_eq!(
+            roles,
+            vec![
+                guild_role(3, "Membro do Youtube", false),
+                guild_role(1, "Twitch Subscriber", false),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_import_roles_skips_existing(pool: sqlx::PgPool) {
+        let roles = [
+            guild_role(277212035652124672, "FELPS", false),
+            guild_role(1, "Novo", false),
+        ];
+
+        let summary = import_roles_inner(&pool, &roles).await.unwrap();
+
+        assert_eq!(summary.created, vec!["Novo".to_string()]);
+        assert_eq!(summary.skipped, vec!["FELPS".to_string()]);
+
+        let mut conn = pool.acquire().await.unwrap();
+        let roles = AllowedRole::get_roles(conn.as_mut()).await.unwrap();
+        let imported = roles.iter().find(|role| role.role_id == 1).unwrap();
+        assert!(!imported.is_admin);
+    }
+
+    async fn listed_sub_ids(pool: &sqlx::PgPool, order_by: Option<&str>) -> Vec<i64> {
+        let formatted = list_roles_inner(pool, order_by.map(String::from), RoleKind::All)
+            .await
+            .unwrap();
+
+        formatted
+            .lines()
+            .filter_map(|line| line.split_once(" - "))
+            .filter_map(|(id, _)| id.parse::<i64>().ok())
+            .filter(|id| [1, 2, 3].contains(id))
+            .collect()
+    }
+
+    async fn insert_unordered_roles(pool: &sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        for (role_id, name) in [(3, "Bravo"), (1, "Charlie"), (2, "Alpha")] {
+            let payload = AllowedRolePayload::new(role_id, name.to_string(), false);
+            AllowedRole::create(conn.as_mut(), payload).await.unwrap();
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_list_roles_ordered_by_created_at(pool: sqlx::PgPool) {
+        insert_unordered_roles(&pool).await;
+
+        assert_eq!(listed_sub_ids(&pool, None).await, vec![3, 1, 2]);
+        assert_eq!(
+            listed_sub_ids(&pool, Some("created_at")).await,
+            vec![3, 1, 2]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_roles_ordered_by_name(pool: sqlx::PgPool) {
+        insert_unordered_roles(&pool).await;
+
+        assert_eq!(listed_sub_ids(&pool, Some("name")).await, vec![2, 3, 1]);
+    }
+
+    #[sqlx::test]
+    async fn test_list_roles_ordered_by_id(pool: sqlx::PgPool) {
+        insert_unordered_roles(&pool).await;
+
+        assert_eq!(listed_sub_ids(&pool, Some("id")).await, vec![1, 2, 3]);
+    }
+
+    #[sqlx::test]
+    async fn test_list_roles_filtered_by_kind(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        for (role_id, name, is_admin) in [(1, "Mod", true), (2, "Sub", false)] {
+            let payload = AllowedRolePayload::new(role_id, name.to_string(), is_admin);
+            AllowedRole::create(conn.as_mut(), payload).await.unwrap();
+        }
+
+        let list = |kind| list_roles_inner(&pool, None, kind);
+
+        let admins = list(RoleKind::Admin).await.unwrap();
+        assert!(admins.contains("[ADMINS]") && admins.contains("1 - Mod"));
+        assert!(!admins.contains("[SUBS]") && !admins.contains("2 - Sub"));
+
+        let subs = list(RoleKind::Sub).await.unwrap();
+        assert!(subs.contains("[SUBS]") && subs.contains("2 - Sub"));
+        assert!(!subs.contains("[ADMINS]") && !subs.contains("1 - Mod"));
+
+        let all = list(RoleKind::All).await.unwrap();
+        assert!(all.contains("[ADMINS]") && all.contains("1 - Mod"));
+        assert!(all.contains("[SUBS]") && all.contains("2 - Sub"));
+    }
+
+    #[sqlx::test]
+    async fn test_add_role_upserts_changed_roles(pool: sqlx::PgPool) {
+        let (role, updated) = add_role_inner(&pool, 1, "Mods".to_string(), false)
+            .await
+            .unwrap();
+        assert!(!updated);
+        assert_eq!(role.created_at, role.updated_at);
+
+        let mut conn = pool.acquire().await.unwrap();
+        let before = AllowedRole::find_by_role_id(conn.as_mut(), 1)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let unchanged = add_role_inner(&pool, 1, "Mods".to_string(), false).await;
+        assert!(matches!(unchanged, Err(Error::InvalidRole(_))));
+        let stored = AllowedRole::find_by_role_id(conn.as_mut(), 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.updated_at, before.updated_at);
+
+        let (role, updated) = add_role_inner(&pool, 1, "Moderadores".to_string(), true)
+            .await
+            .unwrap();
+        assert!(updated);
+        assert_eq!(role.id, before.id);
+        assert_eq!(role.name, "Moderadores");
+        assert!(role.is_admin);
+        assert!(role.updated_at > before.updated_at);
+    }
+
+    #[sqlx::test]
+    async fn test_edit_role_updates_flag_and_name(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let payload = AllowedRolePayload::new(1, "Mods".to_string(), false);
+        AllowedRole::create(conn.as_mut(), payload).await.unwrap();
+
+        let role = edit_role_inner(&pool, 1, "Moderadores".to_string(), Some(true))
+            .await
+            .unwrap();
+        assert_eq!(role.name, "Moderadores");
+        assert!(role.is_admin);
+
+        let role = edit_role_inner(&pool, 1, "Moderação".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(role.name, "Moderação");
+        assert!(role.is_admin);
+
+        let stored = AllowedRole::find_by_role_id(conn.as_mut(), 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.name, "Moderação");
+        assert!(stored.is_admin);
+    }
+
+    #[sqlx::test]
+    async fn test_edit_role_rejects_unknown_role(pool: sqlx::PgPool) {
+        let result = edit_role_inner(&pool, 1, "Mods".to_string(), Some(true)).await;
+        assert!(matches!(result, Err(Error::InvalidRole(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_list_roles_rejects_unknown_order(pool: sqlx::PgPool) {
+        let result = list_roles_inner(
+            &pool,
+            Some("role_id; DROP TABLE".to_string()),
+            RoleKind::All,
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidRole(_))));
+    }
+
+    #[test]
+    fn test_parse_role_id_valid() {
+        let result = parse_role_id("12345");
+        assert_eq!(result.unwrap(), 12345);
+    }
+
+    #[test]
+    fn test_parse_role_id_overflow() {
+        let result = parse_role_id("9223372036854775808");
+        assert!(matches!(result, Err(Error::InvalidRole(_))));
+    }
+
+    #[test]
+    fn test_parse_role_id_from_mention() {
+        assert_eq!(parse_role_id("<@&12345>").unwrap(), 12345);
+        assert_eq!(parse_role_id(" <@&12345>  ").unwrap(), 12345);
+        assert_eq!(parse_role_id("\t12345 ").unwrap(), 12345);
+
+        for malformed in ["<@&12345", "<@12345>", "<#12345>", "<@&>", "<@&abc>"] {
+            let result = parse_role_id(malformed);
+            assert!(matches!(result, Err(Error::InvalidRole(_))), "{malformed}");
+        }
+    }
+}
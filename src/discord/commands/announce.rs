@@ -0,0 +1,147 @@
+use super::telegram_groups::{find_guild_group, guild_id, parse_group_id};
+use super::validate_guild;
+use crate::database::models::{AllowedGuild, TelegramGroup};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidTelegramGroupError, Result};
+use crate::discord::permissions::is_admin;
+use crate::messages::TelegramAction;
+use crate::telegram::{HtmlError, MAX_MESSAGE_LEN, validate_html};
+
+/// Post an announcement to the Telegram group of the server
+#[poise::command(
+    slash_command,
+    rename = "anunciar",
+    name_localized("en-US", "announce"),
+    check = "is_admin",
+    description_localized("pt-BR", "Envia um anúncio no grupo do Telegram do servidor")
+)]
+pub async fn announce(
+    ctx: Context<'_>,
+    #[description = "Mensagem do anúncio, aceita a formatação HTML do Telegram"] mensagem: String,
+    #[description = "ID do grupo do Telegram, o primeiro grupo do servidor se vazio"] grupo: Option<
+        String,
+    >,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let group_id = grupo.as_deref().map(parse_group_id).transpose()?;
+    let data = ctx.data();
+
+    validate_guild(&data.pool, guild_id).await?;
+    let (group_id, html) = resolve_broadcast(&data.pool, guild_id, group_id, &mensagem).await?;
+
+    let action = TelegramAction::Broadcast { group_id, html };
+    let description = match data.telegram_sender.send(action) {
+        Ok(_) => {
+            tracing::info!(group_id = group_id, user_id = %ctx.author().id, "Announcement queued");
+            format!("Anúncio enviado!\n\n**Grupo:** {group_id}")
+        }
+        Err(e) => {
+            tracing::error!(error = %e, group_id = group_id, "Failed to queue announcement");
+            "Falha ao enviar o anúncio".to_string()
+        }
+    };
+
+    let reply = create_standard_reply(&data.embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send announce command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Validates the announcement and finds the group it goes to, returning the group id along
+/// with the trimmed message
+async fn resolve_broadcast(
+    pool: &sqlx::PgPool,
+    guild_id: u64,
+    group_id: Option<i64>,
+    message: &str,
+) -> Result<(i64, String)> {
+    let html = message.trim();
+    if html.is_empty() || html.chars().count() > MAX_MESSAGE_LEN {
+        let message = format!("O anúncio precisa ter entre 1 e {MAX_MESSAGE_LEN} caracteres");
+        return Err(invalid_announcement(message));
+    }
+
+    if let Err(e) = validate_html(html) {
+        return Err(invalid_announcement(html_error_message(&e)));
+    }
+
+    let mut conn = pool.acquire().await?;
+    let group = match group_id {
+        Some(group_id) => find_guild_group(conn.as_mut(), guild_id, group_id).await?,
+        None => find_first_group(conn.as_mut(), guild_id).await?,
+    };
+
+    Ok((group.telegram_group_id, html.to_string()))
+}
+
+async fn find_first_group(conn: &mut sqlx::PgConnection, guild_id: u64) -> Result<TelegramGroup> {
+    let group = match AllowedGuild::find_by_guild_id(conn, guild_id as i64).await? {
+        Some(guild) => TelegramGroup::find_by_guild(conn, guild.id).await?,
+        None => None,
+    };
+
+    group.ok_or_else(|| {
+        invalid_announcement("Esse servidor não tem nenhum grupo do Telegram".to_string())
+    })
+}
+
+pub fn html_error_message(error: &HtmlError) -> String {
+    match error {
+        HtmlError::UnsupportedTag(tag) => format!("O Telegram não aceita a tag `<{tag}>`"),
+        HtmlError::UnclosedTag(tag) => format!("A tag `<{tag}>` não foi fechada"),
+        HtmlError::UnexpectedClosingTag(tag) => {
+            format!("A tag `</{tag}>` fecha uma tag que não estava aberta")
+        }
+        HtmlError::UnescapedBracket => "Use `&lt;` para escrever `<` na mensagem".to_string(),
+    }
+}
+
+fn invalid_announcement(message: String) -> Error {
+    Error::InvalidTelegramGroup(InvalidTelegramGroupError::new(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::insert_telegram_group;
+
+    const GUILD_ID: u64 = 1355012226355957780;
+
+    #[sqlx::test]
+    async fn test_broadcast_goes_to_the_guild_group(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, -100).await;
+
+        let broadcast = resolve_broadcast(&pool, GUILD_ID, None, " <b>Live</b> agora! ")
+            .await
+            .unwrap();
+
+        assert_eq!(broadcast, (-100, "<b>Live</b> agora!".to_string()));
+    }
+
+    #[sqlx::test]
+    async fn test_broadcast_rejects_other_guild_groups(pool: sqlx::PgPool) {
+        let result = resolve_broadcast(&pool, GUILD_ID, Some(-200), "Oi").await;
+        assert!(matches!(result, Err(Error::InvalidTelegramGroup(_))));
+
+        let result = resolve_broadcast(&pool, GUILD_ID, None, "Oi").await;
+        assert!(matches!(result, Err(Error::InvalidTelegramGroup(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_broadcast_rejects_invalid_html(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, -100).await;
+
+        let result = resolve_broadcast(&pool, GUILD_ID, None, "<div>Oi</div>").await;
+
+        let Err(Error::InvalidTelegramGroup(error)) = result else {
+            panic!("expected the announcement to be rejected");
+        };
+        assert_eq!(error.user_message(), "O Telegram não aceita a tag `<div>`");
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::Utc;
+
+use crate::database::models::UserLink;
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::Result;
+use crate::discord::permissions::is_admin;
+
+const DEFAULT_STALE_DAYS: u32 = 7;
+
+/// Remove links of users that never joined the Telegram group
+#[poise::command(
+    slash_command,
+    rename = "limpar",
+    name_localized("en-US", "cleanup"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Remove vínculos de usuários que nunca entraram no grupo do Telegram"
+    )
+)]
+pub async fn cleanup(
+    ctx: Context<'_>,
+    #[description = "Idade mínima dos vínculos em dias (padrão 7)"]
+    #[min = 1]
+    dias: Option<u32>,
+) -> Result<()> {
+    let days = dias.unwrap_or(DEFAULT_STALE_DAYS);
+    let deleted = cleanup_inner(&ctx.data().pool, days).await?;
+
+    tracing::info!(
+        user_id = %ctx.author().id,
+        days = days,
+        deleted = deleted,
+        "Stale pending links purged"
+    );
+
+    let description = format!(
+        "Limpeza concluída!\n\n**Vínculos removidos:** {deleted}\n**Mais antigos que:** {days} dias"
+    );
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send cleanup command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn cleanup_inner(pool: &sqlx::PgPool, days: u32) -> Result<u64> {
+    let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+    let mut conn = pool.acquire().await?;
+    let deleted = UserLink::delete_stale_pending(conn.as_mut(), cutoff).await?;
+    Ok(deleted)
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::database::models::UserLink;
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidDateError, Result};
+use crate::discord::permissions::is_admin;
+
+/// How many of the users that linked in a window were later removed, and how long they stayed
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CohortStats {
+    added: usize,
+    removed: usize,
+    /// `None` when nobody in the cohort was removed
+    average_time_to_removal: Option<TimeDelta>,
+}
+
+impl CohortStats {
+    fn from_links(links: &[UserLink]) -> Self {
+        let times_to_removal = links
+            .iter()
+            .filter_map(|link| {
+                link.deleted_at
+                    .map(|deleted_at| deleted_at - link.created_at)
+            })
+            .collect::<Vec<_>>();
+
+        let removed = times_to_removal.len();
+        let average_time_to_removal = match removed {
+            0 => None,
+            n => Some(times_to_removal.iter().sum::<TimeDelta>() / n as i32),
+        };
+
+        Self {
+            added: links.len(),
+            removed,
+            average_time_to_removal,
+        }
+    }
+
+    fn removed_percentage(&self) -> f64 {
+        match self.added {
+            0 => 0.0,
+            added => self.removed as f64 * 100.0 / added as f64,
+        }
+    }
+}
+
+/// Show how the users that linked in a period did
+#[poise::command(
+    slash_command,
+    rename = "cohort",
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Mostra quantos usuários vincularam em um período e quantos foram removidos depois"
+    )
+)]
+pub async fn cohort(
+    ctx: Context<'_>,
+    #[description = "Início do período, como 2026-10-01 ou 2026-10-01T12:00:00-03:00"]
+    inicio: String,
+    #[description = "Fim do período, incluindo o dia informado, até agora se vazio"] fim: Option<
+        String,
+    >,
+) -> Result<()> {
+    let start = parse_date(&inicio, false)?;
+    let end = match fim.as_deref() {
+        Some(end) => Some(parse_date(end, true)?),
+        None => None,
+    };
+
+    let stats = cohort_inner(&ctx.data().pool, start, end).await?;
+    let description = format_cohort(start, end, &stats);
+
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send cohort command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn cohort_inner(
+    pool: &sqlx::PgPool,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+) -> Result<CohortStats> {
+    if end.is_some_and(|end| end <= start) {
+        let message = "O fim do período precisa ser depois do início".to_string();
+        return Err(Error::InvalidDate(InvalidDateError::new(message)));
+    }
+
+    let mut conn = pool.acquire().await?;
+    let links = match end {
+        Some(end) => UserLink::get_added_between(conn.as_mut(), start, end).await?,
+        None => UserLink::get_added_after(conn.as_mut(), start).await?,
+    };
+
+    Ok(CohortStats::from_links(&links))
+}
+
+/// Parses an ISO-8601 date time, or a plain date at midnight UTC. A plain date used as the end
+/// of the period covers that whole day
+#[allow(clippy::result_large_err)]
+fn parse_date(input: &str, end_of_period: bool) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(date) = DateTime::parse_from_str(input, "%+") {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") else {
+        let message = format!("Data inválida `{input}`, use o formato 2026-10-01");
+        return Err(Error::InvalidDate(InvalidDateError::new(message)));
+    };
+
+    let date = match end_of_period {
+        true => date + TimeDelta::days(1),
+        false => date,
+    };
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+fn format_duration(duration: TimeDelta) -> String {
+    let days = duration.num_days();
+    let hours = duration.num_hours() % 24;
+
+    match days {
+        0 => format!("{hours}h"),
+        days => format!("{days}d {hours}h"),
+    }
+}
+
+fn format_cohort(start: DateTime<Utc>, end: Option<DateTime<Utc>>, stats: &CohortStats) -> String {
+    let period = match end {
+        Some(end) => format!(
+            "{} até {}",
+            start.format("%d/%m/%Y %H:%M"),
+            end.format("%d/%m/%Y %H:%M")
+        ),
+        None => format!("desde {}", start.format("%d/%m/%Y %H:%M")),
+    };
+    let average = stats
+        .average_time_to_removal
+        .map(format_duration)
+        .unwrap_or_else(|| "-".to_string());
+
+    format!(
+        "**Período:** {period} (UTC)\n**Usuários vinculados:** {}\n**Removidos depois:** {} ({:.1}%)\n**Tempo médio até a remoção:** {average}",
+        stats.added,
+        stats.removed,
+        stats.removed_percentage()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::UserLinkPayload;
+    use crate::test_helpers::default_guild_id;
+
+    #[test]
+    fn test_parse_date() {
+        let start = parse_date("2026-10-01", false).unwrap();
+        assert_eq!(start.to_rfc3339(), "2026-10-01T00:00:00+00:00");
+
+        let end = parse_date("2026-10-01", true).unwrap();
+        assert_eq!(end.to_rfc3339(), "2026-10-02T00:00:00+00:00");
+
+        let with_offset = parse_date("2026-10-01T12:00:00-03:00", true).unwrap();
+        assert_eq!(with_offset.to_rfc3339(), "2026-10-01T15:00:00+00:00");
+
+        let invalid = parse_date("01/10/2026", false);
+        assert!(matches!(invalid, Err(Error::InvalidDate(_))));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(TimeDelta::hours(5)), "5h");
+        assert_eq!(format_duration(TimeDelta::hours(50)), "2d 2h");
+    }
+
+    #[sqlx::test]
+    async fn test_cohort_stats(pool: sqlx::PgPool) {
+        let start = parse_date("2026-10-01", false).unwrap();
+        let end = parse_date("2026-10-31", true).unwrap();
+
+        let empty = cohort_inner(&pool, start, Some(end)).await.unwrap();
+        assert_eq!(empty, CohortStats::default());
+        assert_eq!(empty.removed_percentage(), 0.0);
+
+        let mut conn = pool.acquire().await.unwrap();
+
+        let guild_id = default_guild_id(&mut conn).await;
+        for (discord_id, deleted_after_days) in
+            [(10, Some(2)), (20, Some(4)), (30, None), (40, None)]
+        {
+            let payload = UserLinkPayload::new(discord_id, discord_id * 10, guild_id);
+            let user = UserLink::create_link(conn.as_mut(), payload).await.unwrap();
+            let deleted_at = deleted_after_days.map(|days| start + TimeDelta::days(days));
+            sqlx::query("UPDATE user_links SET created_at = $1, deleted_at = $2 WHERE id = $3")
+                .bind(start)
+                .bind(deleted_at)
+                .bind(user.id)
+                .execute(conn.as_mut())
+                .await
+                .unwrap();
+        }
+
+        let stats = cohort_inner(&pool, start, Some(end)).await.unwrap();
+        assert_eq!(stats.added, 4);
+        assert_eq!(stats.removed, 2);
+        assert_eq!(stats.removed_percentage(), 50.0);
+        assert_eq!(stats.average_time_to_removal, Some(TimeDelta::days(3)));
+
+        let later = parse_date("2026-11-01", false).unwrap();
+        let stats = cohort_inner(&pool, later, None).await.unwrap();
+        assert_eq!(stats.added, 0);
+
+        let reversed = cohort_inner(&pool, end, Some(start)).await;
+        assert!(matches!(reversed, Err(Error::InvalidDate(_))));
+    }
+}
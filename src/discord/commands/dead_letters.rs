@@ -0,0 +1,103 @@
+use itertools::Itertools;
+
+use crate::database::models::TelegramDeadLetter;
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::Result;
+use crate::discord::permissions::is_admin;
+
+const DEFAULT_LIMIT: u8 = 10;
+const MAX_LIMIT: u8 = 25;
+/// Errors are cut so a full page still fits in the embed description
+const MAX_ERROR_LEN: usize = 120;
+
+/// Show the Telegram actions that failed even after retrying
+#[poise::command(
+    slash_command,
+    rename = "dlq",
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Mostra as ações do Telegram que falharam mesmo depois de tentar de novo"
+    )
+)]
+pub async fn dead_letters(
+    ctx: Context<'_>,
+    #[description = "Quantas falhas mostrar, 10 se vazio"]
+    #[min = 1]
+    #[max = 25]
+    limite: Option<u8>,
+) -> Result<()> {
+    let limit = limite.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let description = dead_letters_inner(&ctx.data().pool, limit).await?;
+
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send dead letters command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn dead_letters_inner(pool: &sqlx::PgPool, limit: u8) -> Result<String> {
+    let mut conn = pool.acquire().await?;
+    let dead_letters = TelegramDeadLetter::list_recent(conn.as_mut(), limit.into()).await?;
+    Ok(format_dead_letters(&dead_letters))
+}
+
+fn format_dead_letters(dead_letters: &[TelegramDeadLetter]) -> String {
+    if dead_letters.is_empty() {
+        return "Nenhuma ação do Telegram falhou".to_string();
+    }
+
+    let entries = dead_letters
+        .iter()
+        .map(|dead_letter| {
+            let user = match dead_letter.telegram_id {
+                Some(telegram_id) => format!("usuário `{telegram_id}`, "),
+                None => String::new(),
+            };
+            let error = dead_letter
+                .last_error
+                .chars()
+                .take(MAX_ERROR_LEN)
+                .collect::<String>();
+
+            format!(
+                "**{}** {} - {user}grupo `{}`\n{error}",
+                dead_letter.failed_at.format("%d/%m/%Y %H:%M"),
+                dead_letter.action_kind,
+                dead_letter.group_id
+            )
+        })
+        .join("\n\n");
+
+    format!("Ações do Telegram que falharam:\n\n{entries}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_lists_recent_dead_letters(pool: sqlx::PgPool) {
+        let empty = dead_letters_inner(&pool, DEFAULT_LIMIT).await.unwrap();
+        assert_eq!(empty, "Nenhuma ação do Telegram falhou");
+
+        let mut conn = pool.acquire().await.unwrap();
+        let long_error = "x".repeat(MAX_ERROR_LEN + 50);
+        TelegramDeadLetter::insert(conn.as_mut(), "remove", Some(777), -100, &long_error)
+            .await
+            .unwrap();
+        TelegramDeadLetter::insert(conn.as_mut(), "broadcast", None, -200, "Network error")
+            .await
+            .unwrap();
+
+        let listed = dead_letters_inner(&pool, DEFAULT_LIMIT).await.unwrap();
+        assert!(listed.contains("remove - usuário `777`, grupo `-100`"));
+        assert!(listed.contains("broadcast - grupo `-200`\nNetwork error"));
+        assert!(listed.contains(&"x".repeat(MAX_ERROR_LEN)));
+        assert!(!listed.contains(&long_error));
+    }
+}
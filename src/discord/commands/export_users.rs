@@ -0,0 +1,128 @@
+use poise::serenity_prelude as serenity;
+
+use crate::database::models::{AllowedGuild, MAX_EXPORT_ROWS, UserLink};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, Result};
+use crate::discord::permissions::is_admin;
+
+const CSV_HEADER: &str = "discord_id,telegram_id,created_at,added_to_group_at";
+
+fn users_csv(users: &[UserLink]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+
+    for user in users {
+        let added_to_group_at = user
+            .added_to_group_at
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default();
+
+        csv.push('\n');
+        csv.push_str(&format!(
+            "{},{},{},{}",
+            user.discord_id,
+            user.telegram_id,
+            user.created_at.to_rfc3339(),
+            added_to_group_at
+        ));
+    }
+
+    csv.push('\n');
+    csv
+}
+
+/// Export the linked users of the server as a CSV file
+#[poise::command(
+    slash_command,
+    rename = "exportar_usuarios",
+    name_localized("en-US", "export_users"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Exporta os usuários vinculados do servidor em um arquivo CSV"
+    )
+)]
+pub async fn export_users(ctx: Context<'_>) -> Result<()> {
+    let Some(guild_id) = ctx.guild_id() else {
+        let message = "Esse comando só pode ser usado em servidores".to_string();
+        return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+    };
+
+    let users = export_users_inner(&ctx.data().pool, guild_id.get()).await?;
+
+    tracing::info!(
+        user_id = %ctx.author().id,
+        exported = users.len(),
+        "Linked users exported"
+    );
+
+    let mut description = format!("**Usuários exportados:** {}", users.len());
+    if users.len() as i64 >= MAX_EXPORT_ROWS {
+        description.push_str(&format!(
+            "\n\nA exportação foi limitada aos {MAX_EXPORT_ROWS} vínculos mais antigos"
+        ));
+    }
+
+    let attachment = serenity::CreateAttachment::bytes(users_csv(&users), "users.csv");
+    let reply = create_standard_reply(&ctx.data().embed, description).attachment(attachment);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send export users command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn export_users_inner(pool: &sqlx::PgPool, guild_id: u64) -> Result<Vec<UserLink>> {
+    let mut conn = pool.acquire().await?;
+
+    let Some(guild) = AllowedGuild::find_by_guild_id(conn.as_mut(), guild_id as i64).await? else {
+        let message = "Esse canal não é um canal de um servidor permitido".to_string();
+        return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+    };
+
+    let users = UserLink::export_for_guild(conn.as_mut(), guild.id).await?;
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD_ID: u64 = 258648784039313408;
+
+    #[sqlx::test]
+    async fn test_export_only_includes_guild_users(pool: sqlx::PgPool) {
+        sqlx::query(
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id, added_to_group_at)
+            SELECT 1, 2, id, '2026-01-02T03:04:05Z' FROM allowed_guilds WHERE guild_id = $1",
+        )
+        .bind(GUILD_ID as i64)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO user_links (discord_id, telegram_id, guild_id)
+            SELECT 3, 4, id FROM allowed_guilds WHERE guild_id <> $1",
+        )
+        .bind(GUILD_ID as i64)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let users = export_users_inner(&pool, GUILD_ID).await.unwrap();
+        let csv = users_csv(&users);
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with("1,2,"));
+        assert!(lines[1].ends_with(",2026-01-02T03:04:05+00:00"));
+    }
+
+    #[sqlx::test]
+    async fn test_export_rejects_unknown_guild(pool: sqlx::PgPool) {
+        let result = export_users_inner(&pool, 42).await;
+        assert!(matches!(result, Err(Error::InvalidGuild(_))));
+    }
+}
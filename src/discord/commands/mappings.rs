@@ -0,0 +1,282 @@
+use itertools::Itertools;
+
+use super::allowed_roles::parse_role_id;
+use super::telegram_groups::{find_guild_group, guild_id, parse_group_id};
+use super::validate_guild;
+use crate::database::models::{
+    AllowedGuild, AllowedRole, MappedGroup, RoleGroupMapping, RoleGroupMappingPayload,
+    TelegramGroup,
+};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, InvalidRoleError, Result};
+use crate::discord::permissions::is_admin;
+
+/// Manage which Telegram group each role is invited to
+#[poise::command(
+    slash_command,
+    rename = "mapeamentos",
+    name_localized("en-US", "mappings"),
+    check = "is_admin",
+    subcommands("list_mappings", "create_mapping", "delete_mapping"),
+    description_localized("pt-BR", "Gerenciar qual grupo do Telegram cada cargo recebe")
+)]
+pub async fn mappings(ctx: Context<'_>) -> Result<()> {
+    let message = "Por favor, use um dos subcomandos: `/mapeamentos listar`, `/mapeamentos novo` ou `/mapeamentos remover`".into();
+    let reply = create_standard_reply(&ctx.data().embed, message);
+
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send mappings command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// List the role to group mappings of the server
+#[poise::command(
+    slash_command,
+    rename = "listar",
+    name_localized("en-US", "list"),
+    check = "is_admin",
+    description_localized("pt-BR", "Lista os mapeamentos de cargo para grupo do servidor")
+)]
+async fn list_mappings(ctx: Context<'_>) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+
+    validate_guild(&ctx.data().pool, guild_id).await?;
+    let formatted_mappings = list_mappings_inner(&ctx.data().pool, guild_id).await?;
+
+    let reply = create_standard_reply(&ctx.data().embed, formatted_mappings);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list mappings command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn list_mappings_inner(pool: &sqlx::PgPool, guild_id: u64) -> Result<String> {
+    let mut conn = pool.acquire().await?;
+    let guild = find_guild(conn.as_mut(), guild_id).await?;
+    let mappings = RoleGroupMapping::get_all_for_guild(conn.as_mut(), guild.id).await?;
+    Ok(format_mappings(&mappings))
+}
+
+fn format_mappings(mappings: &[MappedGroup]) -> String {
+    if mappings.is_empty() {
+        return "Nenhum cargo mapeado, todos entram no primeiro grupo do servidor".to_string();
+    }
+
+    // Padded inside a code block so the groups line up in a column
+    let width = mappings
+        .iter()
+        .map(|mapping| mapping.role_name.chars().count())
+        .chain(["Cargo".len()])
+        .max()
+        .unwrap_or_default();
+
+    let rows = mappings
+        .iter()
+        .map(|mapping| {
+            format!(
+                "{:<width$} → {} ({})",
+                mapping.role_name, mapping.group_name, mapping.telegram_group_id
+            )
+        })
+        .join("\n");
+
+    format!(
+        "Mapeamentos de cargo para grupo:\n\n```\n{:<width$} → Grupo\n{rows}\n```",
+        "Cargo"
+    )
+}
+
+/// Invite users with a role to a specific Telegram group
+#[poise::command(
+    slash_command,
+    rename = "novo",
+    name_localized("en-US", "new"),
+    check = "is_admin",
+    description_localized("pt-BR", "Faz usuários com um cargo entrarem em um grupo do Telegram")
+)]
+async fn create_mapping(
+    ctx: Context<'_>,
+    #[description = "ID do cargo permitido"] cargo: String,
+    #[description = "ID do grupo do Telegram"] grupo: String,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let role_id = parse_role_id(&cargo)?;
+    let group_id = parse_group_id(&grupo)?;
+    let data = ctx.data();
+
+    validate_guild(&data.pool, guild_id).await?;
+    let (role, group) = create_mapping_inner(&data.pool, guild_id, role_id, group_id).await?;
+
+    tracing::info!(
+        user_id = %ctx.author().id,
+        role_id = role_id,
+        group_id = group_id,
+        "Role group mapping created"
+    );
+
+    let description = format!(
+        "Mapeamento criado!\n\n**Cargo:** {}\n**Grupo:** {}",
+        role.name, group.name
+    );
+    let reply = create_standard_reply(&data.embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send create mapping command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn create_mapping_inner(
+    pool: &sqlx::PgPool,
+    guild_id: u64,
+    role_id: i64,
+    group_id: i64,
+) -> Result<(AllowedRole, TelegramGroup)> {
+    let mut conn = pool.acquire().await?;
+    let role = find_allowed_role(conn.as_mut(), role_id).await?;
+    let group = find_guild_group(conn.as_mut(), guild_id, group_id).await?;
+
+    if RoleGroupMapping::exists(conn.as_mut(), role.id).await? {
+        let message = "Esse cargo já está mapeado para um grupo, remova o mapeamento antes".into();
+        return Err(Error::InvalidRole(InvalidRoleError::new(message)));
+    }
+
+    let payload = RoleGroupMappingPayload::new(role.id, group.id);
+    RoleGroupMapping::create(conn.as_mut(), payload).await?;
+    Ok((role, group))
+}
+
+/// Send users with a role back to the server's first Telegram group
+#[poise::command(
+    slash_command,
+    rename = "remover",
+    name_localized("en-US", "remove"),
+    check = "is_admin",
+    description_localized("pt-BR", "Remove o mapeamento de um cargo")
+)]
+async fn delete_mapping(
+    ctx: Context<'_>,
+    #[description = "ID do cargo mapeado"] cargo: String,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let role_id = parse_role_id(&cargo)?;
+    let data = ctx.data();
+
+    validate_guild(&data.pool, guild_id).await?;
+    let role = delete_mapping_inner(&data.pool, role_id).await?;
+
+    tracing::info!(user_id = %ctx.author().id, role_id = role_id, "Role group mapping removed");
+
+    let description = format!("Mapeamento removido!\n\n**Cargo:** {}", role.name);
+    let reply = create_standard_reply(&data.embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send delete mapping command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn delete_mapping_inner(pool: &sqlx::PgPool, role_id: i64) -> Result<AllowedRole> {
+    let mut conn = pool.acquire().await?;
+    let role = find_allowed_role(conn.as_mut(), role_id).await?;
+
+    let Some(mapping) = RoleGroupMapping::find_by_role(conn.as_mut(), role.id).await? else {
+        let message = "Esse cargo não está mapeado para nenhum grupo".to_string();
+        return Err(Error::InvalidRole(InvalidRoleError::new(message)));
+    };
+
+    RoleGroupMapping::delete(conn.as_mut(), mapping.id).await?;
+    Ok(role)
+}
+
+async fn find_guild(conn: &mut sqlx::PgConnection, guild_id: u64) -> Result<AllowedGuild> {
+    match AllowedGuild::find_by_guild_id(conn, guild_id as i64).await? {
+        Some(guild) => Ok(guild),
+        None => {
+            let message = "Esse canal não é um canal de um servidor permitido".to_string();
+            Err(Error::InvalidGuild(InvalidGuildError::new(message)))
+        }
+    }
+}
+
+async fn find_allowed_role(conn: &mut sqlx::PgConnection, role_id: i64) -> Result<AllowedRole> {
+    match AllowedRole::find_by_role_id(conn, role_id).await? {
+        Some(role) => Ok(role),
+        None => {
+            let message = "Esse cargo não é um cargo permitido".to_string();
+            Err(Error::InvalidRole(InvalidRoleError::new(message)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::insert_telegram_group;
+
+    const GUILD_ID: u64 = 258648784039313408;
+    const GROUP_ID: i64 = -1001234567890;
+    const ADMIN_ROLE: i64 = 258661569200652289;
+
+    #[sqlx::test]
+    async fn test_create_list_and_delete_mapping(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, GROUP_ID).await;
+
+        let empty = list_mappings_inner(&pool, GUILD_ID).await.unwrap();
+        assert!(empty.starts_with("Nenhum cargo mapeado"));
+
+        create_mapping_inner(&pool, GUILD_ID, ADMIN_ROLE, GROUP_ID)
+            .await
+            .unwrap();
+        let listed = list_mappings_inner(&pool, GUILD_ID).await.unwrap();
+        assert!(listed.contains(&format!("→ Grupo ({GROUP_ID})")));
+
+        let duplicate = create_mapping_inner(&pool, GUILD_ID, ADMIN_ROLE, GROUP_ID).await;
+        assert!(matches!(duplicate, Err(Error::InvalidRole(_))));
+
+        delete_mapping_inner(&pool, ADMIN_ROLE).await.unwrap();
+        let empty = list_mappings_inner(&pool, GUILD_ID).await.unwrap();
+        assert!(empty.starts_with("Nenhum cargo mapeado"));
+
+        let missing = delete_mapping_inner(&pool, ADMIN_ROLE).await;
+        assert!(matches!(missing, Err(Error::InvalidRole(_))));
+    }
+
+    #[test]
+    fn test_format_mappings_aligns_groups() {
+        let mapping = |role_name: &str, group_name: &str| MappedGroup {
+            discord_role_id: 1,
+            role_name: role_name.to_string(),
+            telegram_group_id: -1,
+            group_name: group_name.to_string(),
+        };
+
+        let formatted = format_mappings(&[mapping("Mods", "Staff"), mapping("Membros", "Geral")]);
+
+        assert!(
+            formatted
+                .contains("```\nCargo   → Grupo\nMods    → Staff (-1)\nMembros → Geral (-1)\n```")
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_create_mapping_rejects_unknown_role_and_group(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, GROUP_ID).await;
+
+        let unknown_role = create_mapping_inner(&pool, GUILD_ID, 42, GROUP_ID).await;
+        assert!(matches!(unknown_role, Err(Error::InvalidRole(_))));
+
+        let unknown_group = create_mapping_inner(&pool, GUILD_ID, ADMIN_ROLE, -42).await;
+        assert!(matches!(unknown_group, Err(Error::InvalidTelegramGroup(_))));
+    }
+}
@@ -0,0 +1,342 @@
+use poise::ChoiceParameter;
+use sqlx::types::Uuid;
+
+use super::announce::html_error_message;
+use super::telegram_groups::guild_id;
+use super::validate_guild;
+use crate::database::models::{AllowedGuild, MessageTemplate};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, InvalidMessageTemplateError, Result};
+use crate::discord::permissions::is_admin;
+use crate::telegram::validate_html;
+
+/// Longest template accepted, leaves room for the link the bot adds after it
+const MAX_TEMPLATE_LEN: usize = 3500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+enum TemplateChoice {
+    #[name = "boas_vindas"]
+    Welcome,
+    #[name = "nao_vinculado"]
+    Unlinked,
+    #[name = "convite"]
+    Invite,
+}
+
+impl TemplateChoice {
+    fn template_name(self) -> &'static str {
+        match self {
+            TemplateChoice::Welcome => MessageTemplate::WELCOME,
+            TemplateChoice::Unlinked => MessageTemplate::UNLINKED,
+            TemplateChoice::Invite => MessageTemplate::INVITE,
+        }
+    }
+}
+
+/// Manage the messages the Telegram bot sends
+#[poise::command(
+    slash_command,
+    rename = "mensagens",
+    name_localized("en-US", "messages"),
+    check = "is_admin",
+    subcommands("list_templates", "set_template", "remove_template"),
+    description_localized("pt-BR", "Gerenciar as mensagens enviadas pelo bot do Telegram")
+)]
+pub async fn message_templates(ctx: Context<'_>) -> Result<()> {
+    let message =
+        "Por favor, use um dos subcomandos: `/mensagens listar`, `/mensagens definir` ou `/mensagens remover`"
+            .into();
+    let reply = create_standard_reply(&ctx.data().embed, message);
+
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send message templates command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// List the customized messages
+#[poise::command(
+    slash_command,
+    rename = "listar",
+    name_localized("en-US", "list"),
+    check = "is_admin",
+    description_localized("pt-BR", "Lista as mensagens personalizadas")
+)]
+async fn list_templates(ctx: Context<'_>) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    validate_guild(&ctx.data().pool, guild_id).await?;
+
+    let description = list_templates_inner(&ctx.data().pool, guild_id).await?;
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send list message templates command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Global templates and the ones of the current guild, other guilds' overrides are hidden
+async fn list_templates_inner(pool: &sqlx::PgPool, guild_id: u64) -> Result<String> {
+    let mut conn = pool.acquire().await?;
+    let guild = find_guild(conn.as_mut(), guild_id).await?;
+    let templates = MessageTemplate::get_all(conn.as_mut()).await?;
+
+    let templates = templates
+        .iter()
+        .filter(|template| template.guild_id.is_none_or(|id| id == guild.id))
+        .map(|template| {
+            let scope = match template.guild_id {
+                Some(_) => "este servidor",
+                None => "todos os servidores",
+            };
+            format!("**{}** ({scope})\n{}", template.name, template.content)
+        })
+        .collect::<Vec<_>>();
+
+    if templates.is_empty() {
+        return Ok("Nenhuma mensagem personalizada, o bot usa as mensagens padrão".to_string());
+    }
+
+    Ok(format!(
+        "Lista de mensagens personalizadas:\n\n{}",
+        templates.join("\n\n")
+    ))
+}
+
+/// Replace one of the bot messages
+#[poise::command(
+    slash_command,
+    rename = "definir",
+    name_localized("en-US", "set"),
+    check = "is_admin",
+    description_localized("pt-BR", "Personaliza uma das mensagens do bot do Telegram")
+)]
+async fn set_template(
+    ctx: Context<'_>,
+    #[description = "Mensagem para personalizar"] tipo: TemplateChoice,
+    #[description = "Texto com a formatação HTML do Telegram, use \\n para quebrar linha e {usuario} para o nome"]
+    conteudo: String,
+    #[description = "Vale só para este servidor? Apenas para o convite"] servidor: Option<bool>,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let only_guild = servidor.unwrap_or_default();
+    validate_guild(&ctx.data().pool, guild_id).await?;
+
+    let template =
+        set_template_inner(&ctx.data().pool, guild_id, tipo, &conteudo, only_guild).await?;
+
+    tracing::info!(
+        user_id = %ctx.author().id,
+        template = template.name,
+        guild_id = ?template.guild_id,
+        "Message template updated"
+    );
+
+    let description = format!(
+        "Mensagem personalizada!\n\n**Mensagem:** {}\n\n{}",
+        template.name, template.content
+    );
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send set message template command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn set_template_inner(
+    pool: &sqlx::PgPool,
+    guild_id: u64,
+    choice: TemplateChoice,
+    content: &str,
+    only_guild: bool,
+) -> Result<MessageTemplate> {
+    // Slash command options are single line, so line breaks are written as `\n`
+    let content = content.trim().replace("\\n", "\n");
+    if content.is_empty() || content.chars().count() > MAX_TEMPLATE_LEN {
+        let message = format!("A mensagem precisa ter entre 1 e {MAX_TEMPLATE_LEN} caracteres");
+        return Err(invalid_template(message));
+    }
+
+    if let Err(e) = validate_html(&content) {
+        return Err(invalid_template(html_error_message(&e)));
+    }
+
+    let mut conn = pool.acquire().await?;
+    let scope = template_scope(conn.as_mut(), guild_id, choice, only_guild).await?;
+    let template =
+        MessageTemplate::upsert(conn.as_mut(), choice.template_name(), &content, scope).await?;
+
+    Ok(template)
+}
+
+/// Go back to the default text of one of the bot messages
+#[poise::command(
+    slash_command,
+    rename = "remover",
+    name_localized("en-US", "remove"),
+    check = "is_admin",
+    description_localized("pt-BR", "Volta uma das mensagens do bot para o texto padrão")
+)]
+async fn remove_template(
+    ctx: Context<'_>,
+    #[description = "Mensagem para voltar ao padrão"] tipo: TemplateChoice,
+    #[description = "Remover a versão deste servidor? Apenas para o convite"] servidor: Option<
+        bool,
+    >,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let only_guild = servidor.unwrap_or_default();
+    validate_guild(&ctx.data().pool, guild_id).await?;
+
+    remove_template_inner(&ctx.data().pool, guild_id, tipo, only_guild).await?;
+
+    tracing::info!(
+        user_id = %ctx.author().id,
+        template = tipo.template_name(),
+        only_guild = only_guild,
+        "Message template removed"
+    );
+
+    let description = format!(
+        "Mensagem **{}** voltou para o texto padrão",
+        tipo.template_name()
+    );
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send remove message template command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn remove_template_inner(
+    pool: &sqlx::PgPool,
+    guild_id: u64,
+    choice: TemplateChoice,
+    only_guild: bool,
+) -> Result<()> {
+    let mut conn = pool.acquire().await?;
+    let scope = template_scope(conn.as_mut(), guild_id, choice, only_guild).await?;
+
+    let deleted = MessageTemplate::delete(conn.as_mut(), choice.template_name(), scope).await?;
+    if !deleted {
+        let message = "Essa mensagem não foi personalizada".to_string();
+        return Err(invalid_template(message));
+    }
+
+    Ok(())
+}
+
+/// Guild the template belongs to. Only the invite is sent in the context of a group, the other
+/// messages are private chats with the bot and can only be customized for every guild
+async fn template_scope(
+    conn: &mut sqlx::PgConnection,
+    guild_id: u64,
+    choice: TemplateChoice,
+    only_guild: bool,
+) -> Result<Option<Uuid>> {
+    if !only_guild {
+        return Ok(None);
+    }
+
+    if choice != TemplateChoice::Invite {
+        let message = "Só o convite pode ser personalizado por servidor".to_string();
+        return Err(invalid_template(message));
+    }
+
+    let guild = find_guild(conn, guild_id).await?;
+    Ok(Some(guild.id))
+}
+
+async fn find_guild(conn: &mut sqlx::PgConnection, guild_id: u64) -> Result<AllowedGuild> {
+    AllowedGuild::find_by_guild_id(conn, guild_id as i64)
+        .await?
+        .ok_or_else(|| {
+            let message = "Esse canal não é um canal de um servidor permitido".to_string();
+            Error::InvalidGuild(InvalidGuildError::new(message))
+        })
+}
+
+fn invalid_template(message: String) -> Error {
+    Error::InvalidMessageTemplate(InvalidMessageTemplateError::new(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD_ID: u64 = 258648784039313408;
+    const OTHER_GUILD_ID: u64 = 1355012226355957780;
+
+    #[sqlx::test]
+    async fn test_set_and_list_templates(pool: sqlx::PgPool) {
+        let template = set_template_inner(
+            &pool,
+            GUILD_ID,
+            TemplateChoice::Welcome,
+            " <b>Oi {usuario}</b>\\nBora vincular? ",
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(template.content, "<b>Oi {usuario}</b>\nBora vincular?");
+        assert_eq!(template.guild_id, None);
+
+        set_template_inner(&pool, OTHER_GUILD_ID, TemplateChoice::Invite, "Teste", true)
+            .await
+            .unwrap();
+
+        let listed = list_templates_inner(&pool, GUILD_ID).await.unwrap();
+        assert!(listed.contains("**boas_vindas** (todos os servidores)"));
+        assert!(!listed.contains("Teste"));
+
+        let listed = list_templates_inner(&pool, OTHER_GUILD_ID).await.unwrap();
+        assert!(listed.contains("**convite** (este servidor)\nTeste"));
+    }
+
+    #[sqlx::test]
+    async fn test_set_template_rejects_invalid_templates(pool: sqlx::PgPool) {
+        let result = set_template_inner(
+            &pool,
+            GUILD_ID,
+            TemplateChoice::Invite,
+            "<div>Oi</div>",
+            false,
+        )
+        .await;
+        let Err(Error::InvalidMessageTemplate(error)) = result else {
+            panic!("expected the template to be rejected");
+        };
+        assert_eq!(error.user_message(), "O Telegram não aceita a tag `<div>`");
+
+        let result = set_template_inner(&pool, GUILD_ID, TemplateChoice::Welcome, "Oi", true).await;
+        assert!(matches!(result, Err(Error::InvalidMessageTemplate(_))));
+
+        let result =
+            set_template_inner(&pool, GUILD_ID, TemplateChoice::Welcome, "  ", false).await;
+        assert!(matches!(result, Err(Error::InvalidMessageTemplate(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_remove_template(pool: sqlx::PgPool) {
+        set_template_inner(&pool, GUILD_ID, TemplateChoice::Invite, "Oi", true)
+            .await
+            .unwrap();
+
+        let result = remove_template_inner(&pool, GUILD_ID, TemplateChoice::Invite, false).await;
+        assert!(matches!(result, Err(Error::InvalidMessageTemplate(_))));
+
+        remove_template_inner(&pool, GUILD_ID, TemplateChoice::Invite, true)
+            .await
+            .unwrap();
+        let listed = list_templates_inner(&pool, GUILD_ID).await.unwrap();
+        assert!(listed.starts_with("Nenhuma mensagem personalizada"));
+    }
+}
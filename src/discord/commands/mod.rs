@@ -1,17 +1,43 @@
 mod allowed_channels;
+mod allowed_guilds;
 mod allowed_roles;
+mod announce;
+mod cleanup;
+mod cohort;
+mod dead_letters;
+mod export_users;
+mod mappings;
+mod message_templates;
+mod removal_policy;
+mod sync;
 mod telegram;
+mod telegram_groups;
 mod verify_members;
 
 pub use allowed_channels::channels;
+pub use allowed_guilds::guilds;
 pub use allowed_roles::roles;
+pub use announce::announce;
 use chrono::Timelike;
+pub use cleanup::cleanup;
+pub use cohort::cohort;
+pub use dead_letters::dead_letters;
+pub use export_users::export_users;
+pub use mappings::mappings;
+pub use message_templates::message_templates;
 use poise::{CreateReply, serenity_prelude as serenity};
+pub use removal_policy::removal_policy;
+pub use sync::sync;
 pub use telegram::telegram;
+pub use telegram_groups::groups;
 pub use verify_members::verify_members;
 
+use super::EmbedConfig;
 use super::error::{Error, InvalidGuildError, Result};
-use crate::database::models::allowed_guilds::AllowedGuild;
+use crate::database::models::AllowedGuild;
+
+/// Longest role, channel or guild name accepted before storing it
+pub const MAX_NAME_LEN: usize = 100;
 
 pub fn get_meiafelps_formatted_date() -> String {
     let now = chrono::Utc::now();
@@ -24,21 +50,20 @@ pub fn get_meiafelps_formatted_date() -> String {
     format!("{hour}:{minutes} {suffix}")
 }
 
-pub fn create_embed(description: String) -> serenity::CreateEmbed {
+pub fn create_embed(config: &EmbedConfig, description: String) -> serenity::CreateEmbed {
     let author = serenity::CreateEmbedAuthor::new("felbot");
     let footer_message = format!("Agora são {}", get_meiafelps_formatted_date());
-    let footer = serenity::CreateEmbedFooter::new(footer_message)
-        .icon_url("https://yt3.googleusercontent.com/c0u2JGrq6Ke9i15R66z2u3RR0fY8RHFAkrocO8cGkRu2FLhke2DH_e_zjiW17_RnBHDzQw4KlA=s160-c-k-c0x00ffffff-no-rj");
+    let footer = serenity::CreateEmbedFooter::new(footer_message).icon_url(&config.footer_icon_url);
 
     serenity::CreateEmbed::new()
-        .color((255, 62, 117))
+        .color(config.color)
         .description(description)
         .author(author)
         .footer(footer)
 }
 
-pub fn create_standard_reply(description: String) -> CreateReply {
-    let embed = create_embed(description);
+pub fn create_standard_reply(config: &EmbedConfig, description: String) -> CreateReply {
+    let embed = create_embed(config, description);
     CreateReply::default().embed(embed).ephemeral(true)
 }
 
@@ -53,3 +78,52 @@ pub async fn validate_guild(pool: &sqlx::PgPool, guild_id: u64) -> Result<()> {
 
     Ok(())
 }
+
+/// Ids pasted as Discord mentions, like `<#id>` for channels or `<@&id>` for roles, keep only
+/// the id. Anything else is returned trimmed and left for the caller to reject
+pub fn strip_mention<'a>(input: &'a str, prefix: &str) -> &'a str {
+    let input = input.trim();
+    input
+        .strip_prefix(prefix)
+        .and_then(|id| id.strip_suffix('>'))
+        .unwrap_or(input)
+}
+
+/// Rejects names that are too long or contain null bytes, which postgres refuses in text
+/// columns. `into_error` wraps the message in the error of whatever is being named
+#[allow(clippy::result_large_err)]
+pub fn validate_name(name: &str, into_error: fn(String) -> Error) -> Result<()> {
+    if name.chars().count() > MAX_NAME_LEN {
+        let message = format!("O nome deve ter no máximo {MAX_NAME_LEN} caracteres");
+        return Err(into_error(message));
+    }
+
+    if name.contains('\0') {
+        let message = "O nome não pode conter caracteres nulos".to_string();
+        return Err(into_error(message));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord::error::InvalidRoleError;
+
+    fn role_error(message: String) -> Error {
+        Error::InvalidRole(InvalidRoleError::new(message))
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("Felpudo", role_error).is_ok());
+        assert!(validate_name(&"á".repeat(MAX_NAME_LEN), role_error).is_ok());
+
+        let too_long = validate_name(&"a".repeat(MAX_NAME_LEN + 1), role_error);
+        assert!(matches!(too_long, Err(Error::InvalidRole(_))));
+
+        let null_byte = validate_name("Fel\0pudo", role_error);
+        assert!(matches!(null_byte, Err(Error::InvalidRole(_))));
+    }
+}
@@ -0,0 +1,115 @@
+use poise::ChoiceParameter;
+
+use super::validate_guild;
+use crate::database::models::{AllowedGuild, RemovalPolicy};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, Result};
+use crate::discord::permissions::is_admin;
+
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+enum PolicyChoice {
+    #[name = "remover"]
+    Kick,
+    #[name = "apenas notificar"]
+    NotifyOnly,
+    #[name = "restringir"]
+    Restrict,
+}
+
+impl From<PolicyChoice> for RemovalPolicy {
+    fn from(choice: PolicyChoice) -> Self {
+        match choice {
+            PolicyChoice::Kick => RemovalPolicy::Kick,
+            PolicyChoice::NotifyOnly => RemovalPolicy::NotifyOnly,
+            PolicyChoice::Restrict => RemovalPolicy::Restrict,
+        }
+    }
+}
+
+/// Set what happens to users that lost the allowed roles
+#[poise::command(
+    slash_command,
+    rename = "politica",
+    name_localized("en-US", "policy"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Define o que acontece com usuários que perderam os cargos permitidos"
+    )
+)]
+pub async fn removal_policy(
+    ctx: Context<'_>,
+    #[description = "Remover do grupo, apenas notificar os administradores ou restringir antes de remover"]
+    politica: PolicyChoice,
+) -> Result<()> {
+    let Some(guild_id) = ctx.guild_id() else {
+        let message = "Esse comando só pode ser usado em servidores".to_string();
+        return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+    };
+    let guild_id = guild_id.get();
+    let policy = RemovalPolicy::from(politica);
+
+    validate_guild(&ctx.data().pool, guild_id).await?;
+    set_policy_inner(&ctx.data().pool, guild_id, policy).await?;
+
+    tracing::info!(
+        user_id = %ctx.author().id,
+        guild_id = guild_id,
+        policy = ?policy,
+        "Guild removal policy updated"
+    );
+
+    let description = format!(
+        "Política de remoção atualizada!\n\n**Política:** {}",
+        politica.name()
+    );
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send removal policy command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn set_policy_inner(pool: &sqlx::PgPool, guild_id: u64, policy: RemovalPolicy) -> Result<()> {
+    let mut conn = pool.acquire().await?;
+
+    let Some(guild) = AllowedGuild::find_by_guild_id(conn.as_mut(), guild_id as i64).await? else {
+        let message = "Esse canal não é um canal de um servidor permitido".to_string();
+        return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+    };
+
+    AllowedGuild::set_removal_policy(conn.as_mut(), guild.id, policy).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD_ID: u64 = 258648784039313408;
+
+    #[sqlx::test]
+    async fn test_policy_defaults_to_kick_and_can_be_changed(pool: sqlx::PgPool) {
+        let policy = || async {
+            let mut conn = pool.acquire().await.unwrap();
+            AllowedGuild::find_by_guild_id(conn.as_mut(), GUILD_ID as i64)
+                .await
+                .unwrap()
+                .unwrap()
+                .removal_policy
+        };
+
+        assert_eq!(policy().await, RemovalPolicy::Kick);
+
+        set_policy_inner(&pool, GUILD_ID, RemovalPolicy::NotifyOnly)
+            .await
+            .unwrap();
+        assert_eq!(policy().await, RemovalPolicy::NotifyOnly);
+
+        let unknown = set_policy_inner(&pool, 42, RemovalPolicy::Kick).await;
+        assert!(matches!(unknown, Err(Error::InvalidGuild(_))));
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::validate_guild;
+use crate::database::models::{AllowedChannel, AllowedRole};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, Result};
+use crate::discord::permissions::is_admin;
+
+/// A stored entry whose name no longer matches the guild
+#[derive(Debug, PartialEq, Eq)]
+pub struct Renamed {
+    pub id: i64,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+/// How the stored entries compare to what currently exists in the guild
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub renamed: Vec<Renamed>,
+    pub missing: Vec<(i64, String)>,
+}
+
+/// Names of the guild a command was used in and of its roles and channels, copied out of the
+/// cache so they can be held across awaits
+pub struct LiveGuild {
+    pub id: u64,
+    pub name: String,
+    pub roles: HashMap<u64, String>,
+    pub channels: HashMap<u64, String>,
+}
+
+fn reconcile(stored: &[(i64, String)], live: &HashMap<u64, String>) -> Reconciliation {
+    let mut reconciliation = Reconciliation::default();
+
+    for (id, name) in stored {
+        match live.get(&(*id as u64)) {
+            Some(live_name) if live_name != name => reconciliation.renamed.push(Renamed {
+                id: *id,
+                old_name: name.clone(),
+                new_name: live_name.clone(),
+            }),
+            Some(_) => {}
+            None => reconciliation.missing.push((*id, name.clone())),
+        }
+    }
+
+    reconciliation
+}
+
+/// Update the allowed role and channel names and reload the Telegram groups
+#[poise::command(
+    slash_command,
+    rename = "sincronizar",
+    name_localized("en-US", "sync"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Atualiza os nomes dos cargos e canais permitidos e recarrega os grupos do Telegram"
+    )
+)]
+pub async fn sync(ctx: Context<'_>) -> Result<()> {
+    let LiveGuild {
+        id: guild_id,
+        roles: live_roles,
+        channels: live_channels,
+        ..
+    } = live_guild(&ctx)?;
+
+    validate_guild(&ctx.data().pool, guild_id).await?;
+
+    let (roles, channels) = sync_inner(&ctx.data().pool, &live_roles, &live_channels).await?;
+
+    let mut conn = ctx.data().pool.acquire().await?;
+    ctx.data().telegram_groups.refresh(conn.as_mut()).await?;
+    tracing::info!(
+        guild_id = guild_id,
+        roles_renamed = roles.renamed.len(),
+        roles_missing = roles.missing.len(),
+        channels_renamed = channels.renamed.len(),
+        channels_missing = channels.missing.len(),
+        "Allowed roles and channels synchronized"
+    );
+
+    let description = format!(
+        "Sincronização concluída!\n\n[CARGOS]\n{}\n\n[CANAIS]\n{}",
+        format_reconciliation(&roles),
+        format_reconciliation(&channels)
+    );
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send sync command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+pub fn live_guild(ctx: &Context<'_>) -> Result<LiveGuild> {
+    let Some(guild) = ctx.guild() else {
+        let message = "Esse comando só pode ser usado em servidores".to_string();
+        return Err(Error::InvalidGuild(InvalidGuildError::new(message)));
+    };
+
+    let roles = guild
+        .roles
+        .values()
+        .map(|role| (role.id.get(), role.name.clone()))
+        .collect();
+    let channels = guild
+        .channels
+        .values()
+        .map(|channel| (channel.id.get(), channel.name.clone()))
+        .collect();
+
+    Ok(LiveGuild {
+        id: guild.id.get(),
+        name: guild.name.clone(),
+        roles,
+        channels,
+    })
+}
+
+pub async fn sync_inner(
+    pool: &sqlx::PgPool,
+    live_roles: &HashMap<u64, String>,
+    live_channels: &HashMap<u64, String>,
+) -> Result<(Reconciliation, Reconciliation)> {
+    let mut tx = pool.begin().await?;
+
+    let stored_roles = AllowedRole::get_roles(tx.as_mut())
+        .await?
+        .into_iter()
+        .map(|role| (role.role_id, role.name))
+        .collect_vec();
+    let roles = reconcile(&stored_roles, live_roles);
+    for renamed in &roles.renamed {
+        AllowedRole::update_name(tx.as_mut(), renamed.id, &renamed.new_name).await?;
+    }
+
+    let stored_channels = AllowedChannel::get_channels(tx.as_mut())
+        .await?
+        .into_iter()
+        .map(|channel| (channel.channel_id, channel.name))
+        .collect_vec();
+    let channels = reconcile(&stored_channels, live_channels);
+    for renamed in &channels.renamed {
+        AllowedChannel::update_name(tx.as_mut(), renamed.id, &renamed.new_name).await?;
+    }
+
+    tx.commit().await?;
+    Ok((roles, channels))
+}
+
+pub fn format_reconciliation(reconciliation: &Reconciliation) -> String {
+    let mut lines = vec![format!(
+        "Nomes atualizados: {}",
+        reconciliation.renamed.len()
+    )];
+
+    lines.extend(reconciliation.renamed.iter().map(|renamed| {
+        format!(
+            "{} - {} → {}",
+            renamed.id, renamed.old_name, renamed.new_name
+        )
+    }));
+
+    if !reconciliation.missing.is_empty() {
+        lines.push("Não existem mais neste servidor (considere remover):".to_string());
+        lines.extend(
+            reconciliation
+                .missing
+                .iter()
+                .map(|(id, name)| format!("{id} - {name}")),
+        );
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(entries: &[(u64, &str)]) -> HashMap<u64, String> {
+        entries
+            .iter()
+            .map(|(id, name)| (*id, name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reconcile_detects_renamed_and_deleted() {
+        let stored = vec![
+            (1, "Subs".to_string()),
+            (2, "Mods".to_string()),
+            (3, "Antigo".to_string()),
+        ];
+        let live = live(&[(1, "Subs"), (2, "Moderadores"), (4, "Novo")]);
+
+        let reconciliation = reconcile(&stored, &live);
+
+        assert_eq!(
+            reconciliation,
+            Reconciliation {
+                renamed: vec![Renamed {
+                    id: 2,
+                    old_name: "Mods".to_string(),
+                    new_name: "Moderadores".to_string(),
+                }],
+                missing: vec![(3, "Antigo".to_string())],
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_sync_updates_stored_names(pool: sqlx::PgPool) {
+        let live_roles = live(&[(277212035652124672, "FELPS renomeado")]);
+        let live_channels = live(&[(1140461199553740872, "chat-dos-subs")]);
+
+        let (roles, channels) = sync_inner(&pool, &live_roles, &live_channels)
+            .await
+            .unwrap();
+
+        assert_eq!(roles.renamed.len(), 1);
+        assert_eq!(channels.renamed.len(), 1);
+        assert!(!roles.missing.is_empty());
+
+        let mut conn = pool.acquire().await.unwrap();
+        let stored = AllowedRole::get_roles(conn.as_mut()).await.unwrap();
+        let felps = stored
+            .iter()
+            .find(|role| role.role_id == 277212035652124672)
+            .unwrap();
+        assert_eq!(felps.name, "FELPS renomeado");
+
+        let stored = AllowedChannel::get_channels(conn.as_mut()).await.unwrap();
+        let chat = stored
+            .iter()
+            .find(|channel| channel.channel_id == 1140461199553740872)
+            .unwrap();
+        assert_eq!(chat.name, "chat-dos-subs");
+    }
+}
@@ -1,28 +1,57 @@
 use poise::CreateReply;
 use poise::serenity_prelude::{self as serenity};
 
+use crate::database::models::AllowedRole;
 use crate::discord::Context;
-use crate::discord::error::Error;
+use crate::discord::error::Result;
 use crate::discord::permissions::is_subscriber;
 
+/// Which kind of allowed role got the user in, admins are meant to get their own group once
+/// groups can be mapped per role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemberTier {
+    Admin,
+    Subscriber,
+}
+
+impl MemberTier {
+    fn description(self) -> &'static str {
+        match self {
+            MemberTier::Admin => "Você entra como administrador",
+            MemberTier::Subscriber => "Você entra como sub",
+        }
+    }
+}
+
+/// Start joining the Telegram group
 #[poise::command(
     slash_command,
     check = "is_subscriber",
     description_localized("pt-BR", "Inicia o processo de entrar no grupo do Telegram")
 )]
-pub async fn telegram(ctx: Context<'_>) -> Result<(), Error> {
+pub async fn telegram(ctx: Context<'_>) -> Result<()> {
     let user = ctx.author();
 
     tracing::info!(user_id = %user.id, username = %user.name, "Processing /telegram command");
 
+    let roles = match ctx.author_member().await {
+        Some(member) => member.roles.iter().map(|role| role.get()).collect(),
+        None => Vec::new(),
+    };
+    let tier = member_tier(&ctx.data().pool, &roles).await?;
+    tracing::debug!(user_id = %user.id, ?tier, "Resolved member tier");
+
     let author = serenity::CreateEmbedAuthor::new("felbot");
-    let footer = serenity::CreateEmbedFooter::new("a carinha '-'").icon_url("https://yt3.googleusercontent.com/c0u2JGrq6Ke9i15R66z2u3RR0fY8RHFAkrocO8cGkRu2FLhke2DH_e_zjiW17_RnBHDzQw4KlA=s160-c-k-c0x00ffffff-no-rj");
+    let config = &ctx.data().embed;
+    let footer =
+        serenity::CreateEmbedFooter::new("a carinha '-'").icon_url(&config.footer_icon_url);
     let embed = serenity::CreateEmbed::new()
-        .color((255, 62, 117))
+        .color(config.color)
         .description("Oi! eu vou te guiar no processo de entrar no grupo do telegram!")
         .field("Como funciona?", "Pra entrar no grupo do telegram você precisa vincular sua conta do discord com a conta do telegram, mas relaxa que isso é facinho", false)
         .field("E o que eu faço?", "Você precisa falar comigo lá no telegram, e eu vou te falar o que fazer por la.\n\n[Só clicar aqui](https://t.me/telefelps_bot)", false)
         .field("E depois?", "Depois que você vincular sua conta, você vai ser adicionado no grupo automaticamente, isso talvez demore alguns minutos, mas vai acontecer. Ah é, lembrando que você precisa ser sub na twitch ou membro no tutubs", false)
+        .field("Seu acesso", tier.description(), false)
         .author(author)
         .footer(footer);
 
@@ -36,3 +65,47 @@ pub async fn telegram(ctx: Context<'_>) -> Result<(), Error> {
     tracing::info!(user_id = %user.id, "Telegram command response sent successfully");
     Ok(())
 }
+
+/// Admin roles win over subscriber ones, a user with no allowed role at all can't get here
+/// since `is_subscriber` already rejected them
+async fn member_tier(pool: &sqlx::PgPool, roles: &[u64]) -> Result<MemberTier> {
+    let mut conn = pool.acquire().await?;
+    let admin_roles = AllowedRole::get_admin_ids(conn.as_mut()).await?;
+
+    if roles.iter().any(|role| admin_roles.contains(role)) {
+        return Ok(MemberTier::Admin);
+    }
+
+    let subscriber_roles = AllowedRole::get_non_admin_ids(conn.as_mut()).await?;
+    if !roles.iter().any(|role| subscriber_roles.contains(role)) {
+        tracing::warn!("Member has none of the subscriber roles, treating them as a subscriber");
+    }
+
+    Ok(MemberTier::Subscriber)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADMIN_ROLE: u64 = 258661569200652289;
+    const SUB_ROLE: u64 = 649703184033513493;
+
+    #[sqlx::test]
+    async fn test_admin_role_wins_over_subscriber_role(pool: sqlx::PgPool) {
+        let tier = member_tier(&pool, &[SUB_ROLE, ADMIN_ROLE]).await.unwrap();
+        assert_eq!(tier, MemberTier::Admin);
+
+        let tier = member_tier(&pool, &[SUB_ROLE]).await.unwrap();
+        assert_eq!(tier, MemberTier::Subscriber);
+    }
+
+    #[sqlx::test]
+    async fn test_non_admin_ids_leave_out_admin_roles(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let ids = AllowedRole::get_non_admin_ids(conn.as_mut()).await.unwrap();
+
+        assert!(ids.contains(&SUB_ROLE));
+        assert!(!ids.contains(&ADMIN_ROLE));
+    }
+}
@@ -0,0 +1,410 @@
+use super::validate_guild;
+use crate::database::models::{AllowedGuild, TelegramGroup};
+use crate::discord::Context;
+use crate::discord::commands::create_standard_reply;
+use crate::discord::error::{Error, InvalidGuildError, InvalidTelegramGroupError, Result};
+use crate::discord::permissions::is_admin;
+use crate::services::telegram::TelegramService;
+
+const MAX_DESCRIPTION_LEN: usize = 255;
+const MAX_TITLE_LEN: usize = 128;
+const MAX_INVITE_MESSAGE_LEN: usize = 1024;
+
+#[allow(clippy::result_large_err)]
+pub fn parse_group_id(id: &str) -> Result<i64> {
+    id.parse::<i64>().map_err(|_| {
+        let message = "ID do grupo inválido".to_string();
+        Error::InvalidTelegramGroup(InvalidTelegramGroupError::new(message))
+    })
+}
+
+#[allow(clippy::result_large_err)]
+pub fn guild_id(ctx: Context<'_>) -> Result<u64> {
+    match ctx.guild_id() {
+        Some(guild_id) => Ok(guild_id.get()),
+        None => {
+            let message = "Esse comando só pode ser usado em servidores".to_string();
+            Err(Error::InvalidGuild(InvalidGuildError::new(message)))
+        }
+    }
+}
+
+/// Manage the Telegram groups of the server
+#[poise::command(
+    slash_command,
+    rename = "grupos",
+    name_localized("en-US", "groups"),
+    check = "is_admin",
+    subcommands("set_description", "set_title", "set_invite_message"),
+    description_localized("pt-BR", "Gerenciar os grupos do Telegram do servidor")
+)]
+pub async fn groups(ctx: Context<'_>) -> Result<()> {
+    let message = "Por favor, use um dos subcomandos: `/grupos descricao`, `/grupos titulo` ou `/grupos convite`".into();
+    let reply = create_standard_reply(&ctx.data().embed, message);
+
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send telegram groups command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Update the description of a Telegram group
+#[poise::command(
+    slash_command,
+    rename = "descricao",
+    name_localized("en-US", "description"),
+    check = "is_admin",
+    description_localized("pt-BR", "Atualiza a descrição de um grupo do Telegram")
+)]
+async fn set_description(
+    ctx: Context<'_>,
+    #[description = "ID do grupo do Telegram"] grupo: String,
+    #[description = "Nova descrição do grupo"] descricao: String,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let group_id = parse_group_id(&grupo)?;
+    let data = ctx.data();
+
+    validate_guild(&data.pool, guild_id).await?;
+    set_description_inner(
+        &data.pool,
+        &data.telegram_service,
+        guild_id,
+        group_id,
+        &descricao,
+    )
+    .await?;
+
+    let description = format!("Descrição do grupo atualizada!\n\n**ID:** {group_id}");
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send set description command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn set_description_inner(
+    pool: &sqlx::PgPool,
+    telegram: &impl TelegramService,
+    guild_id: u64,
+    group_id: i64,
+    description: &str,
+) -> Result<()> {
+    if description.chars().count() > MAX_DESCRIPTION_LEN {
+        let message = format!("A descrição pode ter no máximo {MAX_DESCRIPTION_LEN} caracteres");
+        return Err(Error::InvalidTelegramGroup(InvalidTelegramGroupError::new(
+            message,
+        )));
+    }
+
+    let mut conn = pool.acquire().await?;
+    find_guild_group(conn.as_mut(), guild_id, group_id).await?;
+
+    telegram.set_chat_description(group_id, description).await?;
+    TelegramGroup::update_description(conn.as_mut(), group_id, description).await?;
+
+    tracing::info!(group_id = group_id, "Telegram group description updated");
+    Ok(())
+}
+
+/// Update the title of a Telegram group
+#[poise::command(
+    slash_command,
+    rename = "titulo",
+    name_localized("en-US", "title"),
+    check = "is_admin",
+    description_localized("pt-BR", "Atualiza o título de um grupo do Telegram")
+)]
+async fn set_title(
+    ctx: Context<'_>,
+    #[description = "ID do grupo do Telegram"] grupo: String,
+    #[description = "Novo título do grupo"] titulo: String,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let group_id = parse_group_id(&grupo)?;
+    let data = ctx.data();
+
+    validate_guild(&data.pool, guild_id).await?;
+    set_title_inner(
+        &data.pool,
+        &data.telegram_service,
+        guild_id,
+        group_id,
+        &titulo,
+    )
+    .await?;
+
+    let description =
+        format!("Título do grupo atualizado!\n\n**ID:** {group_id}\n**Título:** {titulo}");
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send set title command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn set_title_inner(
+    pool: &sqlx::PgPool,
+    telegram: &impl TelegramService,
+    guild_id: u64,
+    group_id: i64,
+    title: &str,
+) -> Result<()> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LEN {
+        let message = format!("O título precisa ter entre 1 e {MAX_TITLE_LEN} caracteres");
+        return Err(Error::InvalidTelegramGroup(InvalidTelegramGroupError::new(
+            message,
+        )));
+    }
+
+    let mut conn = pool.acquire().await?;
+    find_guild_group(conn.as_mut(), guild_id, group_id).await?;
+
+    telegram.set_chat_title(group_id, title).await?;
+    TelegramGroup::update_title(conn.as_mut(), group_id, title).await?;
+
+    tracing::info!(group_id = group_id, "Telegram group title updated");
+    Ok(())
+}
+
+/// Set the text users get with their invite to a Telegram group
+#[poise::command(
+    slash_command,
+    rename = "convite",
+    name_localized("en-US", "invite"),
+    check = "is_admin",
+    description_localized(
+        "pt-BR",
+        "Define a mensagem enviada junto do convite de um grupo do Telegram"
+    )
+)]
+async fn set_invite_message(
+    ctx: Context<'_>,
+    #[description = "ID do grupo do Telegram"] grupo: String,
+    #[description = "Nova mensagem do convite, deixe vazio para voltar à mensagem padrão"]
+    mensagem: Option<String>,
+) -> Result<()> {
+    let guild_id = guild_id(ctx)?;
+    let group_id = parse_group_id(&grupo)?;
+    let data = ctx.data();
+
+    validate_guild(&data.pool, guild_id).await?;
+    set_invite_message_inner(&data.pool, guild_id, group_id, mensagem.as_deref()).await?;
+
+    let description = match mensagem {
+        Some(_) => format!("Mensagem do convite atualizada!\n\n**ID:** {group_id}"),
+        None => format!("O convite voltou a usar a mensagem padrão!\n\n**ID:** {group_id}"),
+    };
+    let reply = create_standard_reply(&ctx.data().embed, description);
+    ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send set invite message command response");
+        e
+    })?;
+
+    Ok(())
+}
+
+async fn set_invite_message_inner(
+    pool: &sqlx::PgPool,
+    guild_id: u64,
+    group_id: i64,
+    invite_message: Option<&str>,
+) -> Result<()> {
+    let invite_message = invite_message
+        .map(str::trim)
+        .filter(|message| !message.is_empty());
+
+    if invite_message.is_some_and(|message| message.chars().count() > MAX_INVITE_MESSAGE_LEN) {
+        let message =
+            format!("A mensagem do convite pode ter no máximo {MAX_INVITE_MESSAGE_LEN} caracteres");
+        return Err(Error::InvalidTelegramGroup(InvalidTelegramGroupError::new(
+            message,
+        )));
+    }
+
+    let mut conn = pool.acquire().await?;
+    find_guild_group(conn.as_mut(), guild_id, group_id).await?;
+    TelegramGroup::update_invite_message(conn.as_mut(), group_id, invite_message).await?;
+
+    tracing::info!(group_id = group_id, "Telegram group invite message updated");
+    Ok(())
+}
+
+/// Only groups mapped to the guild the command was used in can be changed from it
+pub async fn find_guild_group(
+    conn: &mut sqlx::PgConnection,
+    guild_id: u64,
+    group_id: i64,
+) -> Result<TelegramGroup> {
+    let guild = AllowedGuild::find_by_guild_id(conn, guild_id as i64).await?;
+    let group = TelegramGroup::find_by_telegram_group_id(conn, group_id).await?;
+
+    match (guild, group) {
+        (Some(guild), Some(group)) if group.allowed_guild_id == guild.id => Ok(group),
+        _ => {
+            let message = "Esse grupo não é um grupo do Telegram desse servidor".to_string();
+            Err(Error::InvalidTelegramGroup(InvalidTelegramGroupError::new(
+                message,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use teloxide::requests::ResponseResult;
+    use teloxide::types::ChatMemberStatus;
+    use teloxide::{ApiError, RequestError};
+
+    use super::*;
+    use crate::services::BoxFuture;
+    use crate::test_helpers::insert_telegram_group;
+
+    const GUILD_ID: u64 = 258648784039313408;
+    const GROUP_ID: i64 = -1001234567890;
+
+    #[derive(Debug, Default)]
+    struct MockTelegramService {
+        calls: Mutex<Vec<(i64, String)>>,
+        should_fail: bool,
+    }
+
+    impl MockTelegramService {
+        fn record(&self, chat_id: i64, value: &str) -> BoxFuture<'_, ResponseResult<()>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push((chat_id, value.to_string()));
+            let should_fail = self.should_fail;
+            Box::pin(async move {
+                if should_fail {
+                    Err(RequestError::Api(ApiError::ChatNotFound))
+                } else {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    impl TelegramService for MockTelegramService {
+        fn set_chat_description(
+            &self,
+            chat_id: i64,
+            description: &str,
+        ) -> BoxFuture<'_, ResponseResult<()>> {
+            self.record(chat_id, description)
+        }
+
+        fn set_chat_title(&self, chat_id: i64, title: &str) -> BoxFuture<'_, ResponseResult<()>> {
+            self.record(chat_id, title)
+        }
+
+        fn get_chat_member_status(
+            &self,
+            _: i64,
+            _: i64,
+        ) -> BoxFuture<'_, ResponseResult<ChatMemberStatus>> {
+            Box::pin(async { Ok(ChatMemberStatus::Member) })
+        }
+    }
+
+    async fn stored_group(pool: &sqlx::PgPool) -> TelegramGroup {
+        let mut conn = pool.acquire().await.unwrap();
+        TelegramGroup::find_by_telegram_group_id(conn.as_mut(), GROUP_ID)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_set_description_updates_chat_and_database(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, GROUP_ID).await;
+        let telegram = MockTelegramService::default();
+
+        set_description_inner(&pool, &telegram, GUILD_ID, GROUP_ID, "Grupo dos subs")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *telegram.calls.lock().unwrap(),
+            vec![(GROUP_ID, "Grupo dos subs".to_string())]
+        );
+        assert_eq!(
+            stored_group(&pool).await.description.as_deref(),
+            Some("Grupo dos subs")
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_set_title_is_not_stored_when_telegram_fails(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, GROUP_ID).await;
+        let telegram = MockTelegramService {
+            should_fail: true,
+            ..Default::default()
+        };
+
+        let result = set_title_inner(&pool, &telegram, GUILD_ID, GROUP_ID, "Felps").await;
+
+        assert!(matches!(result, Err(Error::Telegram(_))));
+        assert_eq!(stored_group(&pool).await.title, None);
+    }
+
+    #[sqlx::test]
+    async fn test_unknown_group_is_rejected(pool: sqlx::PgPool) {
+        let telegram = MockTelegramService::default();
+
+        let result = set_title_inner(&pool, &telegram, GUILD_ID, GROUP_ID, "Felps").await;
+
+        assert!(matches!(result, Err(Error::InvalidTelegramGroup(_))));
+        assert!(telegram.calls.lock().unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_title_length_is_validated(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, GROUP_ID).await;
+        let telegram = MockTelegramService::default();
+
+        let empty = set_title_inner(&pool, &telegram, GUILD_ID, GROUP_ID, "  ").await;
+        let long = set_title_inner(&pool, &telegram, GUILD_ID, GROUP_ID, &"a".repeat(129)).await;
+
+        assert!(matches!(empty, Err(Error::InvalidTelegramGroup(_))));
+        assert!(matches!(long, Err(Error::InvalidTelegramGroup(_))));
+        assert!(telegram.calls.lock().unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_invite_message_can_be_set_and_cleared(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        insert_telegram_group(&mut conn, GUILD_ID as i64, GROUP_ID).await;
+
+        set_invite_message_inner(&pool, GUILD_ID, GROUP_ID, Some(" Bem-vindo! "))
+            .await
+            .unwrap();
+        assert_eq!(
+            stored_group(&pool).await.invite_message.as_deref(),
+            Some("Bem-vindo!")
+        );
+        let mut conn = pool.acquire().await.unwrap();
+        let invite_message = TelegramGroup::find_invite_message(conn.as_mut(), GROUP_ID).await;
+        assert_eq!(invite_message.unwrap().as_deref(), Some("Bem-vindo!"));
+
+        set_invite_message_inner(&pool, GUILD_ID, GROUP_ID, None)
+            .await
+            .unwrap();
+        assert_eq!(stored_group(&pool).await.invite_message, None);
+        let invite_message = TelegramGroup::find_invite_message(conn.as_mut(), GROUP_ID).await;
+        assert_eq!(invite_message.unwrap(), None);
+    }
+}
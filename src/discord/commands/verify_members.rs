@@ -1,29 +1,78 @@
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
 use crate::discord::Context;
 use crate::discord::commands::create_standard_reply;
 use crate::discord::error::Result;
 use crate::discord::permissions::is_admin;
-use crate::messages::CronAction;
+use crate::messages::{CronAction, CronOptions};
+
+// Interaction tokens expire after 15 minutes, after that the reply can no longer be edited
+const VERIFICATION_TIMEOUT: Duration = Duration::from_secs(14 * 60);
 
-#[poise::command(slash_command, rename = "checar_membros", check = "is_admin")]
+/// Check the roles of every linked member right now
+#[poise::command(
+    slash_command,
+    rename = "checar_membros",
+    name_localized("en-US", "check_members"),
+    check = "is_admin",
+    description_localized("pt-BR", "Verifica agora os cargos de todos os membros vinculados")
+)]
 pub async fn verify_members(ctx: Context<'_>) -> Result<()> {
-    match ctx.data().cron_sender.send(CronAction::Execute) {
-        Ok(_) => {
-            let message = "Verificação de membros iniciada com sucesso".to_string();
-            let reply = create_standard_reply(message);
-            ctx.send(reply).await.map_err(|e| {
-                tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send verify members command response");
-                e
-            })?;
+    let (done_sender, done_receiver) = oneshot::channel();
+    let options = CronOptions {
+        force: true,
+        ..Default::default()
+    };
+    let action = CronAction::Execute {
+        options,
+        actor_discord_id: Some(ctx.author().id.get() as i64),
+        done: Some(done_sender),
+    };
+
+    if ctx.data().cron_sender.send(action).is_err() {
+        let message = "Falha ao iniciar verificação de membros".to_string();
+        let reply = create_standard_reply(&ctx.data().embed, message);
+        ctx.send(reply).await.map_err(|e| {
+            tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send verify members command response");
+            e
+        })?;
+
+        return Ok(());
+    }
+
+    let message = "Verificação de membros iniciada com sucesso".to_string();
+    let reply = create_standard_reply(&ctx.data().embed, message);
+    let handle = ctx.send(reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send verify members command response");
+        e
+    })?;
+
+    let message = match tokio::time::timeout(VERIFICATION_TIMEOUT, done_receiver).await {
+        Ok(Ok(Ok(stats))) => format!(
+            "Verificação de membros concluída!\n\n**Verificados:** {}\n**Removidos:** {}\n**Falhas:** {}",
+            stats.users_checked, stats.users_removed, stats.users_failed
+        ),
+        Ok(Ok(Err(e))) => {
+            tracing::error!(error = %e, "Manually triggered verification failed");
+            "Falha ao executar verificação de membros".to_string()
+        }
+        Ok(Err(_)) => {
+            tracing::error!("Verification finished without reporting a result");
+            "Falha ao executar verificação de membros".to_string()
         }
         Err(_) => {
-            let message = "Falha ao iniciar verificação de membros".to_string();
-            let reply = create_standard_reply(message);
-            ctx.send(reply).await.map_err(|e| {
-                tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to send verify members command response");
-                e
-            })?;
+            tracing::warn!("Timed out waiting for manually triggered verification");
+            "A verificação ainda está em andamento, o resultado vai ficar nos logs".to_string()
         }
-    }
+    };
+
+    let reply = create_standard_reply(&ctx.data().embed, message);
+    handle.edit(ctx, reply).await.map_err(|e| {
+        tracing::error!(error = %e, user_id = %ctx.author().id, "Failed to edit verify members command response");
+        e
+    })?;
 
     Ok(())
 }
@@ -13,31 +13,145 @@ macro_rules! impl_error {
             pub fn new(message: String) -> Self {
                 Self { message }
             }
+
+            pub fn user_message(&self) -> &str {
+                &self.message
+            }
         }
     };
 }
 
-impl_error!(PermissionError);
+/// Why a user was denied access to a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionErrorKind {
+    NotOnGuild,
+    NotOnChannel,
+    NotAdmin,
+    NotSubscriber,
+    OnCooldown,
+}
+
+impl PermissionErrorKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PermissionErrorKind::NotOnGuild => "Servidor não permitido",
+            PermissionErrorKind::NotOnChannel => "Canal não permitido",
+            PermissionErrorKind::NotAdmin | PermissionErrorKind::NotSubscriber => {
+                "Permissão Negada"
+            }
+            PermissionErrorKind::OnCooldown => "Calma aí",
+        }
+    }
+
+    pub fn color(&self) -> (u8, u8, u8) {
+        match self {
+            PermissionErrorKind::NotOnGuild => (255, 0, 0),
+            PermissionErrorKind::NotOnChannel => (255, 140, 0),
+            PermissionErrorKind::NotAdmin | PermissionErrorKind::NotSubscriber => (255, 215, 0),
+            PermissionErrorKind::OnCooldown => (255, 62, 117),
+        }
+    }
+}
+
+#[derive(Debug, Display, DeriveError)]
+#[display("{message}")]
+pub struct PermissionError {
+    kind: PermissionErrorKind,
+    message: String,
+}
+
+impl PermissionError {
+    pub fn new(kind: PermissionErrorKind, message: String) -> Self {
+        Self { kind, message }
+    }
+
+    pub fn kind(&self) -> PermissionErrorKind {
+        self.kind
+    }
+
+    pub fn user_message(&self) -> &str {
+        &self.message
+    }
+}
+
 impl_error!(InvalidChannelError);
 impl_error!(InvalidGuildError);
 impl_error!(InvalidRoleError);
+impl_error!(InvalidTelegramGroupError);
+impl_error!(InvalidMessageTemplateError);
+impl_error!(InvalidDateError);
 
 #[derive(Debug, DeriveError, Display, From)]
 pub enum Error {
-    #[display("{_0}")]
+    #[display("permission denied: {_0}")]
     Permission(PermissionError),
-    #[display("{_0}")]
+    #[display("invalid channel: {_0}")]
     InvalidChannel(InvalidChannelError),
-    #[display("{_0}")]
+    #[display("invalid guild: {_0}")]
     InvalidGuild(InvalidGuildError),
-    #[display("{_0}")]
+    #[display("invalid role: {_0}")]
     InvalidRole(InvalidRoleError),
-    #[display("{_0}")]
+    #[display("invalid telegram group: {_0}")]
+    InvalidTelegramGroup(InvalidTelegramGroupError),
+    #[display("invalid message template: {_0}")]
+    InvalidMessageTemplate(InvalidMessageTemplateError),
+    #[display("invalid date: {_0}")]
+    InvalidDate(InvalidDateError),
+    #[display("discord error: {_0}")]
     #[from]
     Discord(serenity::Error),
-    #[display("{_0}")]
+    #[display("database error: {_0}")]
     #[from]
     Database(sqlx::Error),
+    #[display("telegram error: {_0}")]
+    #[from]
+    Telegram(teloxide::RequestError),
+}
+
+impl Error {
+    /// Message shown to the user in Discord, `Display` is kept for logs
+    pub fn user_message(&self) -> &str {
+        match self {
+            Error::Permission(error) => error.user_message(),
+            Error::InvalidChannel(error) => error.user_message(),
+            Error::InvalidGuild(error) => error.user_message(),
+            Error::InvalidRole(error) => error.user_message(),
+            Error::InvalidTelegramGroup(error) => error.user_message(),
+            Error::InvalidMessageTemplate(error) => error.user_message(),
+            Error::InvalidDate(error) => error.user_message(),
+            Error::Discord(_) => "Não consegui falar com o Discord, tente novamente",
+            Error::Database(_) => "Algo deu errado, tente novamente",
+            Error::Telegram(_) => "Não consegui falar com o Telegram, tente novamente",
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_kinds_are_distinguishable() {
+        let guild = PermissionErrorKind::NotOnGuild;
+        let channel = PermissionErrorKind::NotOnChannel;
+        let role = PermissionErrorKind::NotAdmin;
+
+        assert_ne!(guild.color(), channel.color());
+        assert_ne!(channel.color(), role.color());
+        assert_ne!(guild.title(), channel.title());
+        assert_eq!(role.title(), PermissionErrorKind::NotSubscriber.title());
+    }
+
+    #[test]
+    fn test_user_message_hides_internal_details() {
+        let role = Error::InvalidRole(InvalidRoleError::new("Cargo inválido".to_string()));
+        assert_eq!(role.user_message(), "Cargo inválido");
+        assert_eq!(role.to_string(), "invalid role: Cargo inválido");
+
+        let database = Error::Database(sqlx::Error::RowNotFound);
+        assert_eq!(database.user_message(), "Algo deu errado, tente novamente");
+        assert!(database.to_string().starts_with("database error: "));
+    }
+}
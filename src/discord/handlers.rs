@@ -1,6 +1,7 @@
 use poise::{CreateReply, FrameworkError, serenity_prelude as serenity};
 
-use super::{Data, Error};
+use super::error::PermissionErrorKind;
+use super::{Context, Data, Error};
 
 pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
     match error {
@@ -21,7 +22,7 @@ pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
                 .color((255, 0, 0)) // Red color for errors
                 .description(format!(
                     "Ocorreu um erro ao processar o comando:\n\n{}",
-                    error
+                    error.user_message()
                 ))
                 .author(author);
 
@@ -42,24 +43,29 @@ pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
                 "Command check failed with error"
             );
 
-            let description = match error {
-                Some(error) => error.to_string(),
-                None => "Você não tem permissão para usar esse comando.".to_string(),
+            let kind = match &error {
+                Some(Error::Permission(error)) => Some(error.kind()),
+                _ => None,
             };
+            let description = error
+                .as_ref()
+                .map(|e| e.user_message())
+                .unwrap_or("Você não tem permissão para usar esse comando.")
+                .to_string();
 
-            let author = serenity::CreateEmbedAuthor::new("Permissão Negada");
-            let footer = serenity::CreateEmbedFooter::new(format!("Comando: /{}", command_name));
-            let embed = serenity::CreateEmbed::new()
-                .color((255, 62, 117))
-                .description(description)
-                .author(author)
-                .footer(footer);
-
-            let reply = CreateReply::default().embed(embed).ephemeral(true);
+            send_permission_error(ctx, kind, description).await;
+        }
 
-            if let Err(e) = ctx.send(reply).await {
-                tracing::error!(error = %e, "Failed to send permission error message");
-            }
+        FrameworkError::CooldownHit {
+            remaining_cooldown,
+            ctx,
+            ..
+        } => {
+            let description = format!(
+                "Espera {} segundos antes de usar esse comando de novo.",
+                remaining_cooldown.as_secs().max(1)
+            );
+            send_permission_error(ctx, Some(PermissionErrorKind::OnCooldown), description).await;
         }
 
         FrameworkError::ArgumentParse { error, ctx, .. } => {
@@ -88,3 +94,27 @@ pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
         }
     }
 }
+
+async fn send_permission_error(
+    ctx: Context<'_>,
+    kind: Option<PermissionErrorKind>,
+    description: String,
+) {
+    let title = kind.map_or("Permissão Negada", |kind| kind.title());
+    let color = kind.map_or((255, 62, 117), |kind| kind.color());
+    let command_name = &ctx.command().qualified_name;
+
+    let author = serenity::CreateEmbedAuthor::new(title);
+    let footer = serenity::CreateEmbedFooter::new(format!("Comando: /{}", command_name));
+    let embed = serenity::CreateEmbed::new()
+        .color(color)
+        .description(description)
+        .author(author)
+        .footer(footer);
+
+    let reply = CreateReply::default().embed(embed).ephemeral(true);
+
+    if let Err(e) = ctx.send(reply).await {
+        tracing::error!(error = %e, "Failed to send permission error message");
+    }
+}
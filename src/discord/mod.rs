@@ -5,24 +5,71 @@ mod permissions;
 
 use std::sync::Arc;
 
-use commands::{channels, roles, telegram, verify_members};
+use commands::{
+    announce, channels, cleanup, cohort, dead_letters, export_users, groups, guilds, mappings,
+    message_templates, removal_policy, roles, sync, telegram, verify_members,
+};
 use error::{Error, Result};
 use poise::serenity_prelude::{self as serenity};
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::env::Env;
-use crate::messages::CronAction;
+use crate::messages::{CronAction, TelegramAction};
+use crate::services::telegram::TelegramServiceImpl;
+use crate::services::telegram_groups::TelegramGroupCache;
+
+const DEFAULT_FOOTER_ICON_URL: &str = "https://yt3.googleusercontent.com/c0u2JGrq6Ke9i15R66z2u3RR0fY8RHFAkrocO8cGkRu2FLhke2DH_e_zjiW17_RnBHDzQw4KlA=s160-c-k-c0x00ffffff-no-rj";
+const DEFAULT_EMBED_COLOR: (u8, u8, u8) = (255, 62, 117);
+
+/// Look of the embeds the bot replies with, so it can be deployed for other streamers
+#[derive(Debug, Clone)]
+pub struct EmbedConfig {
+    pub footer_icon_url: String,
+    pub color: (u8, u8, u8),
+}
+
+impl EmbedConfig {
+    pub fn from_env(env: &Env) -> Self {
+        Self {
+            footer_icon_url: env
+                .discord_footer_icon_url
+                .clone()
+                .unwrap_or_else(|| DEFAULT_FOOTER_ICON_URL.to_string()),
+            color: env.discord_embed_color.unwrap_or(DEFAULT_EMBED_COLOR),
+        }
+    }
+}
 
 pub struct Data {
     pool: sqlx::PgPool,
+    embed: EmbedConfig,
     cron_sender: UnboundedSender<CronAction>,
+    telegram_sender: UnboundedSender<TelegramAction>,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
 }
 pub type Context<'a> = poise::Context<'a, Data, Error>;
 
-pub async fn init(env: Arc<Env>, pool: sqlx::PgPool, cron_sender: UnboundedSender<CronAction>) {
+pub async fn init(
+    env: Arc<Env>,
+    pool: sqlx::PgPool,
+    cron_sender: UnboundedSender<CronAction>,
+    telegram_sender: UnboundedSender<TelegramAction>,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
+) {
     tracing::info!("Initializing Discord service");
 
-    let framework = create_framework(pool, cron_sender).await;
+    let embed = EmbedConfig::from_env(&env);
+    let framework = create_framework(
+        pool,
+        embed,
+        cron_sender,
+        telegram_sender,
+        telegram_groups,
+        telegram_service,
+    )
+    .await;
     let intents = serenity::GatewayIntents::non_privileged();
 
     let mut client = serenity::ClientBuilder::new(&env.discord_token, intents)
@@ -37,12 +84,48 @@ pub async fn init(env: Arc<Env>, pool: sqlx::PgPool, cron_sender: UnboundedSende
     }
 }
 
+fn commands() -> Vec<poise::Command<Data, Error>> {
+    vec![
+        telegram(),
+        alias(telegram(), "t"),
+        channels(),
+        guilds(),
+        roles(),
+        verify_members(),
+        alias(verify_members(), "cm"),
+        sync(),
+        groups(),
+        mappings(),
+        cleanup(),
+        removal_policy(),
+        export_users(),
+        announce(),
+        message_templates(),
+        dead_letters(),
+        cohort(),
+    ]
+}
+
+/// Discord has no aliases for slash commands, so the alias is registered as another command
+/// that runs the same handler
+fn alias(mut command: poise::Command<Data, Error>, name: &str) -> poise::Command<Data, Error> {
+    command.name = name.to_string();
+    command.qualified_name = name.to_string();
+    // The localized name belongs to the original command, keeping it would clash with it
+    command.name_localizations.clear();
+    command
+}
+
 async fn create_framework(
     pool: sqlx::PgPool,
+    embed: EmbedConfig,
     cron_sender: UnboundedSender<CronAction>,
+    telegram_sender: UnboundedSender<TelegramAction>,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
 ) -> poise::Framework<Data, Error> {
     let options = poise::FrameworkOptions {
-        commands: vec![telegram(), channels(), roles(), verify_members()],
+        commands: commands(),
         pre_command: |ctx| {
             Box::pin(async move {
                 tracing::debug!(
@@ -59,17 +142,32 @@ async fn create_framework(
     poise::Framework::builder()
         .options(options)
         .setup(move |ctx, ready, framework| {
-            Box::pin(setup(ctx, ready, framework, pool, cron_sender))
+            Box::pin(setup(
+                ctx,
+                ready,
+                framework,
+                pool,
+                embed,
+                cron_sender,
+                telegram_sender,
+                telegram_groups,
+                telegram_service,
+            ))
         })
         .build()
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn setup(
     ctx: &serenity::Context,
     ready: &serenity::Ready,
     framework: &poise::Framework<Data, Error>,
     pool: sqlx::PgPool,
+    embed: EmbedConfig,
     cron_sender: UnboundedSender<CronAction>,
+    telegram_sender: UnboundedSender<TelegramAction>,
+    telegram_groups: TelegramGroupCache,
+    telegram_service: TelegramServiceImpl,
 ) -> Result<Data> {
     tracing::info!(
         bot_username = %ready.user.name,
@@ -90,5 +188,55 @@ async fn setup(
         "Discord commands registered globally"
     );
 
-    Ok(Data { pool, cron_sender })
+    Ok(Data {
+        pool,
+        embed,
+        cron_sender,
+        telegram_sender,
+        telegram_groups,
+        telegram_service,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_run_the_same_handler() {
+        let commands = commands();
+        let handler = |name: &str| {
+            commands
+                .iter()
+                .find(|command| command.name == name)
+                .map(|command| command.identifying_name.clone())
+        };
+
+        assert_eq!(handler("t"), handler("telegram"));
+        assert_eq!(handler("cm"), handler("checar_membros"));
+        assert!(handler("t").is_some());
+        assert!(handler("cm").is_some());
+    }
+
+    #[test]
+    fn test_commands_have_english_descriptions() {
+        fn check(command: &poise::Command<Data, Error>) {
+            assert!(
+                command.description.is_some(),
+                "{} has no description",
+                command.qualified_name
+            );
+            command.subcommands.iter().for_each(check);
+        }
+
+        commands().iter().for_each(check);
+        assert_eq!(
+            commands()
+                .iter()
+                .find(|command| command.name == "cargos")
+                .and_then(|command| command.name_localizations.get("en-US"))
+                .map(String::as_str),
+            Some("roles")
+        );
+    }
 }
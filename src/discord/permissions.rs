@@ -1,13 +1,12 @@
 use super::Context;
-use super::error::{Error, PermissionError, Result};
-use crate::database::models::allowed_channels::AllowedChannel;
-use crate::database::models::allowed_guilds::AllowedGuild;
-use crate::database::models::allowed_roles::AllowedRole;
+use super::error::{Error, PermissionError, PermissionErrorKind, Result};
+use crate::database::models::{AllowedChannel, AllowedGuild, AllowedRole};
 
 async fn is_on_guild(ctx: Context<'_>) -> Result<bool> {
     let Some(guild_id) = ctx.guild_id() else {
         let message = "Esse comando não pode ser usado nesse servidor".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        let error = PermissionError::new(PermissionErrorKind::NotOnGuild, message);
+        return Err(Error::Permission(error));
     };
 
     let pool = &ctx.data().pool;
@@ -18,7 +17,8 @@ async fn is_on_guild(ctx: Context<'_>) -> Result<bool> {
     let user_is_on_allowed_guild = allowed_guild_ids.contains(&guild_id.get());
     if !user_is_on_allowed_guild {
         let message = "Esse comando não pode ser usado nesse servidor".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        let error = PermissionError::new(PermissionErrorKind::NotOnGuild, message);
+        return Err(Error::Permission(error));
     }
 
     Ok(true)
@@ -33,7 +33,8 @@ async fn is_on_channel(ctx: Context<'_>) -> Result<bool> {
 
     if !user_is_on_allowed_channel {
         let message = "Esse comando não pode ser usado nesse canal".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        let error = PermissionError::new(PermissionErrorKind::NotOnChannel, message);
+        return Err(Error::Permission(error));
     }
 
     Ok(true)
@@ -45,7 +46,8 @@ pub async fn is_admin(ctx: Context<'_>) -> Result<bool> {
 
     let Some(member) = ctx.author_member().await else {
         let message = "Não consegui verificar seus cargos".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        let error = PermissionError::new(PermissionErrorKind::NotAdmin, message);
+        return Err(Error::Permission(error));
     };
 
     let pool = &ctx.data().pool;
@@ -58,7 +60,13 @@ pub async fn is_admin(ctx: Context<'_>) -> Result<bool> {
         .iter()
         .any(|role_id| admin_roles.contains(&role_id.get()));
 
-    Ok(user_has_allowed_role)
+    if !user_has_allowed_role {
+        let message = "Esse comando é só para administradores".to_string();
+        let error = PermissionError::new(PermissionErrorKind::NotAdmin, message);
+        return Err(Error::Permission(error));
+    }
+
+    Ok(true)
 }
 
 pub async fn is_subscriber(ctx: Context<'_>) -> Result<bool> {
@@ -67,7 +75,8 @@ pub async fn is_subscriber(ctx: Context<'_>) -> Result<bool> {
 
     let Some(member) = ctx.author_member().await else {
         let message = "Não consegui verificar seus cargos".to_string();
-        return Err(Error::Permission(PermissionError::new(message)));
+        let error = PermissionError::new(PermissionErrorKind::NotSubscriber, message);
+        return Err(Error::Permission(error));
     };
 
     let pool = &ctx.data().pool;
@@ -80,5 +89,11 @@ pub async fn is_subscriber(ctx: Context<'_>) -> Result<bool> {
         .iter()
         .any(|role_id| allowed_role_ids.contains(&role_id.get()));
 
-    Ok(user_has_allowed_role)
+    if !user_has_allowed_role {
+        let message = "Esse comando é só para subs".to_string();
+        let error = PermissionError::new(PermissionErrorKind::NotSubscriber, message);
+        return Err(Error::Permission(error));
+    }
+
+    Ok(true)
 }
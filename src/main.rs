@@ -1,10 +1,19 @@
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
 use cron::RoleVerificationConfig;
-use env::Env;
+use database::migrations::{self, MIGRATOR};
+use env::{Env, RunMode};
+use services::admin_notifier::AdminNotifier;
+use services::telegram::TelegramServiceImpl;
+use services::telegram_groups::TelegramGroupCache;
+use teloxide::Bot;
+use tracing_subscriber::EnvFilter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use utils::retry::{RetryPolicy, retry};
+use utils::supervisor::{Shutdown, Supervisor, install_panic_hook};
 
 #[macro_use]
 mod env;
@@ -15,13 +24,26 @@ mod database;
 mod discord;
 mod error;
 mod messages;
+mod seed;
 mod services;
 mod telegram;
 mod templates;
+#[cfg(test)]
+mod test_helpers;
 mod utils;
 
+/// Log level used when `RUST_LOG` is missing or invalid
+const DEFAULT_LOG_FILTER: &str = "info";
+
 fn init_tracing() {
-    let env_filter = tracing_subscriber::EnvFilter::from("info");
+    // Read before `Env` so its own logs are filtered too, see `Env::rust_log`
+    let (env_filter, invalid_filter) = match dotenvy::var("RUST_LOG") {
+        Ok(directives) => match EnvFilter::try_new(&directives) {
+            Ok(filter) => (filter, None),
+            Err(e) => (EnvFilter::new(DEFAULT_LOG_FILTER), Some(e)),
+        },
+        Err(_) => (EnvFilter::new(DEFAULT_LOG_FILTER), None),
+    };
     let fmt_layer = tracing_subscriber::fmt::layer()
         .with_target(true)
         .with_thread_ids(true);
@@ -39,95 +61,171 @@ fn init_tracing() {
             .with(fmt_layer.pretty())
             .init();
     }
+
+    if let Some(e) = invalid_filter {
+        tracing::warn!(error = %e, "Invalid RUST_LOG, logging at {DEFAULT_LOG_FILTER} level");
+    }
+}
+
+/// Runs a single verification cycle, exiting with a non-zero code if it failed
+async fn run_oneshot(
+    env: Arc<Env>,
+    pool: sqlx::PgPool,
+    admin_notifier: AdminNotifier,
+    telegram_groups: TelegramGroupCache,
+) {
+    tracing::info!("Running a single role verification cycle");
+
+    let (telegram_sender, telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let processor = tokio::spawn(telegram::run_action_processor(
+        pool.clone(),
+        telegram_receiver,
+    ));
+
+    let config = RoleVerificationConfig::from_env(&env);
+    let result = cron::run_once(
+        env,
+        pool,
+        telegram_sender,
+        admin_notifier,
+        telegram_groups,
+        TelegramServiceImpl::new(Bot::from_env()),
+        config,
+    )
+    .await;
+
+    // The sender was moved into the cycle, so the processor stops once queued removals are sent
+    if let Err(e) = processor.await {
+        tracing::error!(error = %e, "Telegram action processor failed");
+    }
+
+    if let Err(e) = result {
+        tracing::error!(error = %e, "Role verification cycle failed");
+        std::process::exit(1);
+    }
+
+    tracing::info!("Role verification cycle completed");
 }
 
 #[tokio::main]
 async fn main() {
     init_tracing();
+    install_panic_hook();
 
-    let env = Arc::new(Env::new());
+    let env = match dotenvy::var("CONFIG_FILE") {
+        Ok(path) => Env::from_config_file(Path::new(&path)),
+        Err(_) => Env::new(),
+    }
+    .expect("Failed to load configuration");
+    let env = Arc::new(env);
     tracing::info!(port = %env.port, "Application starting");
 
-    let pool = sqlx::postgres::PgPoolOptions::new()
-        .max_connections(10)
-        .acquire_timeout(Duration::from_secs(3))
-        .connect(&env.database_url)
-        .await
-        .expect("Failed to connect to database");
+    let pool = retry("database connection", RetryPolicy::default(), || {
+        sqlx::postgres::PgPoolOptions::new()
+            .max_connections(10)
+            .acquire_timeout(Duration::from_secs(3))
+            .connect(&env.database_url)
+    })
+    .await
+    .expect("Failed to connect to database");
 
     tracing::info!("Database connection established");
 
-    sqlx::migrate!()
-        .run(&pool)
-        .await
-        .expect("Failed to run database migrations");
+    retry("database migrations", RetryPolicy::default(), || {
+        MIGRATOR.run(&pool)
+    })
+    .await
+    .expect("Failed to run database migrations");
 
     tracing::info!("Database migrations completed");
+
+    let schema_check = match pool.acquire().await {
+        Ok(mut conn) => migrations::check_schema_version(conn.as_mut()).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = schema_check {
+        tracing::warn!(error = %e, "Failed to verify database schema version");
+    }
+
+    if let Some(path) = &env.seed_config_path {
+        seed::seed_from_file(&pool, Path::new(path))
+            .await
+            .expect("Failed to seed database from config file");
+    }
+
+    let telegram_groups = match pool.acquire().await {
+        Ok(mut conn) => TelegramGroupCache::load(conn.as_mut()).await,
+        Err(e) => Err(e),
+    }
+    .expect("Failed to load telegram groups");
+
+    let admin_notifier = AdminNotifier::new(Bot::from_env(), env.admin_telegram_chat_id);
+
+    if env.run_mode == RunMode::Oneshot {
+        run_oneshot(env, pool, admin_notifier, telegram_groups).await;
+        return;
+    }
+
     tracing::info!("Starting application services");
 
     let (telegram_sender, telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
     let (cron_sender, cron_receiver) = tokio::sync::mpsc::unbounded_channel();
 
-    let mut telegram_handle = tokio::spawn(telegram::init(env.clone(), telegram_receiver));
-    let mut discord_handle = tokio::spawn(discord::init(
-        env.clone(),
-        pool.clone(),
-        cron_sender.clone(),
-    ));
-
-    let mut cron_handle = tokio::spawn(cron::init(
-        env.clone(),
-        pool.clone(),
-        cron_receiver,
-        telegram_sender.clone(),
-        RoleVerificationConfig::default(),
-    ));
-
-    let mut api_handle = tokio::spawn(api::init(
-        env.clone(),
-        pool.clone(),
-        telegram_sender.clone(),
-        cron_sender,
-    ));
+    let mut supervisor = Supervisor::default();
+
+    supervisor.spawn(
+        "Telegram",
+        telegram::init(env.clone(), pool.clone(), telegram_receiver),
+    );
+    supervisor.spawn(
+        "Discord",
+        discord::init(
+            env.clone(),
+            pool.clone(),
+            cron_sender.clone(),
+            telegram_sender.clone(),
+            telegram_groups.clone(),
+            TelegramServiceImpl::new(Bot::from_env()),
+        ),
+    );
+    supervisor.spawn(
+        "Cron",
+        cron::init(
+            env.clone(),
+            pool.clone(),
+            cron_receiver,
+            telegram_sender.clone(),
+            admin_notifier.clone(),
+            telegram_groups,
+            TelegramServiceImpl::new(Bot::from_env()),
+            RoleVerificationConfig::from_env(&env),
+        ),
+    );
+    supervisor.spawn(
+        "API",
+        api::init(env.clone(), pool.clone(), telegram_sender, cron_sender),
+    );
 
     tracing::info!("All services started successfully");
 
-    tokio::select! {
-        result = &mut discord_handle => {
-            tracing::error!(?result, "Discord service exited unexpectedly");
-            tracing::info!("Shutting down other services");
-            telegram_handle.abort();
-            api_handle.abort();
-            cron_handle.abort();
-        }
-        result = &mut telegram_handle => {
-            tracing::error!(?result, "Telegram service exited unexpectedly");
-            tracing::info!("Shutting down other services");
-            api_handle.abort();
-            discord_handle.abort();
-            cron_handle.abort();
+    let shutdown = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for the shutdown signal");
+            std::future::pending::<()>().await;
         }
-        result = &mut api_handle => {
-            tracing::error!(?result, "API service exited unexpectedly");
-            tracing::info!("Shutting down other services");
-            discord_handle.abort();
-            telegram_handle.abort();
-            cron_handle.abort();
-        }
-        result = &mut cron_handle => {
-            tracing::error!(?result, "Cron service exited unexpectedly");
-            tracing::info!("Shutting down other services");
-            discord_handle.abort();
-            telegram_handle.abort();
-            api_handle.abort();
+    };
+
+    match supervisor.run(shutdown).await {
+        Shutdown::ServiceExited(service) => {
+            let message = format!("{service} service exited unexpectedly");
+            admin_notifier.notify_error(&message).await;
         }
-        _ = tokio::signal::ctrl_c() => {
-            tracing::info!("Received shutdown signal, gracefully shutting down");
-            discord_handle.abort();
-            api_handle.abort();
-            telegram_handle.abort();
-            cron_handle.abort();
+        Shutdown::ServicePanicked { service, message } => {
+            let message = format!("{service} service panicked: {message}");
+            admin_notifier.notify_error(&message).await;
         }
+        Shutdown::Requested => {}
     }
 
     tracing::info!("Application shutdown complete");
@@ -1,10 +1,90 @@
-#[derive(Debug, Clone)]
+use tokio::sync::oneshot;
+
+use crate::cron::VerificationStats;
+use crate::error::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TelegramAction {
-    InviteUser { telegram_id: i64 },
-    RemoveUser { telegram_id: i64 },
+    InviteUser {
+        telegram_id: i64,
+        group_id: i64,
+        /// Custom invite text configured for the group
+        invite_message: Option<String>,
+    },
+    RemoveUser {
+        telegram_id: i64,
+        group_id: i64,
+    },
+    /// Makes the user read only in the group, or lifts that when `read_only` is false
+    RestrictUser {
+        telegram_id: i64,
+        group_id: i64,
+        read_only: bool,
+    },
+    /// Posts an admin announcement to the group, `html` was already validated
+    Broadcast {
+        group_id: i64,
+        html: String,
+    },
+}
+
+impl TelegramAction {
+    /// Actions with the same key run one after the other: user actions are keyed by the user
+    /// and broadcasts by their group, which can't clash since group ids are negative
+    pub fn ordering_key(&self) -> i64 {
+        match self {
+            TelegramAction::InviteUser { telegram_id, .. } => *telegram_id,
+            TelegramAction::RemoveUser { telegram_id, .. } => *telegram_id,
+            TelegramAction::RestrictUser { telegram_id, .. } => *telegram_id,
+            TelegramAction::Broadcast { group_id, .. } => *group_id,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TelegramAction::InviteUser { .. } => "invite",
+            TelegramAction::RemoveUser { .. } => "remove",
+            TelegramAction::RestrictUser { .. } => "restrict",
+            TelegramAction::Broadcast { .. } => "broadcast",
+        }
+    }
+
+    /// User the action is for, `None` for broadcasts
+    pub fn telegram_id(&self) -> Option<i64> {
+        match self {
+            TelegramAction::InviteUser { telegram_id, .. }
+            | TelegramAction::RemoveUser { telegram_id, .. }
+            | TelegramAction::RestrictUser { telegram_id, .. } => Some(*telegram_id),
+            TelegramAction::Broadcast { .. } => None,
+        }
+    }
+
+    pub fn group_id(&self) -> i64 {
+        match self {
+            TelegramAction::InviteUser { group_id, .. }
+            | TelegramAction::RemoveUser { group_id, .. }
+            | TelegramAction::RestrictUser { group_id, .. }
+            | TelegramAction::Broadcast { group_id, .. } => *group_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CronOptions {
+    /// Verify even if the guild was verified within the cooldown
+    pub force: bool,
+    /// Report what would change without removing anyone
+    pub dry_run: bool,
+    /// Discord id of the guild to verify instead of the default one
+    pub guild_id: Option<i64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum CronAction {
-    Execute,
+    Execute {
+        options: CronOptions,
+        /// Discord id of the admin that asked for the run, recorded in the audit log
+        actor_discord_id: Option<i64>,
+        done: Option<oneshot::Sender<Result<VerificationStats>>>,
+    },
 }
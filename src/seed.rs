@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use derive_more::{Display, Error, From};
+use serde::Deserialize;
+use sqlx::{PgConnection, PgPool};
+
+use crate::database::models::{
+    AllowedChannel, AllowedChannelPayload, AllowedGuild, AllowedRole, AllowedRolePayload,
+    TelegramGroup, TelegramGroupPayload,
+};
+
+#[derive(Debug, Display, Error, From)]
+pub enum SeedError {
+    #[display("failed to read seed config: {_0}")]
+    Io(std::io::Error),
+    #[display("invalid seed config: {_0}")]
+    Parse(toml::de::Error),
+    #[display("database error while seeding: {_0}")]
+    Database(sqlx::Error),
+    #[display("telegram group references unknown guild {guild_id}")]
+    #[from(ignore)]
+    UnknownGuild { guild_id: i64 },
+}
+
+/// Allowed guilds, roles, channels and telegram groups a deployment should start with
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedConfig {
+    #[serde(default)]
+    pub guilds: Vec<SeedGuild>,
+    #[serde(default)]
+    pub roles: Vec<SeedRole>,
+    #[serde(default)]
+    pub channels: Vec<SeedChannel>,
+    #[serde(default)]
+    pub telegram_groups: Vec<SeedTelegramGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedGuild {
+    pub guild_id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedRole {
+    pub role_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub is_admin: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedChannel {
+    pub channel_id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedTelegramGroup {
+    /// Discord id of the guild this group belongs to, must also be listed in `guilds`
+    pub guild_id: i64,
+    pub telegram_group_id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SeedSummary {
+    pub created: u32,
+    pub updated: u32,
+}
+
+impl SeedSummary {
+    fn record(&mut self, existed: bool) {
+        if existed {
+            self.updated += 1;
+        } else {
+            self.created += 1;
+        }
+    }
+}
+
+pub async fn seed_from_file(pool: &PgPool, path: &Path) -> Result<SeedSummary, SeedError> {
+    tracing::info!(path = %path.display(), "Seeding database from config file");
+
+    let contents = std::fs::read_to_string(path)?;
+    let config = toml::from_str::<SeedConfig>(&contents)?;
+
+    let mut tx = pool.begin().await?;
+    let summary = apply(tx.as_mut(), &config).await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        created = summary.created,
+        updated = summary.updated,
+        "Database seeding completed"
+    );
+
+    Ok(summary)
+}
+
+async fn apply(conn: &mut PgConnection, config: &SeedConfig) -> Result<SeedSummary, SeedError> {
+    let mut summary = SeedSummary::default();
+
+    for guild in &config.guilds {
+        let existing = AllowedGuild::find_by_guild_id(conn, guild.guild_id).await?;
+        let existed = existing.is_some();
+
+        if let Some(existing) = existing {
+            AllowedGuild::update_name(conn, existing.id, &guild.name).await?;
+        } else {
+            AllowedGuild::create(conn, guild.guild_id, &guild.name).await?;
+        }
+
+        summary.record(existed);
+        log_seeded("guild", guild.guild_id, existed);
+    }
+
+    for role in &config.roles {
+        let existed = AllowedRole::exists(conn, role.role_id).await?;
+        let payload = AllowedRolePayload::new(role.role_id, role.name.clone(), role.is_admin);
+        AllowedRole::create(conn, payload).await?;
+
+        summary.record(existed);
+        log_seeded("role", role.role_id, existed);
+    }
+
+    for channel in &config.channels {
+        let existed = AllowedChannel::exists(conn, channel.channel_id).await?;
+        let payload = AllowedChannelPayload::new(channel.channel_id, channel.name.clone());
+        AllowedChannel::create(conn, payload).await?;
+
+        summary.record(existed);
+        log_seeded("channel", channel.channel_id, existed);
+    }
+
+    for group in &config.telegram_groups {
+        let Some(guild) = AllowedGuild::find_by_guild_id(conn, group.guild_id).await? else {
+            return Err(SeedError::UnknownGuild {
+                guild_id: group.guild_id,
+            });
+        };
+
+        let existed = TelegramGroup::is_telegram_group(conn, group.telegram_group_id).await?;
+        let payload =
+            TelegramGroupPayload::new(guild.id, group.telegram_group_id, group.name.clone());
+        TelegramGroup::create(conn, payload).await?;
+
+        summary.record(existed);
+        log_seeded("telegram group", group.telegram_group_id, existed);
+    }
+
+    Ok(summary)
+}
+
+fn log_seeded(kind: &str, id: i64, existed: bool) {
+    if existed {
+        tracing::info!(kind = kind, id = id, "Seeded entry updated");
+    } else {
+        tracing::info!(kind = kind, id = id, "Seeded entry created");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [[guilds]]
+        guild_id = 258648784039313408
+        name = "Server do Felpinho"
+
+        [[guilds]]
+        guild_id = 42
+        name = "Server Novo"
+
+        [[roles]]
+        role_id = 277212035652124672
+        name = "FELPS"
+        is_admin = true
+
+        [[roles]]
+        role_id = 43
+        name = "Subs Novos"
+
+        [[channels]]
+        channel_id = 44
+        name = "Chat novo"
+
+        [[telegram_groups]]
+        guild_id = 42
+        telegram_group_id = -1001234567890
+        name = "Grupo Novo"
+    "#;
+
+    async fn count(conn: &mut PgConnection, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let result = toml::from_str::<SeedConfig>("[[roles]]\nrole_id = 1\nnome = \"x\"\n");
+        assert!(result.is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_seeding_twice_is_idempotent(pool: PgPool) {
+        let config = toml::from_str::<SeedConfig>(CONFIG).unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        let first = apply(&mut conn, &config).await.unwrap();
+        let tables = [
+            "allowed_guilds",
+            "allowed_roles",
+            "allowed_channels",
+            "telegram_groups",
+        ];
+        let mut counts = Vec::new();
+        for table in tables {
+            counts.push(count(&mut conn, table).await);
+        }
+
+        let second = apply(&mut conn, &config).await.unwrap();
+        for (table, expected) in tables.into_iter().zip(counts) {
+            assert_eq!(count(&mut conn, table).await, expected, "{table}");
+        }
+
+        assert_eq!(
+            first,
+            SeedSummary {
+                created: 4,
+                updated: 2
+            }
+        );
+        assert_eq!(
+            second,
+            SeedSummary {
+                created: 0,
+                updated: 6
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_group_for_unknown_guild_fails(pool: PgPool) {
+        let config = toml::from_str::<SeedConfig>(
+            "[[telegram_groups]]\nguild_id = 1\ntelegram_group_id = -1\nname = \"x\"\n",
+        )
+        .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        let result = apply(&mut conn, &config).await;
+
+        assert!(matches!(
+            result,
+            Err(SeedError::UnknownGuild { guild_id: 1 })
+        ));
+    }
+}
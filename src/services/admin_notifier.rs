@@ -0,0 +1,55 @@
+use teloxide::prelude::*;
+
+#[derive(Debug, Clone)]
+pub struct AdminNotifier {
+    bot: Bot,
+    /// Notifications are skipped when no admin chat is configured
+    admin_chat_id: Option<i64>,
+}
+
+impl AdminNotifier {
+    pub fn new(bot: Bot, admin_chat_id: Option<i64>) -> Self {
+        Self { bot, admin_chat_id }
+    }
+
+    pub async fn notify_error(&self, message: &str) {
+        self.send(format!("🚨 Erro crítico no felbot\n\n{message}"))
+            .await;
+    }
+
+    pub async fn notify_cron_failure(&self, cycle: u64, error: &str) {
+        self.send(format!(
+            "🚨 Ciclo de verificação #{cycle} falhou\n\n{error}"
+        ))
+        .await;
+    }
+
+    pub async fn notify_duplicate_links(&self, cycle: u64, users: u32) {
+        self.send(format!(
+            "⚠️ Ciclo de verificação #{cycle} ignorou {users} usuários com vínculos duplicados\n\nConfira a tabela user_links"
+        ))
+        .await;
+    }
+
+    pub async fn notify_flagged_users(&self, cycle: u64, users: u32) {
+        self.send(format!(
+            "⚠️ Ciclo de verificação #{cycle} encontrou {users} usuários sem os cargos necessários\n\nO servidor está configurado para apenas notificar, nenhum usuário foi removido"
+        ))
+        .await;
+    }
+
+    async fn send(&self, message: String) {
+        let Some(admin_chat_id) = self.admin_chat_id else {
+            tracing::debug!("No admin chat configured, skipping admin notification");
+            return;
+        };
+
+        if let Err(e) = self.bot.send_message(ChatId(admin_chat_id), message).await {
+            tracing::error!(
+                error = %e,
+                admin_chat_id = admin_chat_id,
+                "Failed to send admin notification"
+            );
+        }
+    }
+}
@@ -1,16 +1,51 @@
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, TimeDelta, Utc};
 use reqwest::Client;
 use serde::Deserialize;
 
+use super::BoxFuture;
 use crate::api::error::{ApiError, Result};
 use crate::env::Env;
-use crate::utils::BoxFuture;
+use crate::utils::retry::{RetryPolicy, retry_if};
+
+const DISCORD_API_URL: &str = "https://discord.com/api";
+/// Scope that lets the roles of a user be read with their own token, for guilds the bot can't
+/// be in. Opted into by adding it to `DISCORD_OAUTH_SCOPE`
+pub const MEMBER_ROLES_SCOPE: &str = "guilds.members.read";
+const TOKEN_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The user is waiting on the callback page, so only a couple of quick retries are worth it
+const TOKEN_EXCHANGE_RETRY: RetryPolicy = RetryPolicy {
+    max_elapsed: Duration::from_secs(5),
+    initial_delay: Duration::from_millis(250),
+    max_delay: Duration::from_secs(1),
+    max_attempts: 3,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct DiscordTokenResponse {
     pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Seconds until `access_token` expires
+    pub expires_in: Option<i64>,
+    /// Space separated scopes the user granted
+    #[serde(default)]
+    pub scope: String,
+}
+
+impl DiscordTokenResponse {
+    pub fn grants_member_roles(&self) -> bool {
+        self.scope
+            .split_whitespace()
+            .any(|scope| scope == MEMBER_ROLES_SCOPE)
+    }
+
+    pub fn expires_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.expires_in
+            .map(|expires_in| now + TimeDelta::seconds(expires_in))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -19,27 +54,153 @@ pub struct DiscordUser {
     pub username: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct DiscordGuildMember {
+    roles: Vec<String>,
+}
+
+impl DiscordGuildMember {
+    fn role_ids(&self) -> Result<Vec<i64>> {
+        self.roles
+            .iter()
+            .map(|role| role.parse())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| ApiError::discord_api("Invalid discord role id".into()))
+    }
+}
+
 pub trait DiscordService: Debug + Send + Sync {
     fn get_access_token(
         &self,
         env: Arc<Env>,
         code: String,
-    ) -> BoxFuture<Result<DiscordTokenResponse>>;
-    fn get_user_info(&self, token: String) -> BoxFuture<Result<DiscordUser>>;
+    ) -> BoxFuture<'_, Result<DiscordTokenResponse>>;
+    fn get_user_info(&self, token: String) -> BoxFuture<'_, Result<DiscordUser>>;
+    /// Roles of the user in the guild, `None` when they aren't a member. Asked through the bot
+    /// since OAuth only grants `identify`
+    fn get_member_roles(
+        &self,
+        env: Arc<Env>,
+        guild_id: i64,
+        user_id: i64,
+    ) -> BoxFuture<'_, Result<Option<Vec<i64>>>>;
+    /// Roles of the user in the guild read with their own token, which needs the
+    /// `guilds.members.read` scope. `None` when they aren't a member
+    fn get_user_guild_member(
+        &self,
+        token: String,
+        guild_id: i64,
+    ) -> BoxFuture<'_, Result<Option<Vec<i64>>>>;
+    fn refresh_access_token(
+        &self,
+        env: Arc<Env>,
+        refresh_token: String,
+    ) -> BoxFuture<'_, Result<DiscordTokenResponse>>;
     fn get_oauth_url(&self, env: &Env, state: &str) -> String;
 }
 
 #[derive(Debug, Clone)]
 pub struct DiscordServiceImpl {
     client: Client,
+    api_url: String,
 }
 
 impl DiscordServiceImpl {
     pub fn new() -> Self {
         Self {
             client: Client::new(),
+            api_url: DISCORD_API_URL.to_string(),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_api_url(api_url: String) -> Self {
+        Self {
+            client: Client::new(),
+            api_url,
         }
     }
+
+    /// Asks for a token with `grant` along with the client credentials, used both to exchange
+    /// an authorization code and to refresh a token
+    async fn request_token(
+        &self,
+        env: &Env,
+        grant: &[(&str, &str)],
+    ) -> Result<DiscordTokenResponse> {
+        let credentials = [
+            ("client_id", env.discord_client_id.as_str()),
+            ("client_secret", env.discord_client_secret.as_str()),
+        ];
+        let form_data = credentials.iter().chain(grant).collect::<Vec<_>>();
+
+        let response = self
+            .client
+            .post(format!("{}/oauth2/token", self.api_url))
+            .timeout(TOKEN_EXCHANGE_TIMEOUT)
+            .form(&form_data)
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to send token exchange request");
+                ApiError::Http(e)
+            })?;
+
+        // Server errors are kept as http errors so they can be told apart from a bad code
+        let response = response.error_for_status().map_err(|e| {
+            tracing::error!(error = %e, "Discord token exchange failed");
+            if is_server_error(&e) {
+                ApiError::Http(e)
+            } else {
+                ApiError::discord_api(format!("Token exchange failed: {e}"))
+            }
+        })?;
+
+        response.json().await.map_err(|e| {
+            tracing::error!(error = %e, "Failed to parse token response");
+            ApiError::discord_api(e.to_string())
+        })
+    }
+}
+
+/// Sends a guild member request, a 404 means the user isn't in the guild
+async fn fetch_member_roles(request: reqwest::RequestBuilder) -> Result<Option<Vec<i64>>> {
+    let response = request.send().await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to send guild member request");
+        ApiError::Http(e)
+    })?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let response = response.error_for_status().map_err(|e| {
+        let message = format!("Guild member request failed: {e}");
+        tracing::error!(error = %e, "Discord guild member request failed");
+        ApiError::discord_api(message)
+    })?;
+
+    let member: DiscordGuildMember = response.json().await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to parse guild member response");
+        ApiError::discord_api(e.to_string())
+    })?;
+
+    member.role_ids().map(Some)
+}
+
+fn is_server_error(error: &reqwest::Error) -> bool {
+    error
+        .status()
+        .is_some_and(|status| status.is_server_error())
+}
+
+/// Timeouts, connection failures and 5xx responses may go away on their own, anything else
+/// such as an invalid or already used code won't
+fn is_transient(error: &ApiError) -> bool {
+    match error {
+        ApiError::Http(e) => e.is_timeout() || e.is_connect() || is_server_error(e),
+        _ => false,
+    }
 }
 
 impl DiscordService for DiscordServiceImpl {
@@ -47,48 +208,33 @@ impl DiscordService for DiscordServiceImpl {
         &self,
         env: Arc<Env>,
         code: String,
-    ) -> BoxFuture<Result<DiscordTokenResponse>> {
+    ) -> BoxFuture<'_, Result<DiscordTokenResponse>> {
         Box::pin(async move {
             tracing::debug!("Exchanging authorization code for access token");
 
-            let form_data = [
-                ("client_id", env.discord_client_id.as_str()),
-                ("client_secret", env.discord_client_secret.as_str()),
+            let grant = [
                 ("grant_type", "authorization_code"),
-                ("code", &code),
+                ("code", code.as_str()),
                 ("redirect_uri", env.discord_oauth_redirect.as_str()),
             ];
 
-            let response = self
-                .client
-                .post("https://discord.com/api/oauth2/token")
-                .form(&form_data)
-                .send()
-                .await
-                .map_err(|e| {
-                    tracing::error!(error = %e, "Failed to send token exchange request");
-                    ApiError::Http(e)
-                })?;
-
-            let response = response.error_for_status().map_err(|e| {
-                tracing::error!(error = %e, "Discord token exchange failed");
-                ApiError::discord_api(format!("Token exchange failed: {e}"))
-            })?;
-
-            response.json().await.map_err(|e| {
-                tracing::error!(error = %e, "Failed to parse token response");
-                ApiError::discord_api(e.to_string())
-            })
+            retry_if(
+                "discord token exchange",
+                TOKEN_EXCHANGE_RETRY,
+                is_transient,
+                || self.request_token(&env, &grant),
+            )
+            .await
         })
     }
 
-    fn get_user_info(&self, token: String) -> BoxFuture<Result<DiscordUser>> {
+    fn get_user_info(&self, token: String) -> BoxFuture<'_, Result<DiscordUser>> {
         Box::pin(async move {
             tracing::debug!("Fetching Discord user information");
 
             let response = self
                 .client
-                .get("https://discord.com/api/users/@me")
+                .get(format!("{}/users/@me", self.api_url))
                 .bearer_auth(token)
                 .send()
                 .await
@@ -110,12 +256,188 @@ impl DiscordService for DiscordServiceImpl {
         })
     }
 
+    fn get_member_roles(
+        &self,
+        env: Arc<Env>,
+        guild_id: i64,
+        user_id: i64,
+    ) -> BoxFuture<'_, Result<Option<Vec<i64>>>> {
+        Box::pin(async move {
+            tracing::debug!(guild_id, user_id, "Fetching Discord guild member roles");
+
+            let request = self
+                .client
+                .get(format!(
+                    "{}/guilds/{guild_id}/members/{user_id}",
+                    self.api_url
+                ))
+                .header(
+                    reqwest::header::AUTHORIZATION,
+                    format!("Bot {}", env.discord_token),
+                );
+
+            fetch_member_roles(request).await
+        })
+    }
+
+    fn get_user_guild_member(
+        &self,
+        token: String,
+        guild_id: i64,
+    ) -> BoxFuture<'_, Result<Option<Vec<i64>>>> {
+        Box::pin(async move {
+            tracing::debug!(
+                guild_id,
+                "Fetching Discord guild member roles with user token"
+            );
+
+            let request = self
+                .client
+                .get(format!(
+                    "{}/users/@me/guilds/{guild_id}/member",
+                    self.api_url
+                ))
+                .bearer_auth(token);
+
+            fetch_member_roles(request).await
+        })
+    }
+
+    fn refresh_access_token(
+        &self,
+        env: Arc<Env>,
+        refresh_token: String,
+    ) -> BoxFuture<'_, Result<DiscordTokenResponse>> {
+        Box::pin(async move {
+            tracing::debug!("Refreshing Discord access token");
+
+            let grant = [
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+            ];
+
+            retry_if(
+                "discord token refresh",
+                TOKEN_EXCHANGE_RETRY,
+                is_transient,
+                || self.request_token(&env, &grant),
+            )
+            .await
+        })
+    }
+
     fn get_oauth_url(&self, env: &Env, token: &str) -> String {
         format!(
-            "https://discord.com/api/oauth2/authorize?client_id={}&redirect_uri={}&response_type=code&scope=identify&state={}",
+            "{}/oauth2/authorize?client_id={}&redirect_uri={}&response_type=code&scope={}&state={}",
+            self.api_url,
             env.discord_client_id,
             urlencoding::encode(&env.discord_oauth_redirect),
+            urlencoding::encode(&env.discord_oauth_scope),
             token
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+
+    use super::*;
+
+    /// Serves the token endpoint answering with `statuses` in order, returning its url and
+    /// how many requests it got
+    async fn token_server(statuses: Vec<StatusCode>) -> (String, Arc<Mutex<usize>>) {
+        let requests = Arc::new(Mutex::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/oauth2/token",
+            post(move || {
+                let mut requests = counter.lock().unwrap();
+                let status = statuses[*requests];
+                *requests += 1;
+                async move { (status, r#"{"access_token": "token"}"#) }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (url, requests)
+    }
+
+    #[test]
+    fn test_member_roles_are_parsed() {
+        let member: DiscordGuildMember =
+            serde_json::from_str(r#"{"roles": ["649703184033513493"], "nick": null}"#).unwrap();
+        assert_eq!(member.role_ids().unwrap(), vec![649703184033513493]);
+
+        let member: DiscordGuildMember =
+            serde_json::from_str(r#"{"roles": ["not a role"]}"#).unwrap();
+        assert!(member.role_ids().is_err());
+    }
+
+    #[test]
+    fn test_member_roles_scope_must_be_granted() {
+        let token = |scope: &str| DiscordTokenResponse {
+            access_token: "token".to_string(),
+            refresh_token: None,
+            expires_in: None,
+            scope: scope.to_string(),
+        };
+
+        assert!(token("identify guilds.members.read").grants_member_roles());
+        assert!(!token("identify").grants_member_roles());
+        assert!(!token("").grants_member_roles());
+    }
+
+    #[tokio::test]
+    async fn test_token_exchange_retries_server_errors() {
+        let (url, requests) =
+            token_server(vec![StatusCode::INTERNAL_SERVER_ERROR, StatusCode::OK]).await;
+        let service = DiscordServiceImpl::with_api_url(url);
+
+        let token = service
+            .get_access_token(Arc::new(Env::empty()), "code".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(token.access_token, "token");
+        assert_eq!(*requests.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_token_exchange_does_not_retry_client_errors() {
+        let (url, requests) = token_server(vec![StatusCode::BAD_REQUEST, StatusCode::OK]).await;
+        let service = DiscordServiceImpl::with_api_url(url);
+
+        let result = service
+            .get_access_token(Arc::new(Env::empty()), "code".to_string())
+            .await;
+
+        assert!(matches!(result, Err(ApiError::DiscordApi { .. })));
+        assert_eq!(*requests.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_oauth_url_uses_configured_scope() {
+        let mut env = Env::empty();
+        env.discord_client_id = "42".to_string();
+        env.discord_oauth_redirect = "https://felbot.example.com/oauth/callback".to_string();
+        let service = DiscordServiceImpl::new();
+
+        let url = service.get_oauth_url(&env, "token");
+        assert_eq!(
+            url,
+            "https://discord.com/api/oauth2/authorize?client_id=42&redirect_uri=https%3A%2F%2Ffelbot.example.com%2Foauth%2Fcallback&response_type=code&scope=identify&state=token"
+        );
+
+        env.discord_oauth_scope = "identify guilds".to_string();
+        let url = service.get_oauth_url(&env, "token");
+        assert!(url.contains("&scope=identify%20guilds&"));
+    }
+}
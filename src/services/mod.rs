@@ -1 +1,8 @@
+pub mod admin_notifier;
 pub mod discord;
+pub mod telegram;
+pub mod telegram_groups;
+
+use std::pin::Pin;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
@@ -0,0 +1,97 @@
+use std::fmt::Debug;
+
+use teloxide::prelude::*;
+use teloxide::types::ChatMemberStatus;
+
+use super::BoxFuture;
+
+pub trait TelegramService: Debug + Send + Sync {
+    fn set_chat_description(
+        &self,
+        chat_id: i64,
+        description: &str,
+    ) -> BoxFuture<'_, ResponseResult<()>>;
+    fn set_chat_title(&self, chat_id: i64, title: &str) -> BoxFuture<'_, ResponseResult<()>>;
+    fn get_chat_member_status(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> BoxFuture<'_, ResponseResult<ChatMemberStatus>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct TelegramServiceImpl {
+    bot: Bot,
+}
+
+impl TelegramServiceImpl {
+    pub fn new(bot: Bot) -> Self {
+        Self { bot }
+    }
+}
+
+impl TelegramService for TelegramServiceImpl {
+    fn set_chat_description(
+        &self,
+        chat_id: i64,
+        description: &str,
+    ) -> BoxFuture<'_, ResponseResult<()>> {
+        let description = description.to_string();
+        Box::pin(async move {
+            tracing::debug!(chat_id = chat_id, "Setting telegram chat description");
+
+            self.bot
+                .set_chat_description(ChatId(chat_id))
+                .description(description)
+                .await
+                .map_err(|e| {
+                    tracing::error!(error = %e, chat_id = chat_id, "Failed to set chat description");
+                    e
+                })?;
+
+            Ok(())
+        })
+    }
+
+    fn set_chat_title(&self, chat_id: i64, title: &str) -> BoxFuture<'_, ResponseResult<()>> {
+        let title = title.to_string();
+        Box::pin(async move {
+            tracing::debug!(chat_id = chat_id, "Setting telegram chat title");
+
+            self.bot
+                .set_chat_title(ChatId(chat_id), title)
+                .await
+                .map_err(|e| {
+                    tracing::error!(error = %e, chat_id = chat_id, "Failed to set chat title");
+                    e
+                })?;
+
+            Ok(())
+        })
+    }
+
+    fn get_chat_member_status(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> BoxFuture<'_, ResponseResult<ChatMemberStatus>> {
+        Box::pin(async move {
+            tracing::debug!(
+                chat_id = chat_id,
+                user_id = user_id,
+                "Fetching telegram chat member"
+            );
+
+            let member = self
+                .bot
+                .get_chat_member(ChatId(chat_id), UserId(user_id as u64))
+                .await
+                .map_err(|e| {
+                    tracing::error!(error = %e, chat_id = chat_id, user_id = user_id, "Failed to fetch chat member");
+                    e
+                })?;
+
+            Ok(member.kind.status())
+        })
+    }
+}
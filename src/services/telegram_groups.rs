@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sqlx::PgConnection;
+use sqlx::types::Uuid;
+use tokio::sync::RwLock;
+
+use crate::database::models::TelegramGroup;
+
+/// In memory mapping of allowed guilds to the telegram chat their users belong to.
+///
+/// Loaded once at startup and refreshed on demand; guilds missing from the cache are looked up
+/// in the database and remembered, so groups created after startup still resolve.
+#[derive(Debug, Clone, Default)]
+pub struct TelegramGroupCache {
+    groups: Arc<RwLock<HashMap<Uuid, i64>>>,
+}
+
+impl TelegramGroupCache {
+    pub async fn load(conn: &mut PgConnection) -> sqlx::Result<Self> {
+        let cache = Self::default();
+        cache.refresh(conn).await?;
+        Ok(cache)
+    }
+
+    /// Replaces the cached mapping with the current contents of `telegram_groups`
+    pub async fn refresh(&self, conn: &mut PgConnection) -> sqlx::Result<usize> {
+        let mut groups = HashMap::new();
+        for group in TelegramGroup::get_all(conn).await? {
+            // Groups come oldest first, matching the group `TelegramGroup::find_by_guild` picks
+            groups
+                .entry(group.allowed_guild_id)
+                .or_insert(group.telegram_group_id);
+        }
+
+        let count = groups.len();
+        *self.groups.write().await = groups;
+
+        tracing::info!(groups = count, "Telegram group cache refreshed");
+        Ok(count)
+    }
+
+    /// Telegram chat id mapped to the allowed guild, if any
+    pub async fn resolve(
+        &self,
+        conn: &mut PgConnection,
+        allowed_guild_id: Uuid,
+    ) -> sqlx::Result<Option<i64>> {
+        if let Some(chat_id) = self.groups.read().await.get(&allowed_guild_id) {
+            return Ok(Some(*chat_id));
+        }
+
+        tracing::debug!(%allowed_guild_id, "Telegram group cache miss, querying database");
+
+        let Some(group) = TelegramGroup::find_by_guild(conn, allowed_guild_id).await? else {
+            return Ok(None);
+        };
+
+        self.groups
+            .write()
+            .await
+            .insert(allowed_guild_id, group.telegram_group_id);
+
+        Ok(Some(group.telegram_group_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_helpers::{default_guild_id, insert_telegram_group};
+
+    const GUILD_ID: i64 = 258648784039313408;
+
+    #[sqlx::test]
+    async fn test_load_populates_cache(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        insert_telegram_group(&mut conn, GUILD_ID, -100).await;
+
+        let cache = TelegramGroupCache::load(&mut conn).await.unwrap();
+        sqlx::query("DELETE FROM telegram_groups")
+            .execute(conn.as_mut())
+            .await
+            .unwrap();
+
+        let chat_id = cache.resolve(&mut conn, guild_id).await.unwrap();
+        assert_eq!(chat_id, Some(-100));
+
+        cache.refresh(&mut conn).await.unwrap();
+        assert_eq!(cache.resolve(&mut conn, guild_id).await.unwrap(), None);
+    }
+
+    #[sqlx::test]
+    async fn test_miss_falls_back_to_database(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+
+        let cache = TelegramGroupCache::load(&mut conn).await.unwrap();
+        assert_eq!(cache.resolve(&mut conn, guild_id).await.unwrap(), None);
+
+        insert_telegram_group(&mut conn, GUILD_ID, -200).await;
+        assert_eq!(
+            cache.resolve(&mut conn, guild_id).await.unwrap(),
+            Some(-200)
+        );
+        assert_eq!(cache.groups.read().await.get(&guild_id), Some(&-200));
+    }
+}
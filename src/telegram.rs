@@ -1,52 +1,324 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures::StreamExt;
+use sqlx::{PgConnection, PgPool};
+use teloxide::dispatching::UpdateHandler;
 use teloxide::prelude::*;
-use teloxide::types::User;
+use teloxide::types::{ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, User};
 use teloxide::utils::command::BotCommands;
+use teloxide::{ApiError, RequestError};
+use tokio::sync::Mutex;
 use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::Instrument;
 
+use crate::database::models::{
+    MessageTemplate, RoleGroupMapping, TelegramDeadLetter, TelegramGroup, UserLink,
+};
 use crate::env::Env;
 use crate::messages::TelegramAction;
+use crate::utils::retry::{RetryPolicy, retry_if};
 
-pub async fn init(env: Arc<Env>, receiver: UnboundedReceiver<TelegramAction>) {
+const MAX_CONCURRENT_ACTIONS: usize = 5;
+/// Transient failures are retried a few times before the action goes to the dead letters
+const ACTION_RETRY: RetryPolicy = RetryPolicy {
+    max_elapsed: Duration::from_secs(30),
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(10),
+    max_attempts: 3,
+};
+/// Longest text Telegram accepts in a single message
+pub const MAX_MESSAGE_LEN: usize = 4096;
+/// Tags Telegram understands in messages sent with the HTML parse mode
+const SUPPORTED_HTML_TAGS: &[&str] = &[
+    "a",
+    "b",
+    "blockquote",
+    "code",
+    "del",
+    "em",
+    "i",
+    "ins",
+    "pre",
+    "s",
+    "span",
+    "strike",
+    "strong",
+    "tg-spoiler",
+    "u",
+];
+/// Shown by Telegram clients in the empty chat before the user presses start
+const BOT_DESCRIPTION: &str =
+    "Vincule sua conta do Discord para receber o convite do grupo dos subs";
+
+pub async fn init(env: Arc<Env>, pool: PgPool, receiver: UnboundedReceiver<TelegramAction>) {
     tracing::info!("Initializing Telegram service");
 
     let bot = Bot::from_env();
+    register_commands(&bot).await;
 
     let new_bot = bot.clone();
-    let new_env = env.clone();
+    let action_pool = pool.clone();
 
     tokio::spawn(async move {
         tracing::info!("Starting Telegram action processor");
-        process_telegram_actions(new_env, new_bot, receiver).await;
+        process_telegram_actions(new_bot, action_pool, receiver).await;
         tracing::warn!("Telegram action processor stopped");
     });
 
-    tracing::info!("Starting Telegram command handler");
-    Command::repl(bot, move |bot, msg, cmd| {
-        let env = env.clone();
-        async move { answer(env, bot, msg, cmd).await }
-    })
-    .await;
+    tracing::info!("Starting Telegram update handler");
+    Dispatcher::builder(bot, schema())
+        .dependencies(dptree::deps![env, pool])
+        .default_handler(|_| async {})
+        .build()
+        .dispatch()
+        .await;
+}
+
+/// Lets Telegram clients suggest the commands, the bot works without it so failures are only
+/// logged
+async fn register_commands(bot: &Bot) {
+    if let Err(e) = bot.set_my_commands(Command::bot_commands()).await {
+        tracing::warn!(error = %e, "Failed to register Telegram bot commands");
+    }
+
+    if let Err(e) = bot.set_my_description().description(BOT_DESCRIPTION).await {
+        tracing::warn!(error = %e, "Failed to set Telegram bot description");
+    }
+}
+
+/// Processes queued actions without handling updates, returning once every sender is dropped
+pub async fn run_action_processor(pool: PgPool, receiver: UnboundedReceiver<TelegramAction>) {
+    process_telegram_actions(Bot::from_env(), pool, receiver).await;
+}
+
+fn schema() -> UpdateHandler<RequestError> {
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter_command::<Command>()
+                .endpoint(handle_command),
+        )
+        .branch(
+            Update::filter_message()
+                .filter_map(|msg: Message| msg.new_chat_members().map(<[User]>::to_vec))
+                .endpoint(handle_new_members),
+        )
+        .branch(Update::filter_callback_query().endpoint(handle_callback))
+}
+
+async fn handle_command(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    env: Arc<Env>,
+    pool: PgPool,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    if let Err(e) = answer(env, pool, bot.clone(), msg, cmd).await {
+        tracing::error!(error = %e, chat_id = chat_id.0, "Failed to answer Telegram command");
+        send_error_message(&bot, chat_id, &e).await;
+    }
+
+    Ok(())
+}
+
+async fn handle_new_members(
+    msg: Message,
+    members: Vec<User>,
+    env: Arc<Env>,
+    pool: PgPool,
+) -> ResponseResult<()> {
+    record_group_joins(&env, &pool, msg.chat.id, &members).await;
+    Ok(())
+}
+
+/// Tells the links whose invite was accepted apart from the pending ones, a failure here only
+/// leaves the link pending until the cron sees the user in the group
+async fn record_group_joins(env: &Env, pool: &PgPool, chat_id: ChatId, members: &[User]) {
+    if !is_group_chat(env, pool, chat_id).await {
+        return;
+    }
+
+    let mut conn = match pool.acquire().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to acquire connection to record group joins");
+            return;
+        }
+    };
+
+    for member in members.iter().filter(|member| !member.is_bot) {
+        let telegram_id = member.id.0 as i64;
+        match UserLink::mark_joined_group(conn.as_mut(), telegram_id).await {
+            Ok(true) => tracing::info!(telegram_id, "Linked user joined the telegram group"),
+            Ok(false) => {}
+            Err(e) => tracing::error!(error = %e, telegram_id, "Failed to record group join"),
+        }
+    }
+}
+
+/// Callback data of the button users press to get their invite again after linking
+const SEND_INVITE_CALLBACK: &str = "send_invite";
+
+fn start_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        "📨 Já vinculei, me manda o convite",
+        SEND_INVITE_CALLBACK,
+    )]])
+}
+
+#[tracing::instrument(skip_all, fields(user_id = query.from.id.0, data = query.data.as_deref()))]
+async fn handle_callback(
+    bot: Bot,
+    query: CallbackQuery,
+    env: Arc<Env>,
+    pool: PgPool,
+) -> ResponseResult<()> {
+    bot.answer_callback_query(query.id.clone()).await?;
+
+    if query.data.as_deref() != Some(SEND_INVITE_CALLBACK) {
+        tracing::debug!("Ignoring unknown callback query");
+        return Ok(());
+    }
+
+    let user_id = query.from.id;
+    let chat_id = ChatId::from(user_id);
+
+    let user_link = match pool.acquire().await {
+        Ok(mut conn) => UserLink::find_by_telegram_id(conn.as_mut(), user_id.0 as i64).await,
+        Err(e) => Err(e),
+    };
+
+    let result = match user_link {
+        Ok(Some(user_link)) => {
+            let group_id = match pool.acquire().await {
+                Ok(mut conn) => invite_group_for(&env, conn.as_mut(), &user_link).await,
+                Err(e) => Err(e),
+            };
+
+            match group_id {
+                Ok(Some(group_id)) => send_group_invite(&bot, &pool, user_id, group_id).await,
+                Ok(None) => {
+                    tracing::warn!("No telegram group is configured for the user's guild");
+                    let message = "Seu servidor ainda não tem um grupo do Telegram, avisa um admin";
+                    bot.send_message(chat_id, message).await.map(|_| ())
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to resolve telegram group for callback");
+                    let message = "Algo deu errado, tente novamente";
+                    bot.send_message(chat_id, message).await.map(|_| ())
+                }
+            }
+        }
+        Ok(None) => {
+            let message = "Vc ainda não vinculou sua conta, clica no link ali em cima primeiro";
+            bot.send_message(chat_id, message).await.map(|_| ())
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to fetch user link for callback");
+            let message = "Algo deu errado, tente novamente";
+            bot.send_message(chat_id, message).await.map(|_| ())
+        }
+    };
+
+    if let Err(e) = result {
+        tracing::error!(error = %e, "Failed to answer callback query");
+        send_error_message(&bot, chat_id, &e).await;
+    }
+
+    Ok(())
+}
+
+/// Telegram group a linked user is invited to again: the one they were first invited to. Links
+/// made before that group was kept are resolved like the OAuth callback does, the group mapped to
+/// one of their last known roles or else their guild's first group. Deployments without any row
+/// in `telegram_groups` use the group configured in the environment
+async fn invite_group_for(
+    env: &Env,
+    conn: &mut PgConnection,
+    user: &UserLink,
+) -> sqlx::Result<Option<i64>> {
+    if let Some(group_id) = user.telegram_group_id {
+        return Ok(Some(group_id));
+    }
+
+    let Some(group) = TelegramGroup::find_by_guild(conn, user.guild_id).await? else {
+        let has_groups = !TelegramGroup::get_all(conn).await?.is_empty();
+        return Ok((!has_groups).then_some(env.telegram_group_id));
+    };
+
+    let roles = user.discord_roles.as_deref().unwrap_or_default();
+    let mappings = RoleGroupMapping::get_all_for_guild(conn, user.guild_id).await?;
+    let group_id = mappings
+        .iter()
+        .find(|mapping| roles.contains(&mapping.discord_role_id))
+        .map_or(group.telegram_group_id, |mapping| mapping.telegram_group_id);
+
+    Ok(Some(group_id))
+}
+
+async fn send_group_invite(
+    bot: &Bot,
+    pool: &PgPool,
+    user_id: UserId,
+    group_id: i64,
+) -> ResponseResult<()> {
+    let invite_message = match pool.acquire().await {
+        Ok(mut conn) => TelegramGroup::find_invite_message(conn.as_mut(), group_id).await,
+        Err(e) => Err(e),
+    }
+    .unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Failed to fetch group invite message, using default");
+        None
+    });
+
+    send_invite_to_user(bot, pool, user_id, group_id, invite_message.as_deref()).await
+}
+
+fn error_message(error: &RequestError) -> &'static str {
+    match error {
+        RequestError::RetryAfter(_) => {
+            "Muita gente falando comigo agora, tenta de novo daqui a pouquinho"
+        }
+        RequestError::Network(_) => "Tive um problema de conexão, tenta de novo daqui a pouco",
+        _ => "Algo deu errado, tente novamente",
+    }
+}
+
+async fn send_error_message(bot: &Bot, chat_id: ChatId, error: &RequestError) {
+    if let Err(e) = bot.send_message(chat_id, error_message(error)).await {
+        tracing::error!(error = %e, chat_id = chat_id.0, "Failed to send error message to user");
+    }
 }
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
 enum Command {
+    #[command(description = "Mostra como vincular sua conta do Discord")]
     Start,
+    #[command(description = "Mostra se sua conta está vinculada")]
+    Status,
 }
 
-#[tracing::instrument(skip(env, bot, cmd), fields(
+#[tracing::instrument(skip(env, pool, bot, cmd), fields(
     chat_id = msg.chat.id.0,
     user_id = msg.from.as_ref().map(|u| u.id.0),
     username = msg.from.as_ref().and_then(|u| u.username.as_deref())
 ))]
-async fn answer(env: Arc<Env>, bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
+async fn answer(
+    env: Arc<Env>,
+    pool: PgPool,
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+) -> ResponseResult<()> {
     tracing::info!("Processing Telegram command");
 
     match cmd {
         Command::Start => {
-            if msg.chat.id.0 == env.telegram_group_id {
+            if is_group_chat(&env, &pool, msg.chat.id).await {
                 tracing::debug!("Ignoring /start command in group chat");
                 return Ok(());
             }
@@ -62,9 +334,9 @@ async fn answer(env: Arc<Env>, bot: Bot, msg: Message, cmd: Command) -> Response
                 "Sending welcome message to user"
             );
 
-            let welcome_message = make_help_message(&env, user);
-            bot.send_message(msg.chat.id, welcome_message)
-                .parse_mode(teloxide::types::ParseMode::Html)
+            let template = find_template(&pool, MessageTemplate::WELCOME, None).await;
+            let welcome_message = make_help_message(&env, user, template.as_deref());
+            send_html_or_plain(&bot, msg.chat.id, &welcome_message, Some(start_keyboard()))
                 .await
                 .map_err(|e| {
                     tracing::error!(error = %e, "Failed to send welcome message");
@@ -73,32 +345,311 @@ async fn answer(env: Arc<Env>, bot: Bot, msg: Message, cmd: Command) -> Response
 
             tracing::info!("Welcome message sent successfully");
         }
+        Command::Status => {
+            if is_group_chat(&env, &pool, msg.chat.id).await {
+                tracing::debug!("Ignoring /status command in group chat");
+                return Ok(());
+            }
+
+            let Some(user) = msg.from else {
+                tracing::error!("Message has no user information");
+                return Ok(());
+            };
+
+            let user_link = match pool.acquire().await {
+                Ok(mut conn) => {
+                    UserLink::find_by_telegram_id(conn.as_mut(), user.id.0 as i64).await
+                }
+                Err(e) => Err(e),
+            };
+
+            let status_message = match user_link {
+                Ok(Some(user_link)) => make_status_message(Some(&user_link), None),
+                Ok(None) => {
+                    let template = find_template(&pool, MessageTemplate::UNLINKED, None).await;
+                    make_status_message(None, template.as_deref())
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to fetch user link for status");
+                    "Algo deu errado, tente novamente".to_string()
+                }
+            };
+
+            send_html_or_plain(&bot, msg.chat.id, &status_message, None)
+                .await
+                .map_err(|e| {
+                    tracing::error!(error = %e, "Failed to send status message");
+                    e
+                })?;
+
+            tracing::info!("Status message sent successfully");
+        }
     };
 
     Ok(())
 }
 
-fn make_help_message(env: &Env, user: User) -> String {
+async fn is_group_chat(env: &Env, pool: &PgPool, chat_id: ChatId) -> bool {
+    if chat_id.0 == env.telegram_group_id {
+        return true;
+    }
+
+    let result = match pool.acquire().await {
+        Ok(mut conn) => TelegramGroup::is_telegram_group(conn.as_mut(), chat_id.0).await,
+        Err(e) => Err(e),
+    };
+
+    result.unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Failed to check whether chat is a telegram group");
+        false
+    })
+}
+
+/// Sends an HTML message, falling back to plain text when Telegram can't parse the markup so
+/// the user still gets the message
+async fn send_html_or_plain(
+    bot: &Bot,
+    chat_id: ChatId,
+    html: &str,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> ResponseResult<()> {
+    let mut request = bot
+        .send_message(chat_id, html)
+        .parse_mode(teloxide::types::ParseMode::Html);
+    if let Some(keyboard) = keyboard.clone() {
+        request = request.reply_markup(keyboard);
+    }
+
+    match request.await {
+        Ok(_) => Ok(()),
+        Err(RequestError::Api(ApiError::CantParseEntities(reason))) => {
+            tracing::warn!(reason = %reason, "Telegram rejected HTML message, sending as plain text");
+
+            let mut request = bot.send_message(chat_id, strip_html(html));
+            if let Some(keyboard) = keyboard {
+                request = request.reply_markup(keyboard);
+            }
+
+            request.await.map(|_| ())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Turns the HTML we send into readable plain text, links keep their url after the text
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut href = None;
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let tag = &rest[start + 1..start + end];
+        if let Some(attributes) = tag.strip_prefix("a ") {
+            href = attributes
+                .split_once("href=\"")
+                .and_then(|(_, value)| value.split_once('"'))
+                .map(|(url, _)| url.to_string());
+        } else if tag == "/a" {
+            text.extend(href.take().map(|url| format!(" ({url})")));
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Why a message would be rejected by Telegram's HTML parse mode
+#[derive(Debug, PartialEq, Eq)]
+pub enum HtmlError {
+    UnsupportedTag(String),
+    UnclosedTag(String),
+    UnexpectedClosingTag(String),
+    /// A `<` without a matching `>`, which should have been written as `&lt;`
+    UnescapedBracket,
+}
+
+/// Checks `html` only uses tags Telegram supports and closes them in order, so a message is
+/// refused up front instead of failing once it is sent
+pub fn validate_html(html: &str) -> std::result::Result<(), HtmlError> {
+    let mut open_tags = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            return Err(HtmlError::UnescapedBracket);
+        };
+
+        let tag = &rest[start + 1..start + end];
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if !SUPPORTED_HTML_TAGS.contains(&name.as_str()) {
+            return Err(HtmlError::UnsupportedTag(name));
+        }
+
+        if !closing {
+            open_tags.push(name);
+        } else if open_tags.last() == Some(&name) {
+            open_tags.pop();
+        } else {
+            return Err(HtmlError::UnexpectedClosingTag(name));
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+
+    match open_tags.pop() {
+        Some(tag) => Err(HtmlError::UnclosedTag(tag)),
+        None => Ok(()),
+    }
+}
+
+/// Escapes text interpolated into `ParseMode::Html` messages, Telegram rejects malformed HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Content of the template saved under `name`, `None` when the default text should be used.
+///
+/// Templates of the guild owning `group_id` take precedence over the global ones, lookup
+/// failures are only logged so users still get the default message
+async fn find_template(pool: &PgPool, name: &str, group_id: Option<i64>) -> Option<String> {
+    let result = match pool.acquire().await {
+        Ok(mut conn) => find_group_template(conn.as_mut(), name, group_id).await,
+        Err(e) => Err(e),
+    };
+
+    result.unwrap_or_else(|e| {
+        tracing::error!(error = %e, template = name, "Failed to fetch message template, using default");
+        None
+    })
+}
+
+async fn find_group_template(
+    conn: &mut PgConnection,
+    name: &str,
+    group_id: Option<i64>,
+) -> sqlx::Result<Option<String>> {
+    let guild_id = match group_id {
+        Some(group_id) => TelegramGroup::find_by_telegram_group_id(conn, group_id)
+            .await?
+            .map(|group| group.allowed_guild_id),
+        None => None,
+    };
+
+    let template = MessageTemplate::find_by_name(conn, name, guild_id).await?;
+    Ok(template.map(|template| template.content))
+}
+
+fn make_help_message(env: &Env, user: User, template: Option<&str>) -> String {
     let link_base_url = &env.account_link_url;
-    let username = user.username.unwrap_or(user.first_name);
+    let username = escape_html(&user.username.unwrap_or(user.first_name));
     let user_id = user.id.0;
     let link_url = format!("{link_base_url}?telegram_id={user_id}");
 
+    let heading = match template {
+        Some(template) => template.replace("{usuario}", &username),
+        None => [
+            &format!("<b>Opa @{username}, vc já tá quase no grupo '-'</b>"),
+            "",
+            "Só precisa vincular sua conta do telegram com sua conta do discord. Só clicar no link aqui em baixo e fazer login com o discord",
+        ].join("\n"),
+    };
+
     [
-        &format!("<b>Opa @{username}, vc já tá quase no grupo '-'</b>"),
-        "",
-        "Só precisa vincular sua conta do telegram com sua conta do discord. Só clicar no link aqui em baixo e fazer login com o discord",
+        heading.as_str(),
         "",
         &format!("<a href=\"{link_url}\">🔗 Linkar minha conta!</a>"),
-    ].join("\n")
+    ]
+    .join("\n")
+}
+
+fn make_status_message(user_link: Option<&UserLink>, unlinked_template: Option<&str>) -> String {
+    let Some(user_link) = user_link else {
+        if let Some(template) = unlinked_template {
+            return template.to_string();
+        }
+
+        return [
+            "<b>Sua conta ainda não tá vinculada</b>",
+            "",
+            "Manda /start que eu te passo o link pra vincular com o discord",
+        ]
+        .join("\n");
+    };
+
+    let group_status = match user_link.added_to_group_at {
+        Some(added_at) => format!("✅ Adicionado ao grupo em {}", added_at.format("%d/%m/%Y")),
+        None => "⏳ Ainda não foi adicionado ao grupo".to_string(),
+    };
+
+    [
+        "<b>Sua conta tá vinculada '-'</b>",
+        "",
+        &format!("🔗 Discord: <code>{}</code>", user_link.discord_id),
+        &group_status,
+    ]
+    .join("\n")
 }
 
-#[tracing::instrument(skip(bot, env), fields(user_id = user_id.0))]
-async fn send_invite_to_user(env: &Env, bot: &Bot, user_id: UserId) -> ResponseResult<()> {
+/// Invite text users get, the group's custom text wins over the `convite` template, which wins
+/// over the default text
+fn make_invite_message(link: &str, invite_message: Option<&str>, template: Option<&str>) -> String {
+    let heading = match (invite_message, template) {
+        (Some(invite_message), _) => escape_html(invite_message),
+        (None, Some(template)) => template.to_string(),
+        (None, None) => "<b>Oi! aqui tá seu link de convite</b>".to_string(),
+    };
+
+    [
+        heading.as_str(),
+        "",
+        &format!("<a href=\"{link}\">Clique aqui pra entrar no grupo</a>"),
+    ]
+    .join("\n")
+}
+
+#[tracing::instrument(skip(bot, pool, invite_message), fields(user_id = user_id.0))]
+async fn send_invite_to_user(
+    bot: &Bot,
+    pool: &PgPool,
+    user_id: UserId,
+    group_id: i64,
+    invite_message: Option<&str>,
+) -> ResponseResult<()> {
     tracing::info!("Creating invite link for user");
 
     let invite = bot
-        .create_chat_invite_link(env.telegram_group_id.to_string())
+        .create_chat_invite_link(ChatId(group_id))
         .await
         .map_err(|e| {
             tracing::error!(error = %e, "Failed to create chat invite link");
@@ -108,25 +659,19 @@ async fn send_invite_to_user(env: &Env, bot: &Bot, user_id: UserId) -> ResponseR
     let link = invite.invite_link;
     tracing::debug!(invite_link = %link, "Invite link created");
 
-    let invite_message = [
-        "<b>Oi! aqui tá seu link de convite</b>",
-        "",
-        &format!("<a href=\"{link}\">Clique aqui pra entrar no grupo</a>"),
-    ]
-    .join("\n");
-
-    bot.send_message(user_id, invite_message)
-        .parse_mode(teloxide::types::ParseMode::Html)
-        .await?;
+    let template = match invite_message {
+        Some(_) => None,
+        None => find_template(pool, MessageTemplate::INVITE, Some(group_id)).await,
+    };
+    let invite_message = make_invite_message(&link, invite_message, template.as_deref());
+    send_html_or_plain(bot, ChatId::from(user_id), &invite_message, None).await?;
 
     tracing::info!("Invite message sent successfully");
     Ok(())
 }
 
-#[tracing::instrument(skip(bot, env), fields(user_id = user_id.0, group_id = env.telegram_group_id))]
-async fn kick_user(env: &Env, bot: &Bot, user_id: UserId) -> ResponseResult<()> {
-    let group_id = ChatId(env.telegram_group_id);
-
+#[tracing::instrument(skip(bot), fields(user_id = user_id.0, group_id = group_id.0))]
+async fn kick_user(bot: &Bot, user_id: UserId, group_id: ChatId) -> ResponseResult<()> {
     tracing::info!("Removing user from Telegram group");
 
     bot.ban_chat_member(group_id, user_id).await.map_err(|e| {
@@ -147,64 +692,610 @@ async fn kick_user(env: &Env, bot: &Bot, user_id: UserId) -> ResponseResult<()>
     Ok(())
 }
 
+/// Permissions a restricted user gets, read only users can't send anything to the group.
+///
+/// Telegram lifts a restriction when every permission is granted, the group's own defaults
+/// still apply on top of that.
+fn member_permissions(read_only: bool) -> ChatPermissions {
+    if read_only {
+        ChatPermissions::empty()
+    } else {
+        ChatPermissions::all()
+    }
+}
+
+#[tracing::instrument(skip(bot), fields(user_id = user_id.0, group_id = group_id.0))]
+async fn restrict_user(
+    bot: &Bot,
+    user_id: UserId,
+    group_id: ChatId,
+    read_only: bool,
+) -> ResponseResult<()> {
+    tracing::info!("Updating user permissions in Telegram group");
+
+    bot.restrict_chat_member(group_id, user_id, member_permissions(read_only))
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to restrict user in group");
+            e
+        })?;
+
+    tracing::info!("User permissions successfully updated");
+    Ok(())
+}
+
 async fn process_telegram_actions(
-    env: Arc<Env>,
     bot: Bot,
-    mut receiver: UnboundedReceiver<TelegramAction>,
+    pool: PgPool,
+    receiver: UnboundedReceiver<TelegramAction>,
 ) {
+    let action_count = process_actions(receiver, MAX_CONCURRENT_ACTIONS, |action| {
+        let bot = bot.clone();
+        let pool = pool.clone();
+        async move { handle_telegram_action(&bot, &pool, action).await }
+    })
+    .await;
+
+    tracing::warn!(
+        total_actions_processed = action_count,
+        "Telegram action processor shutting down"
+    );
+}
+
+/// Runs up to `concurrency` actions at once while keeping actions for the same telegram user
+/// strictly sequential, returning how many actions were processed once the channel closes.
+async fn process_actions<F, Fut>(
+    receiver: UnboundedReceiver<TelegramAction>,
+    concurrency: usize,
+    handler: F,
+) -> u64
+where
+    F: Fn(TelegramAction) -> Fut,
+    Fut: Future<Output = ()>,
+{
     let mut action_count = 0u64;
+    let mut user_locks: HashMap<i64, Arc<Mutex<()>>> = HashMap::new();
 
-    while let Some(action) = receiver.recv().await {
-        action_count += 1;
+    let actions = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|action| (action, receiver))
+    });
 
-        let span = tracing::info_span!(
-            "telegram_action",
-            action_type = match &action {
-                TelegramAction::InviteUser { .. } => "invite",
-                TelegramAction::RemoveUser { .. } => "remove",
-            },
-            action_count = action_count
-        );
-        let _guard = span.enter();
-
-        match action {
-            TelegramAction::InviteUser { telegram_id } => {
-                tracing::info!(telegram_id = telegram_id, "Processing invite user action");
-
-                if let Err(e) = send_invite_to_user(&env, &bot, UserId(telegram_id as u64)).await {
-                    tracing::error!(
-                        error = %e,
-                        telegram_id = telegram_id,
-                        "Failed to send invite to user"
-                    );
-                } else {
-                    tracing::info!(
-                        telegram_id = telegram_id,
-                        "Invite action completed successfully"
-                    );
-                }
-            }
-            TelegramAction::RemoveUser { telegram_id } => {
-                tracing::info!(telegram_id = telegram_id, "Processing remove user action");
-
-                if let Err(e) = kick_user(&env, &bot, UserId(telegram_id as u64)).await {
-                    tracing::error!(
-                        error = %e,
-                        telegram_id = telegram_id,
-                        "Failed to remove user"
-                    );
-                } else {
-                    tracing::info!(
-                        telegram_id = telegram_id,
-                        "Remove action completed successfully"
-                    );
-                }
+    actions
+        .map(|action| {
+            action_count += 1;
+
+            // Locks nobody is holding or waiting on belong to users with no pending actions
+            user_locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            let user_lock = user_locks.entry(action.ordering_key()).or_default().clone();
+
+            let span = tracing::info_span!(
+                "telegram_action",
+                action_type = action.kind(),
+                action_count = action_count
+            );
+
+            let fut = handler(action);
+
+            // tokio's mutex is fair and buffer_unordered polls new futures in arrival order, so
+            // actions for the same user acquire the lock in the order they were received
+            async move {
+                let _guard = user_lock.lock().await;
+                fut.await;
             }
+            .instrument(span)
+        })
+        .buffer_unordered(concurrency)
+        .for_each(|_| futures::future::ready(()))
+        .await;
+
+    action_count
+}
+
+async fn handle_telegram_action(bot: &Bot, pool: &PgPool, action: TelegramAction) {
+    tracing::info!(
+        telegram_id = action.telegram_id(),
+        group_id = action.group_id(),
+        "Processing Telegram action"
+    );
+
+    let result = retry_if("telegram action", ACTION_RETRY, is_transient, || {
+        run_telegram_action(bot, pool, &action)
+    })
+    .await;
+
+    match result {
+        Ok(()) => tracing::info!("Telegram action completed successfully"),
+        Err(e) => {
+            tracing::error!(error = %e, "Telegram action failed, recording dead letter");
+            record_dead_letter(pool, &action, &e).await;
         }
     }
+}
 
-    tracing::warn!(
-        total_actions_processed = action_count,
-        "Telegram action processor shutting down"
-    );
+async fn run_telegram_action(
+    bot: &Bot,
+    pool: &PgPool,
+    action: &TelegramAction,
+) -> ResponseResult<()> {
+    match action {
+        TelegramAction::InviteUser {
+            telegram_id,
+            group_id,
+            invite_message,
+        } => {
+            let user_id = UserId(*telegram_id as u64);
+            send_invite_to_user(bot, pool, user_id, *group_id, invite_message.as_deref()).await
+        }
+        TelegramAction::RemoveUser {
+            telegram_id,
+            group_id,
+        } => kick_user(bot, UserId(*telegram_id as u64), ChatId(*group_id)).await,
+        TelegramAction::RestrictUser {
+            telegram_id,
+            group_id,
+            read_only,
+        } => {
+            let user_id = UserId(*telegram_id as u64);
+            restrict_user(bot, user_id, ChatId(*group_id), *read_only).await
+        }
+        TelegramAction::Broadcast { group_id, html } => {
+            send_html_or_plain(bot, ChatId(*group_id), html, None).await
+        }
+    }
+}
+
+/// Failures that may go away on their own, anything else would fail the same way again
+fn is_transient(error: &RequestError) -> bool {
+    matches!(
+        error,
+        RequestError::RetryAfter(_) | RequestError::Network(_) | RequestError::Io(_)
+    )
+}
+
+/// Keeps the failed action around for `/dlq`, a failure here can only be logged
+async fn record_dead_letter(pool: &PgPool, action: &TelegramAction, error: &RequestError) {
+    let result = match pool.acquire().await {
+        Ok(mut conn) => {
+            TelegramDeadLetter::insert(
+                conn.as_mut(),
+                action.kind(),
+                action.telegram_id(),
+                action.group_id(),
+                &error.to_string(),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        tracing::error!(error = %e, "Failed to record Telegram dead letter");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Uuid;
+    use teloxide::types::{InlineKeyboardButtonKind, Seconds};
+
+    use super::*;
+    use crate::database::models::AllowedGuild;
+    use crate::database::models::UserLinkPayload;
+    use crate::test_helpers::{default_guild_id, insert_telegram_group};
+
+    #[test]
+    fn test_start_keyboard_requests_invite() {
+        let keyboard = start_keyboard();
+        let button = &keyboard.inline_keyboard[0][0];
+
+        assert_eq!(
+            button.kind,
+            InlineKeyboardButtonKind::CallbackData(SEND_INVITE_CALLBACK.to_string())
+        );
+    }
+
+    #[test]
+    fn test_bot_commands_have_descriptions() {
+        let commands = Command::bot_commands();
+
+        let names = commands
+            .iter()
+            .map(|c| c.command.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["/start", "/status"]);
+        assert!(commands.iter().all(|c| !c.description.is_empty()));
+    }
+
+    #[test]
+    fn test_error_message() {
+        let retry_after = RequestError::RetryAfter(Seconds::from_seconds(5));
+        assert_eq!(
+            error_message(&retry_after),
+            "Muita gente falando comigo agora, tenta de novo daqui a pouquinho"
+        );
+
+        let migrated = RequestError::MigrateToChatId(ChatId(-100));
+        assert_eq!(error_message(&migrated), "Algo deu errado, tente novamente");
+    }
+
+    #[test]
+    fn test_only_transient_errors_are_retried() {
+        assert!(is_transient(&RequestError::RetryAfter(
+            Seconds::from_seconds(5)
+        )));
+        assert!(!is_transient(&RequestError::MigrateToChatId(ChatId(-100))));
+        assert!(!is_transient(&RequestError::Api(ApiError::BotBlocked)));
+    }
+
+    #[test]
+    fn test_member_permissions() {
+        let read_only = member_permissions(true);
+        assert!(!read_only.can_send_messages());
+        assert!(!read_only.can_send_media_messages());
+        assert!(!read_only.can_send_polls());
+        assert!(!read_only.can_invite_users());
+
+        let lifted = member_permissions(false);
+        assert!(lifted.can_send_messages());
+        assert!(lifted.can_send_media_messages());
+        assert!(lifted.can_send_polls());
+    }
+
+    fn invite(telegram_id: i64) -> TelegramAction {
+        TelegramAction::InviteUser {
+            telegram_id,
+            group_id: -100,
+            invite_message: None,
+        }
+    }
+
+    #[test]
+    fn test_invite_message_uses_group_override() {
+        let link = "https://t.me/+abc";
+
+        let default = make_invite_message(link, None, None);
+        assert!(default.starts_with("<b>Oi! aqui tá seu link de convite</b>"));
+        assert!(default.contains(link));
+
+        let custom = make_invite_message(
+            link,
+            Some("Bem-vindo ao grupo da <Carol>"),
+            Some("<b>Convite</b>"),
+        );
+        assert!(custom.starts_with("Bem-vindo ao grupo da &lt;Carol&gt;"));
+        assert!(!custom.contains("Oi! aqui tá seu link de convite"));
+        assert!(custom.contains(link));
+
+        let templated = make_invite_message(link, None, Some("<b>Convite</b>"));
+        assert!(templated.starts_with("<b>Convite</b>"));
+        assert!(templated.contains(link));
+    }
+
+    #[sqlx::test]
+    async fn test_find_group_template_prefers_the_group_guild(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, -100, 'Grupo' FROM allowed_guilds WHERE guild_id = 258648784039313408
+            RETURNING allowed_guild_id",
+        )
+        .fetch_one(conn.as_mut())
+        .await
+        .unwrap();
+
+        let name = MessageTemplate::INVITE;
+        MessageTemplate::upsert(&mut conn, name, "Global", None)
+            .await
+            .unwrap();
+        MessageTemplate::upsert(&mut conn, name, "Felpinho", Some(guild_id))
+            .await
+            .unwrap();
+
+        let template = find_group_template(&mut conn, name, Some(-100)).await;
+        assert_eq!(template.unwrap().as_deref(), Some("Felpinho"));
+
+        let template = find_group_template(&mut conn, name, Some(-200)).await;
+        assert_eq!(template.unwrap().as_deref(), Some("Global"));
+
+        let template = find_group_template(&mut conn, MessageTemplate::WELCOME, None).await;
+        assert_eq!(template.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_process_actions_preserves_per_user_order() {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        sender.send(invite(1)).unwrap();
+        sender
+            .send(TelegramAction::RemoveUser {
+                telegram_id: 1,
+                group_id: 1,
+            })
+            .unwrap();
+        sender.send(invite(2)).unwrap();
+        sender.send(invite(1)).unwrap();
+        drop(sender);
+
+        let processed = process_actions(receiver, 5, |action| {
+            let events = events.clone();
+            async move {
+                let name = match action {
+                    TelegramAction::InviteUser { telegram_id, .. } => {
+                        format!("invite {telegram_id}")
+                    }
+                    TelegramAction::RemoveUser { telegram_id, .. } => {
+                        format!("remove {telegram_id}")
+                    }
+                    TelegramAction::RestrictUser { telegram_id, .. } => {
+                        format!("restrict {telegram_id}")
+                    }
+                    TelegramAction::Broadcast { group_id, .. } => {
+                        format!("broadcast {group_id}")
+                    }
+                };
+                events.lock().unwrap().push(format!("start {name}"));
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                events.lock().unwrap().push(format!("end {name}"));
+            }
+        })
+        .await;
+
+        let events = events.lock().unwrap().clone();
+        let position = |event: &str| events.iter().position(|e| e == event).unwrap();
+        let user_one_events = events
+            .iter()
+            .filter(|event| event.ends_with(" 1"))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        assert_eq!(processed, 4);
+        assert_eq!(
+            user_one_events,
+            [
+                "start invite 1",
+                "end invite 1",
+                "start remove 1",
+                "end remove 1",
+                "start invite 1",
+                "end invite 1",
+            ]
+        );
+        assert!(position("start invite 2") < position("end invite 1"));
+    }
+
+    #[test]
+    fn test_broadcasts_are_ordered_per_group() {
+        let broadcast = TelegramAction::Broadcast {
+            group_id: -100,
+            html: "Oi".to_string(),
+        };
+
+        assert_eq!(broadcast.ordering_key(), -100);
+        assert_eq!(invite(1).ordering_key(), 1);
+    }
+
+    #[test]
+    fn test_validate_html() {
+        assert_eq!(validate_html("Live <b>agora</b> &lt;3"), Ok(()));
+        assert_eq!(
+            validate_html("<a href=\"https://twitch.tv\"><i>Entra</i></a>"),
+            Ok(())
+        );
+        assert_eq!(
+            validate_html("<div>oi</div>"),
+            Err(HtmlError::UnsupportedTag("div".to_string()))
+        );
+        assert_eq!(
+            validate_html("<b>oi"),
+            Err(HtmlError::UnclosedTag("b".to_string()))
+        );
+        assert_eq!(
+            validate_html("<b><i>oi</b></i>"),
+            Err(HtmlError::UnexpectedClosingTag("b".to_string()))
+        );
+        assert_eq!(validate_html("1 < 2"), Err(HtmlError::UnescapedBracket));
+    }
+
+    #[test]
+    fn test_strip_html_keeps_text_and_links() {
+        let html = [
+            "<b>Opa @&lt;Felps &amp; cia&gt;</b>",
+            "",
+            "<a href=\"https://t.me/+abc\">Clique aqui pra entrar no grupo</a>",
+        ]
+        .join("\n");
+
+        assert_eq!(
+            strip_html(&html),
+            "Opa @<Felps & cia>\n\nClique aqui pra entrar no grupo (https://t.me/+abc)"
+        );
+        assert_eq!(strip_html("sem tags 🐸"), "sem tags 🐸");
+        assert_eq!(strip_html("quebrado <b"), "quebrado <b");
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("felps"), "felps");
+        assert_eq!(
+            escape_html("<b>Tom & \"Jerry\"</b>"),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+        );
+        assert_eq!(escape_html("🐸 <3 meia"), "🐸 &lt;3 meia");
+    }
+
+    #[test]
+    fn test_help_message_escapes_first_name() {
+        let user = User {
+            id: UserId(1),
+            is_bot: false,
+            first_name: "<Felps & cia>".to_string(),
+            last_name: None,
+            username: None,
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        };
+
+        let message = make_help_message(&Env::empty(), user.clone(), None);
+
+        assert!(message.contains("Opa @&lt;Felps &amp; cia&gt;,"));
+        assert!(!message.contains("<Felps"));
+
+        let message = make_help_message(&Env::empty(), user, Some("<b>Oi {usuario}</b>"));
+        assert!(message.starts_with("<b>Oi &lt;Felps &amp; cia&gt;</b>"));
+        assert!(message.contains("Linkar minha conta!"));
+    }
+
+    #[test]
+    fn test_status_message_for_unlinked_user() {
+        let message = make_status_message(None, None);
+
+        assert!(message.contains("ainda não tá vinculada"));
+        assert!(message.contains("/start"));
+
+        let message = make_status_message(None, Some("Manda /start aí"));
+        assert_eq!(message, "Manda /start aí");
+    }
+
+    #[sqlx::test]
+    async fn test_status_message_for_linked_user(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        let payload = UserLinkPayload::new(555, 777, guild_id);
+        let user_link = UserLink::create_link(&mut conn, payload).await.unwrap();
+
+        let found = UserLink::find_by_telegram_id(&mut conn, 777).await.unwrap();
+        let message = make_status_message(found.as_ref(), None);
+        assert!(message.contains("<code>555</code>"));
+        assert!(message.contains("Ainda não foi adicionado"));
+
+        UserLink::mark_added_to_group(&mut conn, &user_link.id)
+            .await
+            .unwrap();
+        let found = UserLink::find_by_telegram_id(&mut conn, 777).await.unwrap();
+        let message = make_status_message(found.as_ref(), None);
+        assert!(message.contains("Adicionado ao grupo em"));
+
+        let missing = UserLink::find_by_telegram_id(&mut conn, 778).await.unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_invite_group_for_uses_the_user_guild(pool: PgPool) {
+        let mut env = Env::empty();
+        env.telegram_group_id = -100;
+        let mut conn = pool.acquire().await.unwrap();
+        let felpinho_id = default_guild_id(&mut conn).await;
+        let teste = AllowedGuild::find_by_guild_id(&mut conn, 1355012226355957780)
+            .await
+            .unwrap()
+            .unwrap();
+        let felpinho = UserLink::create_link(&mut conn, UserLinkPayload::new(1, 2, felpinho_id))
+            .await
+            .unwrap();
+        let teste_user = UserLink::create_link(&mut conn, UserLinkPayload::new(3, 4, teste.id))
+            .await
+            .unwrap();
+
+        // Without any group every user is invited to the one configured in the environment
+        let group_id = invite_group_for(&env, &mut conn, &teste_user)
+            .await
+            .unwrap();
+        assert_eq!(group_id, Some(-100));
+
+        insert_telegram_group(&mut conn, 258648784039313408, -1001).await;
+        insert_telegram_group(&mut conn, 1355012226355957780, -1002).await;
+        insert_telegram_group(&mut conn, 1355012226355957780, -1003).await;
+
+        let group_id = invite_group_for(&env, &mut conn, &felpinho).await.unwrap();
+        assert_eq!(group_id, Some(-1001));
+        let group_id = invite_group_for(&env, &mut conn, &teste_user)
+            .await
+            .unwrap();
+        assert_eq!(group_id, Some(-1002));
+
+        // Older links go to the group mapped to their last known roles
+        sqlx::query(
+            "INSERT INTO role_group_mappings (role_id, group_id)
+            SELECT allowed_roles.id, telegram_groups.id FROM allowed_roles, telegram_groups
+            WHERE allowed_roles.role_id = 258661569200652289
+            AND telegram_groups.telegram_group_id = -1003",
+        )
+        .execute(conn.as_mut())
+        .await
+        .unwrap();
+        UserLink::set_discord_roles(&mut conn, &teste_user.id, &[258661569200652289])
+            .await
+            .unwrap();
+        let teste_user = UserLink::find_by_id(&mut conn, teste_user.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let group_id = invite_group_for(&env, &mut conn, &teste_user)
+            .await
+            .unwrap();
+        assert_eq!(group_id, Some(-1003));
+
+        // Links that kept their group are invited back to it
+        let payload = UserLinkPayload::new(5, 6, teste.id).with_telegram_group(-1002);
+        let kept = UserLink::create_link(&mut conn, payload).await.unwrap();
+        let group_id = invite_group_for(&env, &mut conn, &kept).await.unwrap();
+        assert_eq!(group_id, Some(-1002));
+    }
+
+    #[sqlx::test]
+    async fn test_group_joins_are_recorded(pool: PgPool) {
+        let mut env = Env::empty();
+        env.telegram_group_id = -100;
+        let mut conn = pool.acquire().await.unwrap();
+        let guild_id = default_guild_id(&mut conn).await;
+        UserLink::create_link(&mut conn, UserLinkPayload::new(555, 777, guild_id))
+            .await
+            .unwrap();
+
+        let member = |id: u64| User {
+            id: UserId(id),
+            is_bot: false,
+            first_name: "Felps".to_string(),
+            last_name: None,
+            username: None,
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        };
+        let joined_at = async |conn: &mut PgConnection| {
+            UserLink::find_by_telegram_id(conn, 777)
+                .await
+                .unwrap()
+                .unwrap()
+                .joined_group_at
+        };
+
+        // Joins of other chats are not joins of the group
+        record_group_joins(&env, &pool, ChatId(123), &[member(777)]).await;
+        assert_eq!(joined_at(&mut conn).await, None);
+
+        record_group_joins(&env, &pool, ChatId(-100), &[member(1), member(777)]).await;
+        assert!(joined_at(&mut conn).await.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_is_group_chat(pool: PgPool) {
+        let mut env = Env::empty();
+        env.telegram_group_id = -100;
+
+        sqlx::query(
+            "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+            SELECT id, $1, 'Grupo do Felps' FROM allowed_guilds WHERE guild_id = 258648784039313408",
+        )
+        .bind(-200_i64)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(is_group_chat(&env, &pool, ChatId(-100)).await);
+        assert!(is_group_chat(&env, &pool, ChatId(-200)).await);
+        assert!(!is_group_chat(&env, &pool, ChatId(123)).await);
+    }
 }
@@ -81,6 +81,12 @@ pub fn base_layout(title: &str, content: Markup) -> Markup {
                         border-radius: 8px;
                     }
 
+                    .request-id {
+                        color: var(--text-secondary);
+                        font-size: 12px;
+                        margin: 32px 0 0;
+                    }
+
                     a {
                         color: var(--accent-link);
                         text-decoration: none;
@@ -2,4 +2,7 @@ mod layout;
 mod oauth;
 
 pub use layout::base_layout;
-pub use oauth::{oauth_error_page, oauth_success_page};
+pub use oauth::{
+    oauth_already_linked_page, oauth_error_page, oauth_no_group_page, oauth_start_missing_page,
+    oauth_success_page,
+};
@@ -15,10 +15,52 @@ pub fn oauth_success_page(username: &str) -> Markup {
     base_layout("Account Linked", content)
 }
 
-pub fn oauth_error_page(error_message: &str) -> Markup {
+/// Shown when the callback is repeated after the accounts were linked
+pub fn oauth_already_linked_page() -> Markup {
+    let content = html! {
+        div class="success" { "Already Linked" }
+        p { "These accounts are already linked." }
+        p class="info" { "You can close this window and return to Telegram." }
+    };
+
+    base_layout("Already Linked", content)
+}
+
+/// Shown when none of the user's Discord servers has a Telegram group yet, nothing was linked
+pub fn oauth_no_group_page() -> Markup {
+    let content = html! {
+        div class="error" { "No Group Available" }
+        p { "None of your Discord servers has a Telegram group set up yet, so your accounts were not linked." }
+        p class="info" { "Ask the Telegram bot for a new link once the server admins set up a group." }
+    };
+
+    base_layout("No Group Available", content)
+}
+
+/// Shown when `/oauth/start` is opened without the telegram id the bot puts in the link
+pub fn oauth_start_missing_page(bot_username: Option<&str>) -> Markup {
+    let content = html! {
+        div class="error" { "Start From Telegram" }
+        p { "Accounts can only be linked from the link the Telegram bot sends you." }
+        @match bot_username {
+            Some(username) => p class="info" {
+                "Open " a href={ "https://t.me/" (username) } { "@" (username) } " and ask for a new link."
+            },
+            None => p class="info" { "Open the Telegram bot and ask for a new link." },
+        }
+    };
+
+    base_layout("Start From Telegram", content)
+}
+
+/// `request_id` is shown so users can quote it to support, who can find the request in the logs
+pub fn oauth_error_page(error_message: &str, request_id: Option<&str>) -> Markup {
     let content = html! {
         div class="error" { "Error" }
         p class="message" { (error_message) }
+        @if let Some(request_id) = request_id {
+            p class="request-id" { "Request id: " code { (request_id) } }
+        }
     };
 
     base_layout("Error", content)
@@ -0,0 +1,242 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use axum::extract::{Query, State};
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::error::TryRecvError;
+
+use crate::api::AppState;
+use crate::api::error::{ApiError, Result};
+use crate::api::oauth::OAuthStartQueryParams;
+use crate::env::Env;
+use crate::messages::{CronAction, TelegramAction};
+use crate::services::BoxFuture;
+use crate::services::discord::{
+    DiscordService, DiscordTokenResponse, DiscordUser, MEMBER_ROLES_SCOPE,
+};
+
+pub struct TestContext<D: DiscordService> {
+    pub params: Query<OAuthStartQueryParams>,
+    pub state: State<AppState<D>>,
+    pub cron_receiver: UnboundedReceiver<CronAction>,
+    pub telegram_receiver: UnboundedReceiver<TelegramAction>,
+}
+
+impl<D: DiscordService> TestContext<D> {
+    pub fn with_discord_service(
+        pool: PgPool,
+        params: OAuthStartQueryParams,
+        discord_service: D,
+    ) -> TestContext<D> {
+        let params = Query(params);
+        let (cron_sender, cron_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (telegram_sender, telegram_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let env = Arc::new(Env::empty());
+
+        let state = State(AppState {
+            telegram_sender,
+            cron_sender,
+            env,
+            pool,
+            discord_service: Arc::new(discord_service),
+            maintenance: Arc::new(AtomicBool::new(false)),
+        });
+
+        TestContext {
+            params,
+            state,
+            cron_receiver,
+            telegram_receiver,
+        }
+    }
+
+    pub fn with_env(mut self, env: Env) -> Self {
+        self.state.0.env = Arc::new(env);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_telegram_received(&mut self, expected: TelegramAction) {
+        match self.telegram_receiver.try_recv() {
+            Ok(action) => assert_eq!(action, expected),
+            Err(e) => panic!("expected telegram action {expected:?}, got {e}"),
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_no_telegram_action(&mut self) {
+        match self.telegram_receiver.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            other => panic!("expected no telegram action, got {other:?}"),
+        }
+    }
+}
+
+/// Id of the seeded "Server do Felpinho" guild, every link has to belong to a guild
+pub async fn default_guild_id(conn: &mut PgConnection) -> Uuid {
+    sqlx::query_scalar("SELECT id FROM allowed_guilds WHERE guild_id = 258648784039313408")
+        .fetch_one(conn)
+        .await
+        .unwrap()
+}
+
+/// Adds a telegram group to the allowed guild with the discord id `guild_id`
+pub async fn insert_telegram_group(conn: &mut PgConnection, guild_id: i64, chat_id: i64) {
+    sqlx::query(
+        "INSERT INTO telegram_groups (allowed_guild_id, telegram_group_id, name)
+        SELECT id, $1, 'Grupo' FROM allowed_guilds WHERE guild_id = $2",
+    )
+    .bind(chat_id)
+    .bind(guild_id)
+    .execute(conn)
+    .await
+    .unwrap();
+}
+
+pub fn setup_test(
+    pool: PgPool,
+    params: OAuthStartQueryParams,
+    discord_service: MockDiscordService,
+) -> TestContext<MockDiscordService> {
+    TestContext::with_discord_service(pool, params, discord_service)
+}
+
+#[derive(Debug, Clone)]
+pub struct MockDiscordService {
+    discord_user: DiscordUser,
+    should_fail_token: bool,
+    should_fail_user_info: bool,
+    guild_ids: Vec<i64>,
+    role_ids: Vec<i64>,
+    scope: String,
+}
+
+impl MockDiscordService {
+    pub fn new() -> Self {
+        Self {
+            discord_user: DiscordUser {
+                id: "123".to_string(),
+                username: "test_user".to_string(),
+            },
+            should_fail_token: false,
+            should_fail_user_info: false,
+            guild_ids: Vec::new(),
+            role_ids: Vec::new(),
+            scope: "identify".to_string(),
+        }
+    }
+
+    pub fn with_failing_token(mut self) -> Self {
+        self.should_fail_token = true;
+        self
+    }
+
+    pub fn with_failing_user_info(mut self) -> Self {
+        self.should_fail_user_info = true;
+        self
+    }
+
+    pub fn with_guilds(mut self, guild_ids: Vec<i64>) -> Self {
+        self.guild_ids = guild_ids;
+        self
+    }
+
+    /// Roles the user has in every guild they are a member of
+    pub fn with_roles(mut self, role_ids: Vec<i64>) -> Self {
+        self.role_ids = role_ids;
+        self
+    }
+
+    /// Grants `guilds.members.read` along with `identify`
+    pub fn with_member_roles_scope(mut self) -> Self {
+        self.scope = format!("identify {MEMBER_ROLES_SCOPE}");
+        self
+    }
+
+    fn roles_in(&self, guild_id: i64) -> Option<Vec<i64>> {
+        self.guild_ids
+            .contains(&guild_id)
+            .then(|| self.role_ids.clone())
+    }
+}
+
+impl DiscordService for MockDiscordService {
+    fn get_oauth_url(&self, env: &Env, token: &str) -> String {
+        format!(
+            "https://discord.com/api/oauth2/authorize?client_id={}&redirect_uri={}&response_type=code&scope=identify&state={}",
+            &env.discord_client_id,
+            urlencoding::encode(&env.discord_oauth_redirect),
+            token,
+        )
+    }
+
+    fn get_access_token(
+        &self,
+        _: Arc<Env>,
+        _: String,
+    ) -> BoxFuture<'_, Result<DiscordTokenResponse>> {
+        let should_fail = self.should_fail_token;
+        let scope = self.scope.clone();
+        Box::pin(async move {
+            if should_fail {
+                Err(ApiError::discord_api("Failed to get access token".into()))
+            } else {
+                Ok(DiscordTokenResponse {
+                    access_token: "sample_access_token".into(),
+                    refresh_token: Some("sample_refresh_token".into()),
+                    expires_in: Some(604800),
+                    scope,
+                })
+            }
+        })
+    }
+
+    fn get_user_info(&self, _: String) -> BoxFuture<'_, Result<DiscordUser>> {
+        let should_fail = self.should_fail_user_info;
+        let user = self.discord_user.clone();
+        Box::pin(async move {
+            if should_fail {
+                Err(ApiError::discord_api("Failed to get user info".into()))
+            } else {
+                Ok(user)
+            }
+        })
+    }
+
+    fn get_member_roles(
+        &self,
+        _: Arc<Env>,
+        guild_id: i64,
+        _: i64,
+    ) -> BoxFuture<'_, Result<Option<Vec<i64>>>> {
+        let roles = self.roles_in(guild_id);
+        Box::pin(async move { Ok(roles) })
+    }
+
+    fn get_user_guild_member(
+        &self,
+        _: String,
+        guild_id: i64,
+    ) -> BoxFuture<'_, Result<Option<Vec<i64>>>> {
+        let roles = self.roles_in(guild_id);
+        Box::pin(async move { Ok(roles) })
+    }
+
+    fn refresh_access_token(
+        &self,
+        _: Arc<Env>,
+        _: String,
+    ) -> BoxFuture<'_, Result<DiscordTokenResponse>> {
+        let scope = self.scope.clone();
+        Box::pin(async move {
+            Ok(DiscordTokenResponse {
+                access_token: "refreshed_access_token".into(),
+                refresh_token: Some("refreshed_refresh_token".into()),
+                expires_in: Some(604800),
+                scope,
+            })
+        })
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use sqlx::PgConnection;
+
+use crate::services::BoxFuture;
+
+/// `serialization_failure`, raised when concurrent transactions conflict
+const SERIALIZATION_FAILURE: &str = "40001";
+/// `deadlock_detected`, raised on the transaction postgres aborts to break a deadlock
+const DEADLOCK_DETECTED: &str = "40P01";
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Runs `f` on `conn` again, up to `max_retries` times, while it fails with an error postgres
+/// says is worth retrying. Any other error, or the last transient one, is returned as is.
+///
+/// Each attempt runs in a savepoint when `conn` is already in a transaction, postgres aborts the
+/// whole transaction on an error and the next attempt could never succeed otherwise.
+///
+/// A dropped connection is not retried, `conn` is unusable after it and running `f` on it again
+/// would only fail the same way.
+pub async fn with_db_retry<F, T>(
+    conn: &mut PgConnection,
+    mut f: F,
+    max_retries: u32,
+) -> sqlx::Result<T>
+where
+    F: FnMut(&mut PgConnection) -> BoxFuture<'_, sqlx::Result<T>>,
+{
+    let mut retries = 0;
+
+    loop {
+        let mut attempt = sqlx::Connection::begin(&mut *conn).await?;
+
+        match f(attempt.as_mut()).await {
+            Ok(value) => {
+                attempt.commit().await?;
+                return Ok(value);
+            }
+            Err(e) if retries < max_retries && is_transient(&e) => {
+                attempt.rollback().await?;
+                retries += 1;
+                tracing::warn!(error = %e, retry = retries, "Transient database error, retrying");
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+            Err(e) => {
+                attempt.rollback().await?;
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(e) => matches!(
+            e.code().as_deref(),
+            Some(SERIALIZATION_FAILURE | DEADLOCK_DETECTED)
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn raise(conn: &mut PgConnection, code: &str) -> sqlx::Result<()> {
+        let query =
+            format!("DO $$ BEGIN RAISE EXCEPTION 'failed' USING ERRCODE = '{code}'; END $$");
+        sqlx::query(&query).execute(conn).await?;
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_serialization_failure_is_retried(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let mut calls = 0;
+
+        let result = with_db_retry(
+            &mut conn,
+            |conn| {
+                calls += 1;
+                let fail = calls == 1;
+                Box::pin(async move {
+                    match fail {
+                        true => raise(conn, SERIALIZATION_FAILURE).await,
+                        false => Ok(()),
+                    }
+                })
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(calls, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_serialization_failure_is_retried_inside_a_transaction(pool: PgPool) {
+        let mut tx = pool.begin().await.unwrap();
+        let mut calls = 0;
+
+        let result = with_db_retry(
+            tx.as_mut(),
+            |conn| {
+                calls += 1;
+                let fail = calls == 1;
+                Box::pin(async move {
+                    match fail {
+                        true => raise(conn, SERIALIZATION_FAILURE).await,
+                        false => sqlx::query("SELECT 1").execute(conn).await.map(|_| ()),
+                    }
+                })
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(calls, 2);
+
+        // The failed attempt didn't abort the transaction it ran in
+        let value: i32 = sqlx::query_scalar("SELECT 1")
+            .fetch_one(tx.as_mut())
+            .await
+            .unwrap();
+        assert_eq!(value, 1);
+        tx.commit().await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_gives_up_after_max_retries(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let mut calls = 0;
+
+        let result = with_db_retry(
+            &mut conn,
+            |conn| {
+                calls += 1;
+                Box::pin(raise(conn, DEADLOCK_DETECTED))
+            },
+            2,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[sqlx::test]
+    async fn test_other_errors_are_not_retried(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let mut calls = 0;
+
+        // unique_violation
+        let result = with_db_retry(
+            &mut conn,
+            |conn| {
+                calls += 1;
+                Box::pin(raise(conn, "23505"))
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[sqlx::test]
+    async fn test_connection_failure_is_not_retried(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let mut calls = 0;
+
+        // connection_failure
+        let result = with_db_retry(
+            &mut conn,
+            |conn| {
+                calls += 1;
+                Box::pin(raise(conn, "08006"))
+            },
+            3,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
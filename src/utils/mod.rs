@@ -0,0 +1,27 @@
+pub mod db_retry;
+pub mod pagination;
+pub mod retry;
+pub mod snowflake;
+pub mod supervisor;
+
+use sqlx::PgConnection;
+
+/// Runs `f` in a transaction, committed if it succeeds and rolled back if it fails.
+///
+/// Generic over the error so the cron, the API and discord commands can all use it with their
+/// own error type.
+pub async fn with_tx<F, T, E>(conn: &mut PgConnection, f: F) -> Result<T, E>
+where
+    F: AsyncFnOnce(&mut PgConnection) -> Result<T, E>,
+    E: From<sqlx::Error>,
+{
+    let mut tx = sqlx::Connection::begin(conn).await?;
+    let result = f(tx.as_mut()).await;
+
+    match result {
+        Ok(_) => tx.commit().await?,
+        Err(_) => tx.rollback().await?,
+    }
+
+    result
+}
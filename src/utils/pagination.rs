@@ -0,0 +1,219 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+const MAX_CURSOR_LIMIT: i64 = 100;
+
+/// Position right after the last row of a page, rows are ordered by `created_at` then `id`
+/// so the cursor stays stable while rows are inserted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+// Encoded as `<created_at in microseconds>_<id>` so it needs no escaping in a query string,
+// postgres timestamps have microsecond precision so nothing is lost
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.created_at.timestamp_micros(), self.id)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid cursor: {s}");
+        let (micros, id) = s.split_once('_').ok_or_else(invalid)?;
+        let micros = micros.parse().map_err(|_| invalid())?;
+
+        Ok(Self {
+            created_at: DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?,
+            id: Uuid::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Offset pagination read from the query string, `limit` is clamped to `1..=200`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "RawPaginationParams")]
+pub struct PaginationParams {
+    pub offset: i64,
+    pub limit: i64,
+}
+
+#[derive(Deserialize)]
+struct RawPaginationParams {
+    #[serde(default)]
+    offset: i64,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    DEFAULT_LIMIT
+}
+
+impl From<RawPaginationParams> for PaginationParams {
+    fn from(raw: RawPaginationParams) -> Self {
+        Self {
+            offset: raw.offset.max(0),
+            limit: raw.limit.clamp(1, MAX_LIMIT),
+        }
+    }
+}
+
+impl Default for PaginationParams {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T: Serialize> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+    pub has_more: bool,
+}
+
+impl<T: Serialize> From<(Vec<T>, i64, PaginationParams)> for Page<T> {
+    fn from((items, total, params): (Vec<T>, i64, PaginationParams)) -> Self {
+        let has_more = params.offset + (items.len() as i64) < total;
+
+        Self {
+            items,
+            total,
+            offset: params.offset,
+            limit: params.limit,
+            has_more,
+        }
+    }
+}
+
+/// Keyset pagination read from the query string, `limit` is clamped to `1..=100`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawCursorParams")]
+pub struct CursorParams {
+    pub cursor: Option<Cursor>,
+    pub limit: i64,
+}
+
+#[derive(Deserialize)]
+struct RawCursorParams {
+    cursor: Option<String>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+impl TryFrom<RawCursorParams> for CursorParams {
+    type Error = String;
+
+    fn try_from(raw: RawCursorParams) -> Result<Self, Self::Error> {
+        Ok(Self {
+            cursor: raw.cursor.as_deref().map(str::parse).transpose()?,
+            limit: raw.limit.clamp(1, MAX_CURSOR_LIMIT),
+        })
+    }
+}
+
+impl Default for CursorParams {
+    fn default() -> Self {
+        Self {
+            cursor: None,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CursorPage<T: Serialize> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+impl<T: Serialize> CursorPage<T> {
+    pub fn new(items: Vec<T>, next_cursor: Option<Cursor>) -> Self {
+        Self {
+            items,
+            next_cursor: next_cursor.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> PaginationParams {
+        serde_json::from_str(query).unwrap()
+    }
+
+    fn parse_cursor(query: &str) -> CursorParams {
+        serde_json::from_str(query).unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(parse("{}"), PaginationParams::default());
+        assert_eq!(parse_cursor("{}"), CursorParams::default());
+    }
+
+    #[test]
+    fn test_limit_is_clamped() {
+        assert_eq!(parse(r#"{"limit": 1000}"#).limit, MAX_LIMIT);
+        assert_eq!(parse(r#"{"limit": 0}"#).limit, 1);
+        assert_eq!(parse(r#"{"offset": -5}"#).offset, 0);
+        assert_eq!(parse_cursor(r#"{"limit": 1000}"#).limit, MAX_CURSOR_LIMIT);
+        assert_eq!(parse_cursor(r#"{"limit": 0}"#).limit, 1);
+    }
+
+    #[test]
+    fn test_has_more() {
+        let params = PaginationParams {
+            offset: 2,
+            limit: 2,
+        };
+
+        let page = Page::from((vec![1, 2], 5, params));
+        assert!(page.has_more);
+
+        let page = Page::from((vec![1, 2], 4, params));
+        assert!(!page.has_more);
+    }
+
+    #[test]
+    fn test_cursor_round_trips() {
+        let cursor = Cursor {
+            created_at: DateTime::from_timestamp_micros(1_760_000_000_123_456).unwrap(),
+            id: Uuid::new_v4(),
+        };
+
+        let query = format!(r#"{{"cursor": "{cursor}"}}"#);
+        assert_eq!(parse_cursor(&query).cursor, Some(cursor));
+    }
+
+    #[test]
+    fn test_invalid_cursor_is_rejected() {
+        for cursor in [
+            "",
+            "abc",
+            "123_not-a-uuid",
+            "_00000000-0000-0000-0000-000000000000",
+        ] {
+            let query = format!(r#"{{"cursor": "{cursor}"}}"#);
+            assert!(serde_json::from_str::<CursorParams>(&query).is_err());
+        }
+    }
+}
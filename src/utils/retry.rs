@@ -0,0 +1,168 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Exponential backoff bounds for [`retry`]
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Stop retrying once this much time has passed since the first attempt
+    pub max_elapsed: Duration,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Stop retrying after this many attempts, even with time left in the budget
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_elapsed: Duration::from_secs(30),
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            max_attempts: u32::MAX,
+        }
+    }
+}
+
+/// Runs `f` until it succeeds or the policy's time budget is spent, returning the last error.
+pub async fn retry<T, E, F, Fut>(operation: &str, policy: RetryPolicy, f: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_if(operation, policy, |_| true, f).await
+}
+
+/// Like [`retry`], but errors `is_retryable` rejects are returned right away
+pub async fn retry_if<T, E, F, Fut, P>(
+    operation: &str,
+    policy: RetryPolicy,
+    is_retryable: P,
+    mut f: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: Fn(&E) -> bool,
+{
+    let start = Instant::now();
+    let mut delay = policy.initial_delay;
+    let mut attempt = 1;
+
+    loop {
+        let error = match f().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        if !is_retryable(&error) {
+            return Err(error);
+        }
+
+        if attempt >= policy.max_attempts || start.elapsed() + delay > policy.max_elapsed {
+            tracing::error!(error = %error, operation = operation, attempt = attempt, "Giving up after retries");
+            return Err(error);
+        }
+
+        tracing::warn!(
+            error = %error,
+            operation = operation,
+            attempt = attempt,
+            retry_in_ms = delay.as_millis(),
+            "Operation failed, retrying"
+        );
+
+        tokio::time::sleep(jittered(delay)).await;
+        delay = (delay * 2).min(policy.max_delay);
+        attempt += 1;
+    }
+}
+
+/// Somewhere between half and all of `delay`, so clients failing together don't retry together
+fn jittered(delay: Duration) -> Duration {
+    delay / 2 + delay.mul_f64(fastrand::f64() / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_elapsed: Duration::from_secs(1),
+        initial_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(4),
+        max_attempts: u32::MAX,
+    };
+
+    #[tokio::test]
+    async fn test_succeeds_after_failures() {
+        let mut calls = 0;
+
+        let result = retry("test", POLICY, || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                match attempt {
+                    1 | 2 => Err("not yet"),
+                    _ => Ok(attempt),
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_with_last_error() {
+        let policy = RetryPolicy {
+            max_elapsed: Duration::from_millis(20),
+            ..POLICY
+        };
+
+        let result: Result<(), _> = retry("test", policy, || async { Err("still down") }).await;
+
+        assert_eq!(result, Err("still down"));
+    }
+
+    #[tokio::test]
+    async fn test_stops_at_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            ..POLICY
+        };
+        let mut calls = 0;
+
+        let result: Result<(), _> = retry("test", policy, || {
+            calls += 1;
+            async { Err("down") }
+        })
+        .await;
+
+        assert_eq!(result, Err("down"));
+        assert_eq!(calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_unretryable_error_is_returned_right_away() {
+        let mut calls = 0;
+
+        let result: Result<(), _> = retry_if(
+            "test",
+            POLICY,
+            |error| *error != "bad input",
+            || {
+                calls += 1;
+                async { Err("bad input") }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err("bad input"));
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+
+/// Milliseconds since the unix epoch of the first second of 2015, where Discord ids start counting
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+/// When a Discord id (user, guild, role...) was created, taken from the timestamp in its upper
+/// 42 bits
+pub fn created_at(id: i64) -> Option<DateTime<Utc>> {
+    let since_discord_epoch = u64::try_from(id).ok()? >> 22;
+    DateTime::from_timestamp_millis(DISCORD_EPOCH_MS + since_discord_epoch as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_created_at_known_ids() {
+        // Example from Discord's API reference
+        assert_eq!(
+            created_at(175928847299117063),
+            Some(utc("2016-04-30T11:18:25.796Z"))
+        );
+        assert_eq!(
+            created_at(258648784039313408),
+            Some(utc("2016-12-14T17:37:55.577Z"))
+        );
+    }
+
+    #[test]
+    fn test_created_at_discord_epoch() {
+        assert_eq!(created_at(0), Some(utc("2015-01-01T00:00:00Z")));
+        assert_eq!(created_at(-1), None);
+    }
+}
@@ -0,0 +1,196 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::panic::{AssertUnwindSafe, PanicHookInfo};
+
+use futures::FutureExt;
+use tokio::task::{Id, JoinSet};
+
+/// Why a [`Supervisor`] stopped its services
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shutdown {
+    /// The named service returned or was cancelled
+    ServiceExited(&'static str),
+    /// The named service panicked with `message`
+    ServicePanicked {
+        service: &'static str,
+        message: String,
+    },
+    /// The shutdown future passed to [`Supervisor::run`] resolved
+    Requested,
+}
+
+/// Logs every panic with its location and a backtrace before unwinding starts. This still runs
+/// when built with `panic = "abort"`, where the supervisor never gets to see the panic
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info: &PanicHookInfo<'_>| {
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_default();
+
+        tracing::error!(
+            panic = panic_message(info.payload()),
+            location,
+            thread = std::thread::current().name().unwrap_or("unnamed"),
+            backtrace = %Backtrace::force_capture(),
+            "Thread panicked"
+        );
+    }));
+}
+
+/// Text a panic was raised with, panics with a format string carry a `String` and the rest
+/// a `&str`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+
+    match payload.downcast_ref::<String>() {
+        Some(message) => message.clone(),
+        None => String::from("non string panic payload"),
+    }
+}
+
+/// Owns the long running services of the app, none of them is expected to ever return, so
+/// as soon as one does every other one is aborted as well
+#[derive(Default)]
+pub struct Supervisor {
+    /// Each task resolves to the panic message when its service panicked
+    tasks: JoinSet<Result<(), String>>,
+    names: HashMap<Id, &'static str>,
+}
+
+impl Supervisor {
+    pub fn spawn<F>(&mut self, name: &'static str, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // Catching the panic inside the task keeps its message, a `JoinError` only has the
+        // payload as an opaque `Box<dyn Any>`
+        let future = AssertUnwindSafe(future)
+            .catch_unwind()
+            .map(|result| result.map_err(|payload| panic_message(payload.as_ref())));
+
+        let handle = self.tasks.spawn(future);
+        self.names.insert(handle.id(), name);
+        tracing::info!(service = name, "Service started");
+    }
+
+    /// Waits until a service exits or `shutdown` resolves, then aborts every remaining service
+    pub async fn run(mut self, shutdown: impl Future<Output = ()>) -> Shutdown {
+        let reason = tokio::select! {
+            Some(result) = self.tasks.join_next_with_id() => {
+                let id = match &result {
+                    Ok((id, _)) => *id,
+                    Err(e) => e.id(),
+                };
+                let name = self.names.get(&id).copied().unwrap_or("unknown");
+
+                match result {
+                    Ok((_, Ok(()))) => {
+                        tracing::error!(service = name, "Service exited unexpectedly");
+                        Shutdown::ServiceExited(name)
+                    }
+                    Ok((_, Err(message))) => {
+                        tracing::error!(service = name, panic = message, "Service panicked");
+                        Shutdown::ServicePanicked { service: name, message }
+                    }
+                    Err(e) => {
+                        tracing::error!(service = name, error = %e, "Service failed");
+                        Shutdown::ServiceExited(name)
+                    }
+                }
+            }
+            _ = shutdown => {
+                tracing::info!("Received shutdown signal, gracefully shutting down");
+                Shutdown::Requested
+            }
+        };
+
+        tracing::info!("Shutting down other services");
+        self.tasks.shutdown().await;
+
+        reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    /// Signals when the task holding it is dropped, which is what aborting it does
+    struct DropSignal(Option<oneshot::Sender<()>>);
+
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            if let Some(sender) = self.0.take() {
+                sender.send(()).ok();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exiting_service_aborts_the_others() {
+        let (dropped_sender, dropped_receiver) = oneshot::channel();
+        let mut supervisor = Supervisor::default();
+
+        let signal = DropSignal(Some(dropped_sender));
+        supervisor.spawn("long", async move {
+            let _signal = signal;
+            std::future::pending::<()>().await;
+        });
+        supervisor.spawn("short", async {});
+
+        let reason = supervisor.run(std::future::pending()).await;
+
+        assert_eq!(reason, Shutdown::ServiceExited("short"));
+        assert!(dropped_receiver.await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_request_aborts_every_service() {
+        let (dropped_sender, dropped_receiver) = oneshot::channel();
+        let mut supervisor = Supervisor::default();
+
+        let signal = DropSignal(Some(dropped_sender));
+        supervisor.spawn("long", async move {
+            let _signal = signal;
+            std::future::pending::<()>().await;
+        });
+
+        let reason = supervisor.run(async {}).await;
+
+        assert_eq!(reason, Shutdown::Requested);
+        assert!(dropped_receiver.await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_panicking_service_reports_its_message() {
+        let mut supervisor = Supervisor::default();
+        let user_id = 42;
+
+        supervisor.spawn("long", std::future::pending());
+        supervisor.spawn("panicking", async move {
+            panic!("failed to load user {user_id}");
+        });
+
+        let reason = supervisor.run(std::future::pending()).await;
+
+        assert_eq!(
+            reason,
+            Shutdown::ServicePanicked {
+                service: "panicking",
+                message: String::from("failed to load user 42"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_panic_message_of_static_str() {
+        let payload = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "boom");
+    }
+}